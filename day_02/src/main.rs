@@ -1,40 +1,28 @@
+use intcode::{loader, search::Search};
+use std::convert::TryFrom;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let file = File::open("day_02/input.txt")?;
-    let mut reader = BufReader::new(file);
-
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-
-    let intcode: Vec<u32> = line
-        .trim()
-        .split(',')
-        .map(|digit_str| digit_str.parse::<u32>().expect("Invalid input file"))
-        .collect();
+    let program = loader::load("day_02/input.txt")?;
+    let intcode = program
+        .iter()
+        .copied()
+        .map(u32::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
 
     let result_part_1 = run_part(&intcode, 12, 2)?;
     println!("Part 1: {}", result_part_1);
 
     let desired_result = 19690720;
 
-    let mut result_part_2 = None;
-
-    for noun in 0..100 {
-        for verb in 0..100 {
-            let result = run_part(&intcode, noun, verb)?;
-
-            if result == desired_result {
-                result_part_2 = Some((noun, verb))
-            }
-        }
-    }
+    let result_part_2 = Search::new(program)
+        .patch(1, 0..100)
+        .patch(2, 0..100)
+        .find_first(|observed| observed == [desired_result])?;
 
-    match result_part_2 {
-        Some((noun, verb)) => println!("Part 2: Noun={}, Verb={}", noun, verb),
-        None => println!("Part 2: No result found"),
+    match result_part_2.as_deref() {
+        Some([(_, noun), (_, verb)]) => println!("Part 2: Noun={}, Verb={}", noun, verb),
+        _ => println!("Part 2: No result found"),
     }
 
     Ok(())
@@ -112,6 +100,9 @@ fn run_intcode(memory: &mut [u32]) -> Result<(), &str> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use intcode::fuzz::{Generator, Rng};
+    use intcode::io::{adapter::IteratorInput, programmable::ProgrammableOutput};
+    use intcode::Program;
 
     #[test]
     fn parse_add_exact() {
@@ -246,4 +237,28 @@ mod tests {
         assert_eq!(result.is_ok(), true);
         assert_eq!(memory, [30, 1, 1, 4, 2, 5, 6, 0, 99]);
     }
+
+    #[test]
+    fn agrees_with_shared_program() {
+        let generator = Generator::new()
+            .opcodes(&[1, 2])
+            .position_only()
+            .values(0..100);
+        let mut rng = Rng::new(2);
+
+        for _ in 0..300 {
+            let generated = generator.generate(&mut rng);
+            let memory = &generated.case.memory;
+            let mut input = IteratorInput::new(Vec::new());
+            let mut output = ProgrammableOutput::new();
+            let mut program = Program::new(memory.clone(), &mut input, &mut output);
+            program.run().unwrap();
+
+            let mut day_02_memory: Vec<u32> = memory.iter().map(|value| *value as u32).collect();
+            run_intcode(&mut day_02_memory).unwrap();
+
+            let expected: Vec<u32> = program.memory().iter().map(|value| *value as u32).collect();
+            assert_eq!(day_02_memory, expected, "{}", generated);
+        }
+    }
 }
@@ -1,31 +1,44 @@
-use super::{
+use intcode::{
     io::programmable::{ProgrammableInput, ProgrammableOutput},
-    program::Program,
+    specialiser::{Specialisation, Specialiser},
+    threaded, Program,
 };
 use std::borrow::Cow;
+use std::collections::hash_map::{Entry, HashMap};
 
 pub struct Circuit {
     program: Vec<i32>,
+    amplifiers: HashMap<i32, Specialisation>,
 }
 
 impl Circuit {
     pub fn new(program: Vec<i32>) -> Self {
-        Self { program }
+        Self {
+            program,
+            amplifiers: HashMap::new(),
+        }
     }
 
-    pub fn run(&self, phase_settings_sequence: &[i32]) -> Result<i32, Cow<'static, str>> {
+    pub fn run(&mut self, phase_settings_sequence: &[i32]) -> Result<i32, Cow<'static, str>> {
         if phase_settings_sequence.len() < 2 {
-            return Err("There should be at least 2 items in the sequence".into());
+            Err("There should be at least 2 items in the sequence".into())
         } else {
             let mut last_output = 0;
 
             for (phase, phase_setting) in phase_settings_sequence.iter().enumerate() {
-                let mut phase_input = ProgrammableInput::new(vec![*phase_setting, last_output]);
+                let specialisation = self.amplifier(*phase_setting)?;
+                let mut inputs = specialisation.inputs.clone();
+                inputs.push(last_output);
+                let mut phase_input = ProgrammableInput::new(inputs);
                 let mut output = ProgrammableOutput::new();
 
                 {
-                    let mut amplifier =
-                        Program::new(self.program.clone(), &mut phase_input, &mut output);
+                    let mut amplifier = Program::with_entry_point(
+                        specialisation.memory.clone(),
+                        specialisation.entry_point,
+                        &mut phase_input,
+                        &mut output,
+                    );
                     amplifier.run()?;
                 }
 
@@ -39,6 +52,47 @@ impl Circuit {
             Ok(last_output)
         }
     }
+
+    pub fn run_with_feedback(
+        &self,
+        phase_settings_sequence: &[i32],
+    ) -> Result<i32, Cow<'static, str>> {
+        if phase_settings_sequence.len() < 2 {
+            Err("There should be at least 2 items in the sequence".into())
+        } else {
+            let amplifiers = phase_settings_sequence
+                .iter()
+                .enumerate()
+                .map(|(phase, phase_setting)| {
+                    let inputs = if phase == 0 {
+                        vec![*phase_setting, 0]
+                    } else {
+                        vec![*phase_setting]
+                    };
+                    (self.program.clone(), inputs)
+                })
+                .collect();
+
+            let completion = threaded::ring(amplifiers)?;
+
+            completion
+                .outputs
+                .last()
+                .copied()
+                .ok_or_else(|| "The feedback loop did not return any output".into())
+        }
+    }
+
+    fn amplifier(&mut self, phase_setting: i32) -> Result<&Specialisation, Cow<'static, str>> {
+        match self.amplifiers.entry(phase_setting) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let specialisation =
+                    Specialiser::new().specialise(&self.program, &[], &[phase_setting])?;
+                Ok(entry.insert(specialisation))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -46,12 +100,19 @@ mod tests {
     use super::*;
 
     fn run_day_07_part1_example(program: Vec<i32>, phase_setting: &[i32], expected_result: i32) {
-        let circuit = Circuit::new(program);
+        let mut circuit = Circuit::new(program);
         let result = circuit.run(phase_setting);
 
         assert_eq!(result, Ok(expected_result));
     }
 
+    fn run_day_07_part2_example(program: Vec<i32>, phase_setting: &[i32], expected_result: i32) {
+        let circuit = Circuit::new(program);
+        let result = circuit.run_with_feedback(phase_setting);
+
+        assert_eq!(result, Ok(expected_result));
+    }
+
     #[test]
     fn day_07_part1_example1() {
         run_day_07_part1_example(
@@ -86,4 +147,29 @@ mod tests {
             65210,
         );
     }
+
+    #[test]
+    fn day_07_part2_example1() {
+        run_day_07_part2_example(
+            vec![
+                3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28,
+                -1, 28, 1005, 28, 6, 99, 0, 0, 5,
+            ],
+            &[9, 8, 7, 6, 5],
+            139629729,
+        );
+    }
+
+    #[test]
+    fn day_07_part2_example2() {
+        run_day_07_part2_example(
+            vec![
+                3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001,
+                54, -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53,
+                55, 53, 4, 53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+            ],
+            &[9, 7, 8, 5, 6],
+            18216,
+        );
+    }
 }
@@ -1,44 +1,46 @@
 mod circuit;
-mod io;
-mod operations;
-mod program;
 
 use circuit::Circuit;
-use operations::Operation;
+use intcode::loader;
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::ops::Range;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let file = File::open("day_07/input.txt")?;
-    let mut reader = BufReader::new(file);
+    let int_code = loader::load("day_07/input.txt")?;
 
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
+    let mut circuit = Circuit::new(int_code);
 
-    let int_code = line
-        .trim()
-        .split(',')
-        .map(|digit_str| digit_str.parse::<i32>().expect("Invalid input file"))
-        .collect();
+    let part_1 = max_signal(0..5, |phase_sequence| circuit.run(phase_sequence))?;
+    println!("Part 1: {:?}", part_1);
 
-    let circuit = Circuit::new(int_code);
+    let part_2 = max_signal(5..10, |phase_sequence| {
+        circuit.run_with_feedback(phase_sequence)
+    })?;
+    println!("Part 2: {:?}", part_2);
 
+    Ok(())
+}
+
+fn max_signal<F>(phases: Range<i32>, mut run: F) -> Result<Option<i32>, Box<dyn Error>>
+where
+    F: FnMut(&[i32]) -> Result<i32, Cow<'static, str>>,
+{
     let mut max_value = None;
 
-    for phase_0 in 0..5 {
-        for phase_1 in 0..5 {
-            for phase_2 in 0..5 {
-                for phase_3 in 0..5 {
-                    for phase_4 in 0..5 {
+    for phase_0 in phases.clone() {
+        for phase_1 in phases.clone() {
+            for phase_2 in phases.clone() {
+                for phase_3 in phases.clone() {
+                    for phase_4 in phases.clone() {
                         let phase_sequence = &[phase_0, phase_1, phase_2, phase_3, phase_4];
                         let unique_phase_sequences: HashSet<&i32> = phase_sequence.iter().collect();
                         if phase_sequence.len() != unique_phase_sequences.len() {
                             continue;
                         }
 
-                        let value = circuit.run(phase_sequence)?;
+                        let value = run(phase_sequence)?;
 
                         match max_value {
                             Some(max) if max < value => {
@@ -55,7 +57,5 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    println!("value: {:?}", max_value);
-
-    Ok(())
+    Ok(max_value)
 }
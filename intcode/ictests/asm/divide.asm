@@ -0,0 +1,37 @@
+# Reads pairs of a dividend and a divisor and prints their quotient and remainder, like
+# "17 / 5 = 3 r 2", one line per pair, until it reads a divisor of 0.
+
+loop:   in [dividend]
+        in [divisor]
+        jz [divisor], done
+        print_value [dividend]
+        between 47                      # '/'
+        print_value [divisor]
+        between 61                      # '='
+        mov [dividend], [arg0]
+        mov [divisor], [arg1]
+        call divmod
+        mov [res1], [remainder]
+        print_value [res0]
+        between 114                     # 'r'
+        print_value [remainder]
+        out 10
+        jmp loop
+done:   halt
+
+.macro print_value value
+        mov \value, [arg0]
+        call print_int
+.endm
+
+.macro between character
+        out 32
+        out \character
+        out 32
+.endm
+
+dividend:       .word 0
+divisor:        .word 0
+remainder:      .word 0
+
+.include <std>
@@ -0,0 +1,34 @@
+//! The library bundled with the assembler, included with `.include <module>`.
+//!
+//! - `<core>` holds what the other modules build on: the argument cells `arg0` to `arg2`, the
+//!   result cells `res0` and `res1`, a stack of 256 cells and the macros `mov source,
+//!   destination`, `jmp target`, `push value`, `pop destination`, `call target` and `ret`.
+//!   The relative base counts the cells on the stack, which starts at the label `stack`, so
+//!   the top of the stack is `[rb + stack - 1]`. `push` jumps to `stack.overflow`, an invalid
+//!   instruction, when the stack is full, and code that allocates `n <= 256` cells at once
+//!   can check for room with `jnz [rb + stack.full + n - 1], stack.overflow`.
+//! - `<math>` has `divmod`, which divides `arg0 >= 0` by `arg1 > 0` into `res0` and leaves the
+//!   remainder in `res1`, `divide`, which does the same for any signs and rounds towards zero,
+//!   and `pow`, which raises `arg0` to the power `arg1`. Multiplication is an instruction.
+//! - `<print>` has `print_int`, which outputs `arg0` in decimal as ASCII characters.
+//! - `<memory>` has `memcopy`, which copies `arg2` cells from address `arg0` to address `arg1`.
+//! - `<std>` includes all of them.
+//!
+//! Routines are called with `call`, take their arguments in the argument cells, and may
+//! overwrite any argument and result cell. Programs that adjust the relative base themselves
+//! have to leave it as they found it before using the stack. `memcopy` is the only routine
+//! that writes into its own instructions, as it has no other way to address memory through a
+//! pointer. The modules hold routines and data, so programs include them after their last
+//! instruction. Labels starting with `__` are reserved for the macros.
+
+/// Returns the source of a library module.
+pub fn module(name: &str) -> Option<&'static str> {
+    match name {
+        "core" => Some(include_str!("library/core.asm")),
+        "math" => Some(include_str!("library/math.asm")),
+        "memory" => Some(include_str!("library/memory.asm")),
+        "print" => Some(include_str!("library/print.asm")),
+        "std" => Some(include_str!("library/std.asm")),
+        _ => None,
+    }
+}
@@ -0,0 +1,57 @@
+# Argument and result cells, the stack and the macros the other modules build on.
+
+.macro mov source, destination
+        add \source, 0, \destination
+.endm
+
+.macro jmp target
+        jnz 1, \target
+.endm
+
+# The relative base counts the cells on the stack, so the top is [rb + stack - 1]. Pushing
+# onto a full stack jumps to stack.overflow.
+.macro push value
+        jnz [rb + stack.full], stack.overflow
+        add \value, 0, [rb + stack]
+        arb 1
+.endm
+
+# A destination relative to the relative base sees it after the pop.
+.macro pop destination
+        arb -1
+        add [rb + stack], 0, \destination
+.endm
+
+.macro call target
+        push __return\@
+        jnz 1, \target
+__return\@:
+.endm
+
+.macro ret
+        arb -1
+        jnz 1, [rb + stack]
+.endm
+
+.global arg0, arg1, arg2, res0, res1, stack, stack.full, stack.overflow
+
+arg0:   .word 0
+arg1:   .word 0
+arg2:   .word 0
+res0:   .word 0
+res1:   .word 0
+
+.equ stack.size, 256
+
+stack:
+        .zero stack.size
+
+# 0 for every depth with room for one more cell, then 1 for as many depths again, so that
+# allocating up to stack.size cells at once can be checked with a single lookup.
+stack.full:
+        .zero stack.size
+        .fill stack.size, 1
+
+# Not a valid instruction, so that a program that runs out of stack fails here.
+stack.overflow:
+        .word 0
@@ -0,0 +1,102 @@
+# Arithmetic without an instruction of its own.
+
+.include <core>
+
+.global divmod, divide, pow
+
+# Divides arg0 >= 0 by arg1 > 0, leaving the quotient in res0 and the remainder in res1.
+divmod:
+        mul [arg0], -1, [arg0]
+        mul [arg1], -1, [arg1]
+        call divmod.negative
+        mul [res0], -1, [res0]
+        mul [res1], -1, [res1]
+        ret
+
+# Divides arg0 <= 0 by arg1 < 0, leaving the quotient negated in res0 and the remainder,
+# which is not positive either, in res1. Negative magnitudes reach i32::MIN, which has no
+# positive counterpart. A multiple is only subtracted from the remainder once it fits, so the
+# difference never overflows, and it is computed as res1 + 1 - (multiple + 1) so that a
+# multiple of i32::MIN is never negated.
+#
+# Doubles the divisor, pushing every multiple with its factor, while it fits into the
+# remainder twice, then subtracts the multiples that still fit, largest first.
+divmod.negative:
+        mov 0, [res0]
+        mov [arg0], [res1]
+        mov [arg1], [divmod.multiple]
+        mov -1, [divmod.factor]
+        push 0                          # a factor of 0 marks the bottom
+divmod.double:
+        push [divmod.multiple]
+        push [divmod.factor]
+        lt [divmod.multiple], [res1], [divmod.condition]
+        jnz [divmod.condition], divmod.subtract
+        add [divmod.multiple], 1, [divmod.left]
+        mul [divmod.left], -1, [divmod.left]
+        add [res1], [divmod.left], [divmod.left]
+        add [divmod.left], 1, [divmod.left]
+        lt [divmod.multiple], [divmod.left], [divmod.condition]
+        jnz [divmod.condition], divmod.subtract
+        add [divmod.multiple], [divmod.multiple], [divmod.multiple]
+        add [divmod.factor], [divmod.factor], [divmod.factor]
+        jmp divmod.double
+divmod.subtract:
+        pop [divmod.factor]
+        jz [divmod.factor], divmod.done
+        pop [divmod.multiple]
+        lt [divmod.multiple], [res1], [divmod.condition]
+        jnz [divmod.condition], divmod.subtract
+        add [divmod.multiple], 1, [divmod.left]
+        mul [divmod.left], -1, [divmod.left]
+        add [res1], [divmod.left], [res1]
+        add [res1], 1, [res1]
+        add [res0], [divmod.factor], [res0]
+        jmp divmod.subtract
+divmod.done:
+        ret
+
+divmod.multiple:        .word 0
+divmod.factor:          .word 0
+divmod.left:            .word 0
+divmod.condition:       .word 0
+
+# Divides arg0 by arg1 like Rust does, leaving the quotient rounded towards zero in res0 and
+# the remainder, with the sign of arg0, in res1. Dividing by 0 runs into an invalid
+# instruction, and dividing i32::MIN by -1 fails with an arithmetic overflow, as the quotient
+# does not fit.
+#
+# Divides the negative magnitudes of the operands, so i32::MIN works like any other value.
+divide:
+        jz [arg1], divide.by_zero
+        lt 0, [arg0], [divide.dividend_factor]
+        mul [divide.dividend_factor], -2, [divide.dividend_factor]
+        add [divide.dividend_factor], 1, [divide.dividend_factor]
+        lt 0, [arg1], [divide.divisor_factor]
+        mul [divide.divisor_factor], -2, [divide.divisor_factor]
+        add [divide.divisor_factor], 1, [divide.divisor_factor]
+        mul [arg0], [divide.dividend_factor], [arg0]
+        mul [arg1], [divide.divisor_factor], [arg1]
+        call divmod.negative
+        mul [divide.divisor_factor], [divide.dividend_factor], [divide.divisor_factor]
+        mul [divide.divisor_factor], -1, [divide.divisor_factor]
+        mul [res0], [divide.divisor_factor], [res0]
+        mul [res1], [divide.dividend_factor], [res1]
+        ret
+divide.by_zero:
+        .word 0
+
+# -1 for positive operands and 1 otherwise, which turns them into negative magnitudes.
+divide.dividend_factor: .word 0
+divide.divisor_factor:  .word 0
+
+# Raises arg0 to the power arg1 >= 0, into res0.
+pow:
+        mov 1, [res0]
+pow.loop:
+        jz [arg1], pow.done
+        mul [res0], [arg0], [res0]
+        add [arg1], -1, [arg1]
+        jmp pow.loop
+pow.done:
+        ret
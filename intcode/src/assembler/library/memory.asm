@@ -0,0 +1,19 @@
+# Operations on ranges of memory.
+
+.include <core>
+
+.global memcopy
+
+# Copies arg2 cells from address arg0 to address arg1, first cell first.
+memcopy:
+        jz [arg2], memcopy.done
+        mov [arg0], [memcopy.copy + 1]
+        mov [arg1], [memcopy.copy + 3]
+memcopy.copy:
+        add [0], 0, [0]
+        add [arg0], 1, [arg0]
+        add [arg1], 1, [arg1]
+        add [arg2], -1, [arg2]
+        jmp memcopy
+memcopy.done:
+        ret
@@ -0,0 +1,46 @@
+# Printing numbers as ASCII text.
+
+.include <core>
+
+.global print_int
+
+# Outputs arg0 in decimal, with a leading '-' when it is negative. The smallest cell value
+# cannot be negated, so it is not supported.
+#
+# Pushes every power of ten up to arg0, then counts how often each fits, largest first.
+print_int:
+        lt [arg0], 0, [print_int.condition]
+        jz [print_int.condition], print_int.powers
+        out 45                                  # '-'
+        mul [arg0], -1, [arg0]
+print_int.powers:
+        push 0                                  # a power of 0 marks the bottom
+        mov 1, [print_int.value]
+print_int.push:
+        push [print_int.value]
+        lt [print_int.value], 1000000000, [print_int.condition]
+        jz [print_int.condition], print_int.power
+        mul [print_int.value], 10, [print_int.value]
+        lt [arg0], [print_int.value], [print_int.condition]
+        jz [print_int.condition], print_int.push
+print_int.power:
+        pop [print_int.value]
+        jz [print_int.value], print_int.done
+        mov 48, [print_int.character]           # '0'
+print_int.count:
+        lt [arg0], [print_int.value], [print_int.condition]
+        jnz [print_int.condition], print_int.print
+        mul [print_int.value], -1, [print_int.negated]
+        add [arg0], [print_int.negated], [arg0]
+        add [print_int.character], 1, [print_int.character]
+        jmp print_int.count
+print_int.print:
+        out [print_int.character]
+        jmp print_int.power
+print_int.done:
+        ret
+
+print_int.value:        .word 0
+print_int.negated:      .word 0
+print_int.character:    .word 0
+print_int.condition:    .word 0
@@ -0,0 +1,6 @@
+# Every module of the library.
+
+.include <core>
+.include <math>
+.include <memory>
+.include <print>
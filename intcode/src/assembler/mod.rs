@@ -0,0 +1,949 @@
+//! Assembles Intcode from a small assembly language.
+//!
+//! Instructions use the mnemonics and operand syntax of the disassembly in traces and
+//! listings: `[expression]` reads from an address, `[rb + expression]` from an address relative
+//! to the relative base, which `arb` adjusts, and a bare expression is an immediate value.
+//! Destinations are always addresses in brackets, and `rb` cannot name a label or constant.
+//!
+//! ```text
+//! .include <print>
+//!
+//! start:  in [arg0]
+//!         call print_int      # a macro from <core>, which <print> includes
+//!         out 10
+//!         jz [arg0], done
+//!         jnz 1, start
+//! done:   halt
+//!
+//! .macro twice value, destination
+//!         add \value, \value, \destination
+//! .endm
+//! ```
+//!
+//! Expressions add and subtract numbers, labels and `.equ` constants, as in `table + 3`. A line
+//! starting with a label followed by `:` names the address of the next word. `#` starts a
+//! comment.
+//!
+//! Directives:
+//!
+//! - `.word expression, ...` places values directly into memory.
+//! - `.zero count` places `count` zeros, and `.fill count, expression` as many copies of a
+//!   value.
+//! - `.equ name, expression` defines a constant from numbers and constants defined before.
+//! - `.global label, ...` lets other modules refer to labels when linking objects, and does
+//!   nothing otherwise.
+//! - `.include "path"` splices in a file, relative to the including file, and
+//!   `.include <module>` a module of the bundled library. Each file and module is included
+//!   once, however often it is named.
+//! - `.macro name parameter, ...` up to `.endm` defines a macro, which is used like an
+//!   instruction anywhere in the program. In its body, `\parameter` stands for an argument and
+//!   `\@` for a number unique to each use, to keep labels apart.
+//!
+//! The library is described in `library`.
+//!
+//! Programs are either assembled on their own, or as objects that `linker` combines. Objects
+//! may refer to global labels of other objects, and leave the library modules they include out
+//! so that the linker adds each of them once.
+
+pub mod library;
+pub mod object;
+mod preprocessor;
+
+use super::operations::ParameterMode;
+use super::source_map::{SourceLine, SourceMap};
+use object::{Object, ObjectSymbol, Relocation};
+use preprocessor::{Origin, Preprocessor, Source};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A line of a source file, counted from 1. Library modules are named like `<core>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+}
+
+impl Location {
+    pub fn new(file: &str, line: usize) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+        }
+    }
+
+    fn error<M>(&self, message: M) -> AssemblyError
+    where
+        M: Into<Cow<'static, str>>,
+    {
+        AssemblyError {
+            location: self.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyError {
+    pub location: Location,
+    pub message: Cow<'static, str>,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+impl Error for AssemblyError {}
+
+impl From<AssemblyError> for Cow<'static, str> {
+    fn from(error: AssemblyError) -> Self {
+        error.to_string().into()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assembly {
+    pub memory: Vec<i32>,
+    /// The address of every label, including those of included files and macros.
+    pub labels: BTreeMap<String, usize>,
+    pub source_map: SourceMap,
+}
+
+/// Assembles `text`, which includes files relative to `directory`.
+pub fn assemble(text: &str, directory: &Path) -> Result<Assembly, AssemblyError> {
+    let source = Preprocessor::new().process(text, Origin::text(directory))?;
+
+    Ok(translate(source, "<input>", false)?.into())
+}
+
+pub fn assemble_file<P>(path: P) -> Result<Assembly, AssemblyError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let (text, origin) = read_file(path)?;
+    let source = Preprocessor::new().process(&text, origin)?;
+
+    Ok(translate(source, &path.display().to_string(), false)?.into())
+}
+
+/// Assembles `text` into an object named `name`, leaving symbols it does not define and the
+/// library modules it includes to the linker.
+pub fn assemble_object(text: &str, directory: &Path, name: &str) -> Result<Object, AssemblyError> {
+    let source = Preprocessor::new()
+        .linking()
+        .process(text, Origin::text(directory))?;
+
+    translate(source, name, true)
+}
+
+/// Assembles a file into an object named after the file.
+pub fn assemble_object_file<P>(path: P) -> Result<Object, AssemblyError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let (text, origin) = read_file(path)?;
+    let name = path.file_stem().map_or_else(
+        || path.display().to_string(),
+        |stem| stem.to_string_lossy().into(),
+    );
+    let source = Preprocessor::new().linking().process(&text, origin)?;
+
+    translate(source, &name, true)
+}
+
+/// Assembles a module of the library into an object named like `<core>`.
+pub fn library_object(name: &str) -> Result<Object, AssemblyError> {
+    let text = library::module(name).ok_or_else(|| {
+        Location::new("<input>", 0).error(format!("No library module <{}>", name))
+    })?;
+    let source = Preprocessor::new()
+        .linking()
+        .process(text, Origin::Library(name.to_string()))?;
+
+    translate(source, &format!("<{}>", name), true)
+}
+
+fn read_file(path: &Path) -> Result<(String, Origin), AssemblyError> {
+    let name = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|error| {
+        Location::new(&name, 0).error(format!("Failed to read {}: {}", name, error))
+    })?;
+    let origin = Origin::File {
+        name,
+        directory: path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+    };
+
+    Ok((text, origin))
+}
+
+impl From<Object> for Assembly {
+    fn from(object: Object) -> Self {
+        let mut source_map = SourceMap::new(object.words.len());
+        for symbol in &object.symbols {
+            source_map.add_label(symbol.offset, &symbol.name);
+        }
+        for (offset, line) in object.lines {
+            source_map.add_line(offset, line.location, line.code);
+        }
+
+        Self {
+            memory: object.words,
+            labels: object
+                .symbols
+                .into_iter()
+                .map(|symbol| (symbol.name, symbol.offset))
+                .collect(),
+            source_map,
+        }
+    }
+}
+
+/// Encodes the lines of a program as an object. Unless `linking`, every symbol has to be
+/// defined, and the words are final when the object is loaded at address 0.
+fn translate(source: Source, name: &str, linking: bool) -> Result<Object, AssemblyError> {
+    let statements = source
+        .lines
+        .iter()
+        .map(|line| Ok((&line.location, parse_statement(&line.text, &line.location)?)))
+        .collect::<Result<Vec<_>, AssemblyError>>()?;
+
+    // The first pass places every label, the second encodes with all of them known.
+    let mut symbols = Symbols::default();
+    let mut sizes = Vec::with_capacity(statements.len());
+    for (location, statement) in &statements {
+        let size = match statement {
+            Statement::Label(name) => {
+                let address = sizes.iter().sum();
+                symbols.define(name, Definition::Label(address), location)?;
+                0
+            }
+            Statement::Constant(name, value) => {
+                let value = symbols.evaluate(value, location)?;
+                symbols.define(name, Definition::Constant(value), location)?;
+                0
+            }
+            Statement::Fill(count, _) => {
+                let count = symbols.evaluate(count, location)?;
+                usize::try_from(count)
+                    .map_err(|_| location.error("The count of `.fill` cannot be negative"))?
+            }
+            Statement::Global(_) => 0,
+            Statement::Instruction { operands, .. } => 1 + operands.len(),
+            Statement::Words(words) => words.len(),
+        };
+        sizes.push(size);
+    }
+
+    let mut object = Object {
+        name: name.to_string(),
+        words: Vec::with_capacity(sizes.iter().sum()),
+        symbols: Vec::new(),
+        relocations: Vec::new(),
+        libraries: source.libraries,
+        lines: Vec::new(),
+    };
+    let mut globals = HashSet::new();
+    for ((location, statement), size) in statements.iter().zip(sizes) {
+        if size > 0 {
+            let code = matches!(statement, Statement::Instruction { .. });
+            object.lines.push((
+                object.words.len(),
+                SourceLine {
+                    location: (*location).clone(),
+                    code,
+                },
+            ));
+        }
+
+        match statement {
+            Statement::Label(_) | Statement::Constant(..) => {}
+            Statement::Global(names) => {
+                for name in names {
+                    match symbols.definitions.get(name) {
+                        Some(Definition::Label(_)) => globals.insert(name.as_str()),
+                        _ => return Err(location.error(format!("`{}` is not a label", name))),
+                    };
+                }
+            }
+            Statement::Fill(_, value) => {
+                for _ in 0..size {
+                    symbols.encode(value, location, linking, &mut object)?;
+                }
+            }
+            Statement::Instruction { opcode, operands } => {
+                let modes = operands
+                    .iter()
+                    .zip(&[100, 1000, 10000])
+                    .map(|((mode, _), factor)| match mode {
+                        ParameterMode::Position => 0,
+                        ParameterMode::Immediate => *factor,
+                        ParameterMode::Relative => 2 * factor,
+                    })
+                    .sum::<i32>();
+                object.words.push(opcode + modes);
+                for (_, value) in operands {
+                    symbols.encode(value, location, linking, &mut object)?;
+                }
+            }
+            Statement::Words(words) => {
+                for value in words {
+                    symbols.encode(value, location, linking, &mut object)?;
+                }
+            }
+        }
+    }
+
+    for (name, definition) in &symbols.definitions {
+        if let Definition::Label(offset) = definition {
+            object.symbols.push(ObjectSymbol {
+                name: name.clone(),
+                offset: *offset,
+                global: globals.contains(name.as_str()),
+            });
+        }
+    }
+
+    Ok(object)
+}
+
+/// What an instruction looks like in assembly.
+struct Instruction {
+    opcode: i32,
+    parameters: usize,
+    destination: bool,
+}
+
+fn instruction(mnemonic: &str) -> Option<Instruction> {
+    let (opcode, parameters, destination) = match mnemonic {
+        "add" => (1, 2, true),
+        "mul" => (2, 2, true),
+        "in" => (3, 0, true),
+        "out" => (4, 1, false),
+        "jnz" => (5, 2, false),
+        "jz" => (6, 2, false),
+        "lt" => (7, 2, true),
+        "eq" => (8, 2, true),
+        "arb" => (9, 1, false),
+        "halt" => (99, 0, false),
+        _ => return None,
+    };
+
+    Some(Instruction {
+        opcode,
+        parameters,
+        destination,
+    })
+}
+
+/// A sum of a number and of symbols, each added or subtracted.
+#[derive(Debug, Clone, PartialEq)]
+struct Expression {
+    constant: i64,
+    symbols: Vec<(bool, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Statement {
+    Label(String),
+    Constant(String, Expression),
+    Instruction {
+        opcode: i32,
+        operands: Vec<(ParameterMode, Expression)>,
+    },
+    Words(Vec<Expression>),
+    /// A count and the value repeated that many times.
+    Fill(Expression, Expression),
+    Global(Vec<String>),
+}
+
+fn parse_statement(text: &str, location: &Location) -> Result<Statement, AssemblyError> {
+    if let Some(label) = text.strip_suffix(':') {
+        return Ok(Statement::Label(label.to_string()));
+    }
+
+    let (word, rest) = preprocessor::split_word(text);
+    let operands = preprocessor::split_operands(rest);
+    match word {
+        ".word" if !operands.is_empty() => Ok(Statement::Words(
+            operands
+                .iter()
+                .map(|operand| parse_expression(operand, location))
+                .collect::<Result<_, _>>()?,
+        )),
+        ".zero" => match operands.as_slice() {
+            [count] => Ok(Statement::Fill(
+                parse_expression(count, location)?,
+                parse_expression("0", location)?,
+            )),
+            _ => Err(location.error("Expected `.zero count`")),
+        },
+        ".fill" => match operands.as_slice() {
+            [count, value] => Ok(Statement::Fill(
+                parse_expression(count, location)?,
+                parse_expression(value, location)?,
+            )),
+            _ => Err(location.error("Expected `.fill count, value`")),
+        },
+        ".equ" => match operands.as_slice() {
+            [name, value] if preprocessor::is_identifier(name) => Ok(Statement::Constant(
+                name.to_string(),
+                parse_expression(value, location)?,
+            )),
+            _ => Err(location.error("Expected `.equ name, value`")),
+        },
+        ".global"
+            if operands
+                .iter()
+                .all(|name| preprocessor::is_identifier(name)) =>
+        {
+            Ok(Statement::Global(
+                operands.iter().map(|name| name.to_string()).collect(),
+            ))
+        }
+        _ if word.starts_with('.') => Err(location.error(format!("Invalid directive `{}`", text))),
+        _ => parse_instruction(word, &operands, location),
+    }
+}
+
+fn parse_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+    location: &Location,
+) -> Result<Statement, AssemblyError> {
+    let instruction = instruction(mnemonic)
+        .ok_or_else(|| location.error(format!("Unknown instruction `{}`", mnemonic)))?;
+    let expected = instruction.parameters + instruction.destination as usize;
+    if operands.len() != expected {
+        return Err(location.error(format!(
+            "`{}` takes {} operands, got {}",
+            mnemonic,
+            expected,
+            operands.len()
+        )));
+    }
+
+    let operands = operands
+        .iter()
+        .enumerate()
+        .map(|(idx, operand)| {
+            let (mode, value) = match operand
+                .strip_prefix('[')
+                .and_then(|operand| operand.strip_suffix(']'))
+            {
+                Some(address) => match relative_offset(address) {
+                    Some(offset) => (ParameterMode::Relative, offset),
+                    None => (ParameterMode::Position, address),
+                },
+                None if idx == instruction.parameters => {
+                    return Err(location.error(format!(
+                        "The destination of `{}` must be an address in brackets",
+                        mnemonic
+                    )))
+                }
+                None => (ParameterMode::Immediate, *operand),
+            };
+
+            Ok((mode, parse_expression(value, location)?))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Statement::Instruction {
+        opcode: instruction.opcode,
+        operands,
+    })
+}
+
+/// The offset of an address written as `rb`, `rb + offset` or `rb - offset`, including its
+/// sign.
+fn relative_offset(address: &str) -> Option<&str> {
+    let rest = address.trim().strip_prefix("rb")?.trim_start();
+
+    match rest.strip_prefix('+') {
+        _ if rest.is_empty() => Some("0"),
+        Some(offset) => Some(offset),
+        None if rest.starts_with('-') => Some(rest),
+        None => None,
+    }
+}
+
+fn parse_expression(text: &str, location: &Location) -> Result<Expression, AssemblyError> {
+    let invalid = || location.error(format!("Invalid expression `{}`", text));
+    let mut expression = Expression {
+        constant: 0,
+        symbols: Vec::new(),
+    };
+
+    let mut rest = text.trim();
+    let mut negative = false;
+    if let Some(after) = rest.strip_prefix('-') {
+        negative = true;
+        rest = after.trim_start();
+    }
+
+    loop {
+        let len = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = rest[..len].trim();
+
+        if let Ok(value) = term.parse::<i32>() {
+            let value = i64::from(value);
+            expression.constant += if negative { -value } else { value };
+        } else if preprocessor::is_identifier(term) {
+            expression.symbols.push((negative, term.to_string()));
+        } else {
+            return Err(invalid());
+        }
+
+        match rest[len..].chars().next() {
+            Some(operator) => {
+                negative = operator == '-';
+                rest = rest[len + 1..].trim_start();
+            }
+            None => return Ok(expression),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Definition {
+    Label(usize),
+    Constant(i64),
+}
+
+#[derive(Debug, Default)]
+struct Symbols {
+    definitions: BTreeMap<String, Definition>,
+}
+
+impl Symbols {
+    fn define(
+        &mut self,
+        name: &str,
+        definition: Definition,
+        location: &Location,
+    ) -> Result<(), AssemblyError> {
+        if name == "rb" {
+            return Err(location.error("`rb` is reserved for the relative base"));
+        }
+        if self
+            .definitions
+            .insert(name.to_string(), definition)
+            .is_some()
+        {
+            return Err(location.error(format!("`{}` is defined twice", name)));
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates `expression`, which may only refer to constants defined so far.
+    fn evaluate(&self, expression: &Expression, location: &Location) -> Result<i64, AssemblyError> {
+        let mut value = expression.constant;
+
+        for (negative, name) in &expression.symbols {
+            let constant = match self.definitions.get(name) {
+                Some(Definition::Constant(constant)) => *constant,
+                Some(Definition::Label(_)) => {
+                    return Err(location.error(format!("`{}` must be a constant", name)))
+                }
+                None => return Err(location.error(format!("Unknown symbol `{}`", name))),
+            };
+            value += if *negative { -constant } else { constant };
+        }
+
+        Ok(value)
+    }
+
+    /// Appends the value of `expression` to the words of `object`, with the relocations it
+    /// needs when `linking`.
+    fn encode(
+        &self,
+        expression: &Expression,
+        location: &Location,
+        linking: bool,
+        object: &mut Object,
+    ) -> Result<(), AssemblyError> {
+        let offset = object.words.len();
+        let mut value = expression.constant;
+        // How often the address of the module is added, minus how often it is subtracted.
+        let mut bases = 0;
+
+        for (negative, name) in &expression.symbols {
+            let sign = if *negative { -1 } else { 1 };
+            match self.definitions.get(name) {
+                Some(Definition::Constant(constant)) => value += sign * constant,
+                Some(Definition::Label(address)) => {
+                    value += sign * *address as i64;
+                    bases += sign;
+                }
+                None if linking => object.relocations.push(Relocation {
+                    offset,
+                    symbol: Some(name.clone()),
+                    negative: *negative,
+                }),
+                None => return Err(location.error(format!("Unknown symbol `{}`", name))),
+            }
+        }
+
+        if linking && bases != 0 {
+            if bases.abs() > 1 {
+                return Err(location.error("The expression cannot be relocated"));
+            }
+            object.relocations.push(Relocation {
+                offset,
+                symbol: None,
+                negative: bases < 0,
+            });
+        }
+
+        let value = i32::try_from(value)
+            .map_err(|_| location.error(format!("{} does not fit a cell", value)))?;
+        object.words.push(value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler;
+    use crate::io::{adapter::IteratorInput, programmable::ProgrammableOutput};
+    use crate::protection::Protection;
+    use crate::Program;
+
+    fn assemble_text(text: &str) -> Result<Assembly, AssemblyError> {
+        assemble(text, Path::new("."))
+    }
+
+    fn run(text: &str, inputs: Vec<i32>) -> Vec<i32> {
+        let memory = assemble_text(text).unwrap().memory;
+        let mut input = IteratorInput::new(inputs);
+        let mut output = ProgrammableOutput::new();
+        Program::new(memory, &mut input, &mut output).run().unwrap();
+
+        output.output()
+    }
+
+    fn printed(text: &str, inputs: Vec<i32>) -> String {
+        run(text, inputs)
+            .into_iter()
+            .map(|value| value as u8 as char)
+            .collect()
+    }
+
+    #[test]
+    fn assembles_what_the_disassembler_prints() {
+        let text = "
+            in [12]
+            jz [12], [15]
+            add [13], [14], [13]
+            out [13]
+            halt
+            .word -1, 0, 1, 9
+        ";
+
+        assert_eq!(
+            assemble_text(text).unwrap().memory,
+            [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9]
+        );
+    }
+
+    #[test]
+    fn relative_operands() {
+        let text = "
+            .equ frame, 2
+                arb frame + 9
+                add [rb - frame], [rb], [rb+1]
+                out [rb + table - 8]
+                halt
+            table: .word 7, 5, 30, 0
+        ";
+        let assembly = assemble_text(text).unwrap();
+
+        assert_eq!(
+            assembly.memory,
+            [109, 11, 22201, -2, 0, 1, 204, 1, 99, 7, 5, 30, 0]
+        );
+        assert_eq!(
+            disassembler::disassemble(&assembly.memory, None)
+                .lines()
+                .nth(1),
+            Some("       2  add [rb - 2], [rb], [rb + 1]")
+        );
+        assert_eq!(run(text, vec![]), [37]);
+        assert_eq!(
+            assemble_text("rb: halt\n").unwrap_err().to_string(),
+            "<input>:1: `rb` is reserved for the relative base"
+        );
+    }
+
+    #[test]
+    fn labels_and_expressions() {
+        let text = "
+            .equ size, 2
+            start:  mul [value], -3, [value + 1]
+                    jnz 1, end - 1
+                    halt
+            end:    .zero size + 1
+            value:  .word start - 5, value
+                    .fill size, end
+        ";
+        let assembly = assemble_text(text).unwrap();
+
+        assert_eq!(
+            assembly.memory,
+            [1002, 11, -3, 12, 1105, 1, 7, 99, 0, 0, 0, -5, 11, 8, 8]
+        );
+        assert_eq!(assembly.labels["end"], 8);
+        assert!(!assembly.labels.contains_key("size"));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let cases = [
+            (
+                "halt\nadd 1, 2, 3\n",
+                "<input>:2: The destination of `add` must be an address in brackets",
+            ),
+            ("out [missing]\n", "<input>:1: Unknown symbol `missing`"),
+            ("x: halt\nx: halt\n", "<input>:2: `x` is defined twice"),
+            ("jnz 1\n", "<input>:1: `jnz` takes 2 operands, got 1"),
+            ("push 1\n", "<input>:1: Unknown instruction `push`"),
+            (
+                ".word 2147483647 + 1\n",
+                "<input>:1: 2147483648 does not fit a cell",
+            ),
+            (
+                "early: halt\n.zero early\n",
+                "<input>:2: `early` must be a constant",
+            ),
+            (
+                ".include \"missing.asm\"\n",
+                "<input>:1: Failed to read ./missing.asm: No such file or directory (os error 2)",
+            ),
+        ];
+
+        for (text, message) in &cases {
+            assert_eq!(assemble_text(text).unwrap_err().to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn files_are_included_relative_to_the_includer() {
+        let directory =
+            std::env::temp_dir().join(format!("intcode-assembler-{}", std::process::id()));
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::write(
+            directory.join("main.asm"),
+            ".include \"lib/answer.asm\"\nout [answer]\nhalt\n.include \"lib/data.asm\"\n",
+        )
+        .unwrap();
+        fs::write(directory.join("lib/answer.asm"), ".include \"data.asm\"\n").unwrap();
+        // Including the root file again does not splice it in twice.
+        fs::write(
+            directory.join("lib/data.asm"),
+            ".include \"../main.asm\"\nanswer: .word 42\n",
+        )
+        .unwrap();
+
+        let assembly = assemble_file(directory.join("main.asm"));
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(assembly.unwrap().memory, [42, 4, 0, 99]);
+    }
+
+    #[test]
+    fn call_and_return_nest() {
+        let text = "
+                    call outer
+                    out 3
+                    halt
+            outer:  out 1
+                    call inner
+                    out 2
+                    ret
+            inner:  out 0
+                    ret
+            .include <core>
+        ";
+
+        assert_eq!(run(text, vec![]), [1, 0, 2, 3]);
+    }
+
+    #[test]
+    fn print_int() {
+        let text = "
+            loop:   in [arg0]
+                    call print_int
+                    out 10
+                    jmp loop
+            .include <print>
+        ";
+        let memory = assemble_text(text).unwrap().memory;
+        let values = [0, 7, -12, 10, 1000, 1000000000, 2147483647, -2147483647];
+
+        let mut input = IteratorInput::new(values.to_vec());
+        let mut output = ProgrammableOutput::new();
+        let _ = Program::new(memory, &mut input, &mut output).run();
+        let text: String = output
+            .output()
+            .into_iter()
+            .map(|value| value as u8 as char)
+            .collect();
+
+        assert_eq!(
+            text,
+            "0\n7\n-12\n10\n1000\n1000000000\n2147483647\n-2147483647\n"
+        );
+    }
+
+    #[test]
+    fn library_runs_with_its_code_read_only() {
+        let text = "
+                    in [arg0]
+                    call print_int
+                    in [arg0]
+                    in [arg1]
+                    call divide
+                    out [res0]
+                    halt
+            .include <std>
+        ";
+        let assembly = assemble_text(text).unwrap();
+        let mut input = IteratorInput::new(vec![-42, 100, -7]);
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(assembly.memory.clone(), &mut input, &mut output);
+        for address in 0..assembly.memory.len() {
+            if assembly
+                .source_map
+                .line(address)
+                .is_some_and(|line| line.code)
+            {
+                program.protect(address..address + 1, Protection::ReadOnly);
+            }
+        }
+
+        assert_eq!(program.run(), Ok(()));
+        assert_eq!(output.output(), [45, 52, 50, -14]);
+    }
+
+    #[test]
+    fn stack_overflow_fails_at_stack_overflow() {
+        let text = "
+            loop:   push [count]
+                    add [count], 1, [count]
+                    jmp loop
+            count:  .word 0
+            .include <core>
+        ";
+        let assembly = assemble_text(text).unwrap();
+        let mut input = IteratorInput::new(vec![]);
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(assembly.memory, &mut input, &mut output);
+
+        assert!(program.run().is_err());
+        assert_eq!(
+            program.instruction_pointer(),
+            assembly.labels["stack.overflow"]
+        );
+        assert_eq!(program.memory()[assembly.labels["count"]], 256);
+        assert_eq!(program.memory()[assembly.labels["stack"] + 255], 255);
+        assert_eq!(program.relative_base(), 256);
+    }
+
+    #[test]
+    fn divmod_and_pow() {
+        let text = "
+                    in [arg0]
+                    in [arg1]
+                    call divmod
+                    out [res0]
+                    out [res1]
+                    mov 3, [arg0]
+                    mov 4, [arg1]
+                    call pow
+                    out [res0]
+                    halt
+            .include <math>
+        ";
+
+        assert_eq!(run(text, vec![0, 5]), [0, 0, 81]);
+        assert_eq!(run(text, vec![17, 5]), [3, 2, 81]);
+        assert_eq!(run(text, vec![2147483647, 1]), [2147483647, 0, 81]);
+        assert_eq!(run(text, vec![2147483647, 65536]), [32767, 65535, 81]);
+    }
+
+    #[test]
+    fn divide_rounds_towards_zero() {
+        let text = "
+                    in [arg0]
+                    in [arg1]
+                    call divide
+                    out [res0]
+                    out [res1]
+                    halt
+            .include <math>
+        ";
+
+        assert_eq!(run(text, vec![7, 2]), [3, 1]);
+        assert_eq!(run(text, vec![-7, 2]), [-3, -1]);
+        assert_eq!(run(text, vec![7, -2]), [-3, 1]);
+        assert_eq!(run(text, vec![-7, -2]), [3, -1]);
+        assert_eq!(run(text, vec![i32::MIN, 1]), [i32::MIN, 0]);
+        assert_eq!(run(text, vec![i32::MIN, 2]), [-1 << 30, 0]);
+        assert_eq!(run(text, vec![i32::MIN, 3]), [i32::MIN / 3, i32::MIN % 3]);
+        assert_eq!(
+            run(text, vec![i32::MIN, -3]),
+            [i32::MIN / -3, i32::MIN % -3]
+        );
+        assert_eq!(run(text, vec![i32::MIN, i32::MIN]), [1, 0]);
+        assert_eq!(run(text, vec![i32::MAX, i32::MIN]), [0, i32::MAX]);
+        assert_eq!(run(text, vec![i32::MAX, -1]), [-i32::MAX, 0]);
+
+        let memory = assemble_text(text).unwrap().memory;
+        let mut input = IteratorInput::new(vec![i32::MIN, -1]);
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(memory, &mut input, &mut output);
+        assert_eq!(program.run(), Err("Arithmetic overflow".into()));
+    }
+
+    #[test]
+    fn memcopy() {
+        let text = "
+                    mov source, [arg0]
+                    mov destination, [arg1]
+                    mov 3, [arg2]
+                    call memcopy
+                    out [destination]
+                    out [destination + 2]
+                    out [destination + 3]
+                    halt
+            source:         .word 4, 5, 6
+            destination:    .zero 4
+            .include <std>
+        ";
+
+        assert_eq!(run(text, vec![]), [4, 6, 0]);
+        assert_eq!(
+            printed(
+                "mov 42, [arg0]\ncall print_int\nhalt\n.include <std>\n",
+                vec![]
+            ),
+            "42"
+        );
+    }
+}
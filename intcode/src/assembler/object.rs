@@ -0,0 +1,300 @@
+//! Relocatable object files, which the assembler writes for the linker.
+//!
+//! An object holds the words of one module as if it was loaded at address 0. Its relocations
+//! list the words the linker still has to adjust, by adding or subtracting the address the
+//! module ends up at or that of a global symbol of another module. Objects are stored as text:
+//!
+//! ```text
+//! module: main
+//! words: 1101,0,0,5,99,0
+//! symbol: start 0 global
+//! symbol: result 5 local
+//! relocation: 3 +
+//! relocation: 2 +answer
+//! library: core
+//! source: 0 code 3 main.asm
+//! source: 5 data 8 main.asm
+//! ```
+//!
+//! `source` gives the line and file of the words starting at an offset, as in a source map.
+
+use super::Location;
+use crate::loader;
+use crate::source_map::SourceLine;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const EXTENSION: &str = "icobj";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub offset: usize,
+    /// Whether other modules can refer to the symbol.
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub offset: usize,
+    /// The global symbol whose address to add, or `None` for the address of the module itself.
+    pub symbol: Option<String>,
+    pub negative: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub name: String,
+    pub words: Vec<i32>,
+    pub symbols: Vec<ObjectSymbol>,
+    pub relocations: Vec<Relocation>,
+    /// The library modules the object included, which the linker adds once for all objects.
+    pub libraries: Vec<String>,
+    /// The source line of the words starting at each offset.
+    pub lines: Vec<(usize, SourceLine)>,
+}
+
+impl Object {
+    pub fn parse(text: &str) -> Result<Self, Cow<'static, str>> {
+        let mut object = Object {
+            name: String::new(),
+            words: Vec::new(),
+            symbols: Vec::new(),
+            relocations: Vec::new(),
+            libraries: Vec::new(),
+            lines: Vec::new(),
+        };
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: &str| format!("Line {}: {}", idx + 1, message);
+            let (key, value) = line
+                .split_once(':')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| error("expected `key: value`"))?;
+            let fields: Vec<_> = value.split_whitespace().collect();
+
+            match (key, fields.as_slice()) {
+                ("module", [name]) => object.name = name.to_string(),
+                ("words", _) => object
+                    .words
+                    .extend(loader::parse(value).map_err(|parse| error(&parse.to_string()))?),
+                ("symbol", [name, offset, visibility]) => object.symbols.push(ObjectSymbol {
+                    name: name.to_string(),
+                    offset: offset.parse().map_err(|_| error("invalid offset"))?,
+                    global: match *visibility {
+                        "global" => true,
+                        "local" => false,
+                        _ => return Err(error("expected `global` or `local`").into()),
+                    },
+                }),
+                ("relocation", [offset, target]) => {
+                    let (negative, symbol) =
+                        match (target.strip_prefix('+'), target.strip_prefix('-')) {
+                            (Some(symbol), _) => (false, symbol),
+                            (_, Some(symbol)) => (true, symbol),
+                            _ => return Err(error("expected `+` or `-` before the symbol").into()),
+                        };
+                    object.relocations.push(Relocation {
+                        offset: offset.parse().map_err(|_| error("invalid offset"))?,
+                        symbol: Some(symbol.to_string()).filter(|symbol| !symbol.is_empty()),
+                        negative,
+                    });
+                }
+                ("library", [name]) => object.libraries.push(name.to_string()),
+                ("source", _) => {
+                    let fields: Vec<_> = value.splitn(4, ' ').collect();
+                    let line = match fields.as_slice() {
+                        [offset, kind @ ("code" | "data"), line, file] => Some((
+                            offset.parse().ok(),
+                            *kind == "code",
+                            line.parse().ok(),
+                            file,
+                        )),
+                        _ => None,
+                    };
+                    match line {
+                        Some((Some(offset), code, Some(line), file)) => object.lines.push((
+                            offset,
+                            SourceLine {
+                                location: Location::new(file, line),
+                                code,
+                            },
+                        )),
+                        _ => return Err(error("expected `offset code|data line file`").into()),
+                    }
+                }
+                _ => return Err(error(&format!("invalid `{}`", key)).into()),
+            }
+        }
+
+        if object.name.is_empty() {
+            return Err("The object has no module name".into());
+        }
+        if let Some(relocation) = object
+            .relocations
+            .iter()
+            .find(|relocation| relocation.offset >= object.words.len())
+        {
+            return Err(
+                format!("Relocation at {} is outside the module", relocation.offset).into(),
+            );
+        }
+        // A label may mark the end of the module, but every source line has words.
+        if let Some(symbol) = object
+            .symbols
+            .iter()
+            .find(|symbol| symbol.offset > object.words.len())
+        {
+            return Err(format!(
+                "Symbol `{}` at {} is outside the module",
+                symbol.name, symbol.offset
+            )
+            .into());
+        }
+        if let Some((offset, _)) = object
+            .lines
+            .iter()
+            .find(|(offset, _)| *offset >= object.words.len())
+        {
+            return Err(format!("Source line at {} is outside the module", offset).into());
+        }
+
+        Ok(object)
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+
+        Self::parse(&text).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "module: {}", self.name)?;
+        for words in self.words.chunks(16) {
+            let words: Vec<_> = words.iter().map(i32::to_string).collect();
+            writeln!(f, "words: {}", words.join(","))?;
+        }
+        for symbol in &self.symbols {
+            let visibility = if symbol.global { "global" } else { "local" };
+            writeln!(
+                f,
+                "symbol: {} {} {}",
+                symbol.name, symbol.offset, visibility
+            )?;
+        }
+        for relocation in &self.relocations {
+            let sign = if relocation.negative { '-' } else { '+' };
+            let symbol = relocation.symbol.as_deref().unwrap_or_default();
+            writeln!(f, "relocation: {} {}{}", relocation.offset, sign, symbol)?;
+        }
+        for library in &self.libraries {
+            writeln!(f, "library: {}", library)?;
+        }
+        for (offset, line) in &self.lines {
+            let kind = if line.code { "code" } else { "data" };
+            writeln!(
+                f,
+                "source: {} {} {} {}",
+                offset, kind, line.location.line, line.location.file
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object() -> Object {
+        Object {
+            name: "main".to_string(),
+            words: (0..20).collect(),
+            symbols: vec![
+                ObjectSymbol {
+                    name: "start".to_string(),
+                    offset: 0,
+                    global: true,
+                },
+                ObjectSymbol {
+                    name: "loop".to_string(),
+                    offset: 4,
+                    global: false,
+                },
+            ],
+            relocations: vec![
+                Relocation {
+                    offset: 3,
+                    symbol: None,
+                    negative: false,
+                },
+                Relocation {
+                    offset: 7,
+                    symbol: Some("print_int".to_string()),
+                    negative: true,
+                },
+            ],
+            libraries: vec!["print".to_string()],
+            lines: vec![(
+                0,
+                SourceLine {
+                    location: Location::new("src/main file.asm", 3),
+                    code: true,
+                },
+            )],
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let text = object().to_string();
+
+        assert_eq!(
+            text,
+            "module: main
+words: 0,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15
+words: 16,17,18,19
+symbol: start 0 global
+symbol: loop 4 local
+relocation: 3 +
+relocation: 7 -print_int
+library: print
+source: 0 code 3 src/main file.asm
+"
+        );
+        assert_eq!(Object::parse(&text), Ok(object()));
+    }
+
+    #[test]
+    fn invalid_objects() {
+        assert!(Object::parse("words: 1,2\n").is_err());
+        assert!(Object::parse("module: m\nwords: 1\nrelocation: 1 +\n").is_err());
+        assert!(Object::parse("module: m\nsymbol: a 0 public\n").is_err());
+        assert!(Object::parse("module: m\nwords: 1\nrelocation: 0 *a\n").is_err());
+        assert!(Object::parse("module: m\nwords: 1\nrelocation: 0 é\n").is_err());
+        assert!(Object::parse("module: m\nwords: 1\nsymbol: a 2 local\n").is_err());
+        assert!(Object::parse("module: m\nwords: 1\nsource: 1 code 3 m.asm\n").is_err());
+    }
+
+    #[test]
+    fn labels_may_end_the_module() {
+        let object = Object::parse("module: m\nwords: 99\nsymbol: end 1 local\n").unwrap();
+
+        assert_eq!(object.symbols[0].offset, 1);
+    }
+}
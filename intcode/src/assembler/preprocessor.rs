@@ -0,0 +1,504 @@
+//! Splices included files into the source and expands macros, leaving only labels,
+//! instructions and data directives for the assembler.
+
+use super::{library, AssemblyError, Location};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// A line of source without its comment, trimmed and not empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// Where the line was written, or for lines from a macro, where the outermost macro was
+    /// used.
+    pub location: Location,
+    pub text: String,
+}
+
+#[derive(Debug)]
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Line>,
+}
+
+/// Where the text of a source file came from, which decides how its includes are resolved.
+#[derive(Debug, Clone)]
+pub enum Origin {
+    /// A file, or text read from elsewhere that includes files relative to a directory.
+    File {
+        name: String,
+        directory: PathBuf,
+    },
+    Library(String),
+}
+
+impl Origin {
+    /// Text that is not from a file, which includes files relative to `directory`.
+    pub fn text(directory: &Path) -> Self {
+        Origin::File {
+            name: "<input>".to_string(),
+            directory: directory.to_path_buf(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Origin::File { name, .. } => name.clone(),
+            Origin::Library(name) => format!("<{}>", name),
+        }
+    }
+}
+
+/// The lines of a program, and the library modules it left for the linker.
+#[derive(Debug, Default)]
+pub struct Source {
+    pub lines: Vec<Line>,
+    pub libraries: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Preprocessor {
+    /// Every file and library module seen so far, so that each is included once.
+    included: HashSet<String>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    /// When set, library modules only contribute their macros and are listed here instead.
+    linked_libraries: Option<Vec<String>>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves the code and data of library modules out, for the linker to add once.
+    pub fn linking(mut self) -> Self {
+        self.linked_libraries = Some(Vec::new());
+        self
+    }
+
+    /// Returns the lines of `text` and everything it includes, with macros expanded.
+    pub fn process(mut self, text: &str, origin: Origin) -> Result<Source, AssemblyError> {
+        match &origin {
+            Origin::Library(name) => {
+                self.included.insert(format!("<{}>", name));
+            }
+            Origin::File { name, .. } => {
+                if let Ok(path) = fs::canonicalize(name) {
+                    self.included.insert(path.display().to_string());
+                }
+            }
+        }
+        let lines = self.read(text, &origin)?;
+
+        let mut expanded = Vec::new();
+        for line in lines {
+            self.expand(line, None, 0, &mut expanded)?;
+        }
+
+        Ok(Source {
+            lines: expanded,
+            libraries: self.linked_libraries.unwrap_or_default(),
+        })
+    }
+
+    /// Reads the lines of one source, splicing in its includes and setting aside its macro
+    /// definitions, which apply to the whole program wherever they are written.
+    fn read(&mut self, text: &str, origin: &Origin) -> Result<Vec<Line>, AssemblyError> {
+        let name = origin.name();
+        let mut lines = Vec::new();
+        let mut definition: Option<(String, Macro, Location)> = None;
+
+        for (idx, text) in text.lines().enumerate() {
+            let location = Location::new(&name, idx + 1);
+            let text = text.split('#').next().unwrap_or_default().trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let (directive, rest) = split_word(text);
+            if let Some((macro_name, mut body, start)) = definition.take() {
+                match directive {
+                    ".endm" => self.define(macro_name, body, start)?,
+                    ".macro" => return Err(location.error("Macros cannot be nested")),
+                    _ => {
+                        body.body.push(Line {
+                            location,
+                            text: text.to_string(),
+                        });
+                        definition = Some((macro_name, body, start));
+                    }
+                }
+                continue;
+            }
+
+            match directive {
+                ".macro" => {
+                    let (macro_name, parameters) = split_word(rest);
+                    if !is_identifier(macro_name) {
+                        return Err(location.error("Expected a macro name"));
+                    }
+                    let parameters = split_operands(parameters)
+                        .into_iter()
+                        .map(|parameter| {
+                            if is_identifier(parameter) {
+                                Ok(parameter.to_string())
+                            } else {
+                                Err(location
+                                    .error(format!("Invalid macro parameter `{}`", parameter)))
+                            }
+                        })
+                        .collect::<Result<_, _>>()?;
+
+                    let body = Macro {
+                        parameters,
+                        body: Vec::new(),
+                    };
+                    definition = Some((macro_name.to_string(), body, location));
+                }
+                ".endm" => return Err(location.error("`.endm` without `.macro`")),
+                ".include" => lines.extend(self.include(rest, origin, &location)?),
+                _ => lines.extend(split_labels(text, &location)),
+            }
+        }
+
+        match definition {
+            Some((macro_name, _, location)) => {
+                Err(location.error(format!("Macro `{}` has no `.endm`", macro_name)))
+            }
+            None => Ok(lines),
+        }
+    }
+
+    fn define(
+        &mut self,
+        name: String,
+        definition: Macro,
+        location: Location,
+    ) -> Result<(), AssemblyError> {
+        if super::instruction(&name).is_some() {
+            return Err(location.error(format!("Macro `{}` would hide an instruction", name)));
+        }
+        if self.macros.contains_key(&name) {
+            return Err(location.error(format!("Macro `{}` is defined twice", name)));
+        }
+
+        self.macros.insert(name, definition);
+        Ok(())
+    }
+
+    /// Reads `"path"`, relative to the including file, or `<name>` from the library, unless it
+    /// was included before.
+    fn include(
+        &mut self,
+        target: &str,
+        origin: &Origin,
+        location: &Location,
+    ) -> Result<Vec<Line>, AssemblyError> {
+        if let Some(name) = target
+            .strip_prefix('<')
+            .and_then(|target| target.strip_suffix('>'))
+        {
+            let text = library::module(name)
+                .ok_or_else(|| location.error(format!("No library module <{}>", name)))?;
+            if !self.included.insert(format!("<{}>", name)) {
+                return Ok(Vec::new());
+            }
+
+            let lines = self.read(text, &Origin::Library(name.to_string()))?;
+            return Ok(match &mut self.linked_libraries {
+                Some(libraries) => {
+                    libraries.push(name.to_string());
+                    Vec::new()
+                }
+                None => lines,
+            });
+        }
+
+        let path = match target
+            .strip_prefix('"')
+            .and_then(|target| target.strip_suffix('"'))
+        {
+            Some(path) => path,
+            None => return Err(location.error("Expected \"path\" or <module> after `.include`")),
+        };
+        let directory = match origin {
+            Origin::File { directory, .. } => directory,
+            Origin::Library(_) => {
+                return Err(location.error("Library modules can only include other modules"))
+            }
+        };
+
+        let path = directory.join(path);
+        let text = fs::read_to_string(&path).map_err(|error| {
+            location.error(format!("Failed to read {}: {}", path.display(), error))
+        })?;
+        let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !self.included.insert(key.display().to_string()) {
+            return Ok(Vec::new());
+        }
+
+        let origin = Origin::File {
+            name: path.display().to_string(),
+            directory: path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+        };
+        self.read(&text, &origin)
+    }
+
+    /// Appends `line` to `output`, replacing macro invocations with their expanded bodies.
+    fn expand(
+        &mut self,
+        line: Line,
+        invocation: Option<&Location>,
+        depth: usize,
+        output: &mut Vec<Line>,
+    ) -> Result<(), AssemblyError> {
+        let location = invocation.unwrap_or(&line.location).clone();
+        let (name, arguments) = split_word(&line.text);
+        let definition = match self.macros.get(name) {
+            Some(definition) => definition,
+            None => {
+                output.push(Line {
+                    location,
+                    text: line.text,
+                });
+                return Ok(());
+            }
+        };
+
+        if depth == MAX_EXPANSION_DEPTH {
+            return Err(location.error(format!("Macro `{}` expands too deeply", name)));
+        }
+        let arguments = split_operands(arguments);
+        if arguments.len() != definition.parameters.len() {
+            return Err(location.error(format!(
+                "Macro `{}` takes {} arguments, got {}",
+                name,
+                definition.parameters.len(),
+                arguments.len()
+            )));
+        }
+
+        self.expansions += 1;
+        let mut body = Vec::new();
+        for body_line in &definition.body {
+            let text = substitute(
+                &body_line.text,
+                &definition.parameters,
+                &arguments,
+                self.expansions,
+            )
+            .map_err(|message| body_line.location.error(message))?;
+            body.extend(split_labels(&text, &body_line.location));
+        }
+
+        for body_line in body {
+            self.expand(body_line, Some(&location), depth + 1, output)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Replaces `\parameter` with its argument and `\@` with a number unique to the expansion.
+fn substitute(
+    text: &str,
+    parameters: &[String],
+    arguments: &[&str],
+    expansion: usize,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(idx) = rest.find('\\') {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+
+        if let Some(after) = rest.strip_prefix('@') {
+            result.push_str(&expansion.to_string());
+            rest = after;
+            continue;
+        }
+
+        let len = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+        let name = &rest[..len];
+        match parameters.iter().position(|parameter| parameter == name) {
+            Some(parameter) => result.push_str(arguments[parameter]),
+            None => return Err(format!("Unknown macro parameter `\\{}`", name)),
+        }
+        rest = &rest[len..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Splits `label: label: rest` into a line per label followed by the rest, if any.
+fn split_labels(mut text: &str, location: &Location) -> Vec<Line> {
+    let mut lines = Vec::new();
+
+    while let Some(idx) = text.find(':') {
+        let label = text[..idx].trim();
+        if !is_identifier(label) {
+            break;
+        }
+
+        lines.push(Line {
+            location: location.clone(),
+            text: format!("{}:", label),
+        });
+        text = text[idx + 1..].trim();
+    }
+
+    if !text.is_empty() {
+        lines.push(Line {
+            location: location.clone(),
+            text: text.to_string(),
+        });
+    }
+
+    lines
+}
+
+/// Splits off the first word of `text`, returning it and the trimmed rest.
+pub fn split_word(text: &str) -> (&str, &str) {
+    match text.find(char::is_whitespace) {
+        Some(idx) => (&text[..idx], text[idx..].trim()),
+        None => (text, ""),
+    }
+}
+
+/// Splits comma separated operands, returning nothing for an empty list.
+pub fn split_operands(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    text.split(',').map(str::trim).collect()
+}
+
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(is_identifier_char)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(text: &str) -> Result<Vec<String>, AssemblyError> {
+        let origin = Origin::File {
+            name: "test.asm".to_string(),
+            directory: PathBuf::from("."),
+        };
+
+        Ok(Preprocessor::new()
+            .process(text, origin)?
+            .lines
+            .into_iter()
+            .map(|line| line.text)
+            .collect())
+    }
+
+    #[test]
+    fn comments_and_labels() {
+        let lines = process("start: loop: in [x] # read\n\n  halt\n").unwrap();
+
+        assert_eq!(lines, ["start:", "loop:", "in [x]", "halt"]);
+    }
+
+    #[test]
+    fn macros_substitute_arguments_and_unique_labels() {
+        let text = "
+            twice [a], [b]
+            .macro twice source, destination
+            again\\@: add \\source, \\source, \\destination
+            .endm
+            twice 1, [c]
+        ";
+
+        assert_eq!(
+            process(text).unwrap(),
+            ["again1:", "add [a], [a], [b]", "again2:", "add 1, 1, [c]"]
+        );
+    }
+
+    #[test]
+    fn nested_macros_report_the_outermost_use() {
+        let text = ".macro inner\nhalt\n.endm\n.macro outer\ninner\n.endm\n\nouter\n";
+        let lines = Preprocessor::new()
+            .process(
+                text,
+                Origin::File {
+                    name: "test.asm".to_string(),
+                    directory: PathBuf::from("."),
+                },
+            )
+            .unwrap()
+            .lines;
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "halt");
+        assert_eq!(lines[0].location, Location::new("test.asm", 8));
+    }
+
+    #[test]
+    fn library_modules_are_included_once() {
+        let lines = process(".include <core>\n.include <core>\n").unwrap();
+
+        assert_eq!(
+            lines.iter().filter(|line| line.as_str() == "arg0:").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn linked_libraries_only_contribute_macros() {
+        let origin = Origin::File {
+            name: "test.asm".to_string(),
+            directory: PathBuf::from("."),
+        };
+        let source = Preprocessor::new()
+            .linking()
+            .process("call print_int\n.include <print>\n", origin)
+            .unwrap();
+
+        assert_eq!(source.libraries, ["core", "print"]);
+        assert!(source
+            .lines
+            .iter()
+            .all(|line| !line.text.contains("print_int:")));
+        assert!(source
+            .lines
+            .iter()
+            .any(|line| line.text == "jnz 1, print_int"));
+    }
+
+    #[test]
+    fn recursive_macros_are_rejected() {
+        let error = process(".macro forever\nforever\n.endm\nforever\n").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "test.asm:4: Macro `forever` expands too deeply"
+        );
+    }
+
+    #[test]
+    fn invalid_macros() {
+        assert!(process(".macro add\n.endm\n").is_err());
+        assert!(process(".macro twice\n.macro again\n.endm\n.endm\n").is_err());
+        assert!(process(".macro unfinished\nhalt\n").is_err());
+        assert!(process(".macro one a\n.endm\none\n").is_err());
+        assert!(process(".macro one a\nout \\b\n.endm\none 1\n").is_err());
+        assert!(process(".include <missing>\n").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use super::{AsyncLineReader, AsyncLineWriter};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+#[derive(Default)]
+struct Shared {
+    values: VecDeque<i32>,
+    senders: usize,
+    waker: Option<Waker>,
+}
+
+impl Shared {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Receiving half of an unbounded channel between futures on the same thread.
+pub struct Receiver {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Receiver {
+    /// Takes every value that was sent but never read.
+    pub fn drain(&mut self) -> Vec<i32> {
+        self.shared.borrow_mut().values.drain(..).collect()
+    }
+}
+
+impl AsyncLineReader for Receiver {
+    fn poll_read_line(&mut self, cx: &mut Context<'_>) -> Poll<Result<i32, Cow<'static, str>>> {
+        let mut shared = self.shared.borrow_mut();
+
+        match shared.values.pop_front() {
+            Some(value) => Poll::Ready(Ok(value)),
+            None if shared.senders == 0 => Poll::Ready(Err("Input channel was closed".into())),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Sending half of an unbounded channel, which never makes the writer wait.
+pub struct Sender {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Sender {
+    pub fn send(&mut self, value: i32) {
+        let mut shared = self.shared.borrow_mut();
+        shared.values.push_back(value);
+        shared.wake();
+    }
+}
+
+impl AsyncLineWriter for Sender {
+    fn poll_write_line(
+        &mut self,
+        _: &mut Context<'_>,
+        value: i32,
+    ) -> Poll<Result<(), Cow<'static, str>>> {
+        self.send(value);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.borrow_mut().senders += 1;
+
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            shared.wake();
+        }
+    }
+}
+
+pub fn channel() -> (Sender, Receiver) {
+    let shared = Rc::new(RefCell::new(Shared {
+        senders: 1,
+        ..Shared::default()
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn empty_channel_is_pending_until_sent() {
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let (mut sender, mut receiver) = channel();
+
+        assert!(receiver.poll_read_line(&mut cx).is_pending());
+        sender.send(3);
+
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(receiver.poll_read_line(&mut cx), Poll::Ready(Ok(3)));
+    }
+
+    #[test]
+    fn closed_after_last_sender_dropped() {
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let (sender, mut receiver) = channel();
+        let mut clone = sender.clone();
+        clone.send(1);
+
+        drop(sender);
+        assert_eq!(receiver.poll_read_line(&mut cx), Poll::Ready(Ok(1)));
+        assert!(receiver.poll_read_line(&mut cx).is_pending());
+
+        drop(clone);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            receiver.poll_read_line(&mut cx),
+            Poll::Ready(Err(_))
+        ));
+    }
+}
@@ -0,0 +1,250 @@
+pub mod channel;
+#[cfg(test)]
+pub mod testing;
+
+use super::{
+    io::{LineReader, LineWriter},
+    Operation, Program, State,
+};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::future;
+use std::task::{Context, Poll};
+
+/// The async counterpart of `LineReader`.
+///
+/// Returning `Poll::Pending` must arrange for the waker in `cx` to be woken once a value is
+/// available, just like `Future::poll`.
+pub trait AsyncLineReader {
+    fn poll_read_line(&mut self, cx: &mut Context<'_>) -> Poll<Result<i32, Cow<'static, str>>>;
+}
+
+/// The async counterpart of `LineWriter`, which may apply back pressure by returning
+/// `Poll::Pending`.
+pub trait AsyncLineWriter {
+    fn poll_write_line(
+        &mut self,
+        cx: &mut Context<'_>,
+        value: i32,
+    ) -> Poll<Result<(), Cow<'static, str>>>;
+}
+
+/// Runs `memory` like `Program::run`, but yields to the executor whenever the program waits
+/// for input or its output is not accepted yet.
+///
+/// The future does not depend on any runtime, so any executor can drive it. Nothing is
+/// spawned, which means a single thread can interleave as many programs as it likes.
+pub async fn run<Input, Output>(
+    memory: Vec<i32>,
+    input: &mut Input,
+    output: &mut Output,
+) -> Result<Vec<i32>, Cow<'static, str>>
+where
+    Input: AsyncLineReader,
+    Output: AsyncLineWriter,
+{
+    let next_input = Cell::new(None);
+    let outputs = RefCell::new(Vec::new());
+    let mut reader = Slot(&next_input);
+    let mut writer = Buffer(&outputs);
+    let mut program = Program::new(memory, &mut reader, &mut writer);
+
+    loop {
+        if let Operation::Input { .. } = program.next_operation()? {
+            let value = future::poll_fn(|cx| input.poll_read_line(cx)).await?;
+            next_input.set(Some(value));
+        }
+
+        let state = program.step()?;
+
+        let written = outputs.take();
+        for value in written {
+            future::poll_fn(|cx| output.poll_write_line(cx, value)).await?;
+        }
+
+        if state == State::Halted {
+            return Ok(program.memory().to_vec());
+        }
+    }
+}
+
+/// Hands the value that was awaited before an input instruction to the synchronous VM.
+struct Slot<'a>(&'a Cell<Option<i32>>);
+
+impl LineReader for Slot<'_> {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        self.0
+            .take()
+            .ok_or_else(|| "Input was read before it was awaited".into())
+    }
+}
+
+/// Collects the values written by the synchronous VM until they are awaited.
+struct Buffer<'a>(&'a RefCell<Vec<i32>>);
+
+impl LineWriter for Buffer<'_> {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        self.0.borrow_mut().push(value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::channel::{channel, Receiver, Sender};
+    use super::testing::Executor;
+    use super::*;
+    use std::future::Future;
+    use std::rc::Rc;
+    use std::task::Waker;
+
+    type Outcome = Rc<Cell<Option<Result<Vec<i32>, Cow<'static, str>>>>>;
+
+    fn spawn_program(
+        executor: &mut Executor,
+        memory: Vec<i32>,
+        mut input: Receiver,
+        mut output: Sender,
+    ) -> Outcome {
+        let result = Rc::new(Cell::new(None));
+        let slot = result.clone();
+        executor.spawn(async move {
+            slot.set(Some(run(memory, &mut input, &mut output).await));
+        });
+
+        result
+    }
+
+    #[test]
+    fn day_05_example_equal_to_8() {
+        let mut executor = Executor::default();
+        let (mut input, receiver) = channel();
+        let (sender, mut output) = channel();
+        input.send(8);
+
+        let result = spawn_program(
+            &mut executor,
+            vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            receiver,
+            sender,
+        );
+        executor.run().unwrap();
+
+        assert!(result.take().unwrap().is_ok());
+        assert_eq!(output.drain(), [1]);
+    }
+
+    #[test]
+    fn final_memory_is_returned() {
+        let mut executor = Executor::default();
+        let (_, receiver) = channel();
+        let (sender, _) = channel();
+
+        let result = spawn_program(&mut executor, vec![1, 0, 0, 0, 99], receiver, sender);
+        executor.run().unwrap();
+
+        assert_eq!(result.take().unwrap(), Ok(vec![2, 0, 0, 0, 99]));
+    }
+
+    #[test]
+    fn waiting_for_input_yields() {
+        let (mut input, mut receiver) = channel();
+        let (mut sender, mut output) = channel();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut future = Box::pin(run(
+            vec![104, 7, 3, 9, 4, 9, 99, 0, 0, 0],
+            &mut receiver,
+            &mut sender,
+        ));
+
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        input.send(42);
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+        drop(future);
+
+        assert_eq!(output.drain(), [7, 42]);
+    }
+
+    #[test]
+    fn closed_input_fails() {
+        let mut executor = Executor::default();
+        let (input, receiver) = channel();
+        let (sender, _) = channel();
+        drop(input);
+
+        let result = spawn_program(&mut executor, vec![3, 0, 99], receiver, sender);
+        executor.run().unwrap();
+
+        assert!(result.take().unwrap().is_err());
+    }
+
+    #[test]
+    fn feedback_loop_on_one_thread() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let mut executor = Executor::default();
+        let (mut senders, receivers): (Vec<_>, Vec<_>) = (0..5).map(|_| channel()).unzip();
+
+        for (sender, phase_setting) in senders.iter_mut().zip(&[9, 8, 7, 6, 5]) {
+            sender.send(*phase_setting);
+        }
+        senders[0].send(0);
+
+        senders.rotate_left(1);
+        let mut amplifiers = receivers.into_iter().zip(senders);
+
+        // The first amplifier keeps reading its input after halting, since that is where the
+        // final signal ends up once the last amplifier is done.
+        let (mut input, mut output) = amplifiers.next().unwrap();
+        let signal = Rc::new(Cell::new(Vec::new()));
+        let unread = signal.clone();
+        let first = program.clone();
+        executor.spawn(async move {
+            run(first, &mut input, &mut output).await.unwrap();
+            let mut values = Vec::new();
+            while let Ok(value) = future::poll_fn(|cx| input.poll_read_line(cx)).await {
+                values.push(value);
+            }
+            unread.set(values);
+        });
+
+        for (receiver, sender) in amplifiers {
+            spawn_program(&mut executor, program.clone(), receiver, sender);
+        }
+        executor.run().unwrap();
+
+        assert_eq!(signal.take(), [139629729]);
+    }
+
+    #[test]
+    fn thousands_of_programs_on_one_thread() {
+        let increment = vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+        let mut executor = Executor::default();
+        let (mut first, mut receiver) = channel();
+
+        for _ in 0..2000 {
+            let (sender, next) = channel();
+            spawn_program(&mut executor, increment.clone(), receiver, sender);
+            receiver = next;
+        }
+        first.send(0);
+        executor.run().unwrap();
+
+        assert_eq!(receiver.drain(), [2000]);
+    }
+
+    #[test]
+    fn deadlock_is_detected() {
+        let mut executor = Executor::default();
+        let (_input, receiver) = channel();
+        let (sender, _) = channel();
+
+        spawn_program(&mut executor, vec![3, 0, 99], receiver, sender);
+
+        assert!(executor.run().is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// A minimal single-threaded executor, which shows that the async VM needs no runtime.
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<Task>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+struct TaskWaker {
+    task: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.task);
+    }
+}
+
+impl Executor {
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(future)));
+    }
+
+    /// Polls woken tasks until all of them finished.
+    ///
+    /// Fails when unfinished tasks remain but none of them can make progress.
+    pub fn run(&mut self) -> Result<(), Cow<'static, str>> {
+        loop {
+            let next = self.ready.lock().unwrap().pop_front();
+
+            let idx = match next {
+                Some(idx) => idx,
+                None if self.tasks.iter().all(Option::is_none) => return Ok(()),
+                None => return Err("Every unfinished task is waiting".into()),
+            };
+
+            let task = match &mut self.tasks[idx] {
+                Some(task) => task,
+                None => continue,
+            };
+            let waker = Waker::from(Arc::new(TaskWaker {
+                task: idx,
+                ready: self.ready.clone(),
+            }));
+
+            if let Poll::Ready(()) = task.as_mut().poll(&mut Context::from_waker(&waker)) {
+                self.tasks[idx] = None;
+            }
+        }
+    }
+}
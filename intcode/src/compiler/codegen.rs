@@ -0,0 +1,701 @@
+//! Generates assembly from a syntax tree, using the macros and routines of the assembler
+//! library.
+//!
+//! Every call gets a frame of cells on the library's stack, which holds the parameters, the
+//! variables and the temporaries of the expressions of its function. Cells are allocated as
+//! they are needed and reused once their value is dead. A function checks that the stack has
+//! room for its frame and moves the relative base past it on entry, so cell `i` of the frame
+//! is `[rb + stack - 1 - i]`, and moves the relative base back before returning.
+//!
+//! Arguments are passed in the cells `call.arguments` and results in `call.result`. Globals
+//! are cells labelled `var.<name>`.
+
+use super::parser::{
+    BinaryOperator, Expression, Function, Statement, StatementKind, UnaryOperator, Unit,
+};
+use super::{CompileError, Position};
+use std::collections::HashMap;
+
+/// The number of cells of the library's stack, which no frame can be larger than.
+const STACK_SIZE: usize = 256;
+
+const BUILTINS: &[(&str, usize)] = &[("input", 0), ("output", 1)];
+
+fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|(builtin, _)| *builtin == name)
+}
+
+/// Where the value of an expression is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand<'u> {
+    Value(i32),
+    /// A cell of the frame that holds a variable.
+    Local(usize),
+    /// A cell of the frame that is free again once the value has been used.
+    Temporary(usize),
+    Global(&'u str),
+}
+
+struct Frame<'u> {
+    function: &'u Function,
+    /// Whether each cell is in use.
+    cells: Vec<bool>,
+    /// The variables of every block the generator is in, innermost last.
+    scopes: Vec<Vec<(&'u str, usize)>>,
+    labels: usize,
+}
+
+impl<'u> Frame<'u> {
+    fn new(function: &'u Function) -> Self {
+        Self {
+            function,
+            cells: Vec::new(),
+            scopes: Vec::new(),
+            labels: 0,
+        }
+    }
+
+    fn allocate(&mut self) -> usize {
+        match self.cells.iter().position(|used| !used) {
+            Some(cell) => {
+                self.cells[cell] = true;
+                cell
+            }
+            None => {
+                self.cells.push(true);
+                self.cells.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, operand: Operand<'u>) {
+        if let Operand::Temporary(cell) = operand {
+            self.cells[cell] = false;
+        }
+    }
+
+    /// Returns a temporary cell for the result of an operation on `operands`, reusing the cell
+    /// of one of them when possible and releasing the others.
+    fn result(&mut self, operands: &[Operand<'u>]) -> usize {
+        let reused = operands.iter().find_map(|operand| match operand {
+            Operand::Temporary(cell) => Some(*cell),
+            _ => None,
+        });
+        for operand in operands {
+            match operand {
+                Operand::Temporary(cell) if reused != Some(*cell) => self.cells[*cell] = false,
+                _ => {}
+            }
+        }
+
+        reused.unwrap_or_else(|| self.allocate())
+    }
+
+    fn variable(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(variable, _)| *variable == name)
+            .map(|(_, cell)| *cell)
+    }
+
+    fn cell(&self, cell: usize) -> String {
+        format!("rb + stack - {}", cell + 1)
+    }
+
+    /// Returns new labels for a statement, which share a number.
+    fn labels<const N: usize>(&mut self, kinds: [&str; N]) -> [String; N] {
+        self.labels += 1;
+        kinds.map(|kind| format!("fn.{}.{}{}", self.function.name, kind, self.labels))
+    }
+
+    fn format(&self, operand: Operand<'_>) -> String {
+        match operand {
+            Operand::Value(value) => value.to_string(),
+            Operand::Local(cell) | Operand::Temporary(cell) => format!("[{}]", self.cell(cell)),
+            Operand::Global(name) => format!("[var.{}]", name),
+        }
+    }
+}
+
+struct Generator<'u> {
+    source: Vec<&'u str>,
+    functions: HashMap<&'u str, &'u Function>,
+    globals: HashMap<&'u str, i32>,
+    lines: Vec<(String, usize)>,
+    /// The source line that the code being generated belongs to.
+    line: usize,
+    /// The last source line copied into a comment.
+    commented: usize,
+    arguments: usize,
+    divides: bool,
+}
+
+/// Generates the assembly for `unit`, as lines paired with the line of `source` they came from.
+pub fn generate<'u>(unit: &'u Unit, source: &'u str) -> Result<Vec<(String, usize)>, CompileError> {
+    let mut functions = HashMap::new();
+    for function in &unit.functions {
+        if is_builtin(&function.name) {
+            return Err(function
+                .position
+                .error(format!("`{}` is a builtin function", function.name)));
+        }
+        if functions.insert(function.name.as_str(), function).is_some() {
+            return Err(function
+                .position
+                .error(format!("`{}` is already defined", function.name)));
+        }
+    }
+    let main = match functions.get("main") {
+        Some(main) if !main.parameters.is_empty() => {
+            return Err(main.position.error("`main` cannot take parameters"))
+        }
+        Some(main) => *main,
+        None => return Err(Position::new(1, 1).error("There is no `main` function")),
+    };
+
+    let mut globals = HashMap::new();
+    for global in &unit.globals {
+        let value = constant(&global.value).ok_or_else(|| {
+            global
+                .position
+                .error(format!("The value of `{}` must be a constant", global.name))
+        })?;
+        if globals.insert(global.name.as_str(), value).is_some() {
+            return Err(global
+                .position
+                .error(format!("`{}` is already defined", global.name)));
+        }
+    }
+
+    let mut generator = Generator {
+        source: source.lines().collect(),
+        functions,
+        globals,
+        lines: Vec::new(),
+        line: main.position.line,
+        commented: 0,
+        arguments: 0,
+        divides: false,
+    };
+
+    generator.label("start");
+    generator.emit("call fn.main");
+    generator.emit("halt");
+    for function in &unit.functions {
+        generator.function(function)?;
+    }
+
+    for global in &unit.globals {
+        generator.line = global.position.line;
+        generator.label(&format!("var.{}", global.name));
+        generator.emit(&format!(
+            ".word {}",
+            generator.globals[global.name.as_str()]
+        ));
+    }
+    generator.line = main.position.line;
+    if generator.arguments > 0 {
+        generator.label("call.arguments");
+        generator.emit(&format!(".zero {}", generator.arguments));
+    }
+    generator.label("call.result");
+    generator.emit(".word 0");
+    generator.blank();
+    generator.emit(".include <core>");
+    if generator.divides {
+        generator.emit(".include <math>");
+    }
+
+    Ok(generator.lines)
+}
+
+/// Returns the address expression of the cell for argument `idx`.
+fn argument(idx: usize) -> String {
+    match idx {
+        0 => "call.arguments".to_string(),
+        _ => format!("call.arguments + {}", idx),
+    }
+}
+
+/// Returns the value of an expression made of numbers, if it has one without overflowing.
+fn constant(expression: &Expression) -> Option<i32> {
+    match expression {
+        Expression::Number(value) => Some(*value),
+        Expression::Unary(operator, operand) => unary(*operator, constant(operand)?),
+        Expression::Binary(operator, left, right) => {
+            binary(*operator, constant(left)?, constant(right)?)
+        }
+        Expression::Variable { .. } | Expression::Call { .. } => None,
+    }
+}
+
+fn unary(operator: UnaryOperator, value: i32) -> Option<i32> {
+    match operator {
+        UnaryOperator::Negate => value.checked_neg(),
+        UnaryOperator::Not => Some((value == 0) as i32),
+    }
+}
+
+fn binary(operator: BinaryOperator, left: i32, right: i32) -> Option<i32> {
+    Some(match operator {
+        BinaryOperator::Add => left.checked_add(right)?,
+        BinaryOperator::Subtract => left.checked_sub(right)?,
+        BinaryOperator::Multiply => left.checked_mul(right)?,
+        BinaryOperator::Divide => left.checked_div(right)?,
+        BinaryOperator::Remainder => left.checked_rem(right)?,
+        BinaryOperator::Less => (left < right) as i32,
+        BinaryOperator::LessEqual => (left <= right) as i32,
+        BinaryOperator::Greater => (left > right) as i32,
+        BinaryOperator::GreaterEqual => (left >= right) as i32,
+        BinaryOperator::Equal => (left == right) as i32,
+        BinaryOperator::NotEqual => (left != right) as i32,
+        BinaryOperator::And => (left != 0 && right != 0) as i32,
+        BinaryOperator::Or => (left != 0 || right != 0) as i32,
+    })
+}
+
+impl<'u> Generator<'u> {
+    fn emit(&mut self, instruction: &str) {
+        self.lines
+            .push((format!("        {}", instruction), self.line));
+    }
+
+    fn label(&mut self, label: &str) {
+        self.lines.push((format!("{}:", label), self.line));
+    }
+
+    fn blank(&mut self) {
+        self.lines.push((String::new(), self.line));
+    }
+
+    /// Starts the code of a source line, copying the line into a comment the first time.
+    fn source_line(&mut self, position: Position) {
+        self.line = position.line;
+        if self.commented != position.line {
+            self.commented = position.line;
+            let text = self
+                .source
+                .get(position.line - 1)
+                .map_or("", |text| text.trim());
+            self.lines
+                .push((format!("        # {}", text), position.line));
+        }
+    }
+
+    fn function(&mut self, function: &'u Function) -> Result<(), CompileError> {
+        let mut frame = Frame::new(function);
+        let mut parameters = Vec::new();
+        for parameter in &function.parameters {
+            if parameters.iter().any(|(name, _)| name == parameter) {
+                return Err(function
+                    .position
+                    .error(format!("`{}` is already a parameter", parameter)));
+            }
+            parameters.push((parameter.as_str(), frame.allocate()));
+        }
+        frame.scopes.push(parameters);
+
+        self.blank();
+        self.source_line(function.position);
+        let prologue = self.lines.len();
+
+        self.block(&mut frame, &function.body)?;
+        self.line = function.position.line;
+        if !matches!(
+            function.body.last(),
+            Some(Statement {
+                kind: StatementKind::Return(_),
+                ..
+            })
+        ) {
+            self.emit("mov 0, [call.result]");
+        }
+
+        // The frame is only known now, so the code that allocates it goes in before the body.
+        let size = frame.cells.len();
+        if size > STACK_SIZE {
+            return Err(function.position.error(format!(
+                "`{}` needs {} cells but the stack only holds {}",
+                function.name, size, STACK_SIZE
+            )));
+        }
+        let body = self.lines.split_off(prologue);
+        self.label(&format!("fn.{}", function.name));
+        if size > 0 {
+            self.emit(&format!(
+                "jnz [rb + stack.full + {}], stack.overflow",
+                size - 1
+            ));
+            self.emit(&format!("arb {}", size));
+        }
+        for idx in 0..function.parameters.len() {
+            self.emit(&format!("mov [{}], [{}]", argument(idx), frame.cell(idx)));
+        }
+        self.lines.extend(body);
+
+        self.label(&format!("fn.{}.return", function.name));
+        if size > 0 {
+            self.emit(&format!("arb -{}", size));
+        }
+        self.emit("ret");
+
+        Ok(())
+    }
+
+    fn block(
+        &mut self,
+        frame: &mut Frame<'u>,
+        statements: &'u [Statement],
+    ) -> Result<(), CompileError> {
+        frame.scopes.push(Vec::new());
+        for statement in statements {
+            self.statement(frame, statement)?;
+        }
+        for (_, cell) in frame.scopes.pop().unwrap_or_default() {
+            frame.cells[cell] = false;
+        }
+
+        Ok(())
+    }
+
+    fn statement(
+        &mut self,
+        frame: &mut Frame<'u>,
+        statement: &'u Statement,
+    ) -> Result<(), CompileError> {
+        self.source_line(statement.position);
+
+        match &statement.kind {
+            StatementKind::Let { name, value } => {
+                let cell = match self.expression(frame, value)? {
+                    Operand::Temporary(cell) => cell,
+                    value => {
+                        let cell = frame.allocate();
+                        self.emit(&format!(
+                            "mov {}, [{}]",
+                            frame.format(value),
+                            frame.cell(cell)
+                        ));
+                        cell
+                    }
+                };
+                if let Some(scope) = frame.scopes.last_mut() {
+                    scope.push((name, cell));
+                }
+            }
+            StatementKind::Assign { name, value } => {
+                let variable = self.variable(frame, name, statement.position)?;
+                let value = self.expression(frame, value)?;
+                self.emit(&format!(
+                    "mov {}, {}",
+                    frame.format(value),
+                    frame.format(variable)
+                ));
+                frame.release(value);
+            }
+            StatementKind::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let [otherwise_label, end] = frame.labels(["else", "end"]);
+                let condition = self.expression(frame, condition)?;
+                self.emit(&format!(
+                    "jz {}, {}",
+                    frame.format(condition),
+                    if otherwise.is_empty() {
+                        &end
+                    } else {
+                        &otherwise_label
+                    }
+                ));
+                frame.release(condition);
+
+                self.block(frame, then)?;
+                if !otherwise.is_empty() {
+                    self.line = statement.position.line;
+                    self.emit(&format!("jmp {}", end));
+                    self.label(&otherwise_label);
+                    self.block(frame, otherwise)?;
+                }
+                self.label(&end);
+            }
+            StatementKind::While { condition, body } => {
+                let [start, end] = frame.labels(["while", "end"]);
+                self.label(&start);
+                let condition = self.expression(frame, condition)?;
+                self.emit(&format!("jz {}, {}", frame.format(condition), end));
+                frame.release(condition);
+
+                self.block(frame, body)?;
+                self.line = statement.position.line;
+                self.emit(&format!("jmp {}", start));
+                self.label(&end);
+            }
+            StatementKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expression(frame, value)?,
+                    None => Operand::Value(0),
+                };
+                self.emit(&format!("mov {}, [call.result]", frame.format(value)));
+                frame.release(value);
+                self.emit(&format!("jmp fn.{}.return", frame.function.name));
+            }
+            StatementKind::Expression(value) => {
+                let value = self.expression(frame, value)?;
+                frame.release(value);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn variable(
+        &self,
+        frame: &Frame<'u>,
+        name: &str,
+        position: Position,
+    ) -> Result<Operand<'u>, CompileError> {
+        if let Some(cell) = frame.variable(name) {
+            return Ok(Operand::Local(cell));
+        }
+
+        match self.globals.get_key_value(name) {
+            Some((name, _)) => Ok(Operand::Global(name)),
+            None => Err(position.error(format!("Unknown variable `{}`", name))),
+        }
+    }
+
+    fn expression(
+        &mut self,
+        frame: &mut Frame<'u>,
+        expression: &'u Expression,
+    ) -> Result<Operand<'u>, CompileError> {
+        match expression {
+            Expression::Number(value) => Ok(Operand::Value(*value)),
+            Expression::Variable { name, position } => self.variable(frame, name, *position),
+            Expression::Call {
+                name,
+                arguments,
+                position,
+            } => self.call(frame, name, arguments, *position),
+            Expression::Unary(operator, operand) => {
+                let operand = self.expression(frame, operand)?;
+                Ok(self.unary(frame, *operator, operand))
+            }
+            Expression::Binary(
+                operator @ (BinaryOperator::And | BinaryOperator::Or),
+                left,
+                right,
+            ) => self.logical(frame, *operator, left, right),
+            Expression::Binary(operator, left, right) => {
+                let mut left = self.expression(frame, left)?;
+                if right.calls(is_builtin) {
+                    left = self.stable(frame, left);
+                }
+                let right = self.expression(frame, right)?;
+                Ok(self.binary(frame, *operator, left, right))
+            }
+        }
+    }
+
+    /// Copies a global into a temporary, so that a function called before it is used cannot
+    /// change it.
+    fn stable(&mut self, frame: &mut Frame<'u>, operand: Operand<'u>) -> Operand<'u> {
+        match operand {
+            Operand::Global(_) => {
+                let cell = frame.allocate();
+                self.emit(&format!(
+                    "mov {}, [{}]",
+                    frame.format(operand),
+                    frame.cell(cell)
+                ));
+                Operand::Temporary(cell)
+            }
+            _ => operand,
+        }
+    }
+
+    fn call(
+        &mut self,
+        frame: &mut Frame<'u>,
+        name: &'u str,
+        arguments: &'u [Expression],
+        position: Position,
+    ) -> Result<Operand<'u>, CompileError> {
+        let parameters = match BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+            Some((_, parameters)) => *parameters,
+            None => match self.functions.get(name) {
+                Some(function) => function.parameters.len(),
+                None => return Err(position.error(format!("Unknown function `{}`", name))),
+            },
+        };
+        if arguments.len() != parameters {
+            return Err(position.error(format!(
+                "`{}` takes {} argument{} but {} {} given",
+                name,
+                parameters,
+                if parameters == 1 { "" } else { "s" },
+                arguments.len(),
+                if arguments.len() == 1 { "was" } else { "were" }
+            )));
+        }
+
+        let mut values = Vec::with_capacity(arguments.len());
+        for (idx, argument) in arguments.iter().enumerate() {
+            let mut value = self.expression(frame, argument)?;
+            if arguments[idx + 1..]
+                .iter()
+                .any(|argument| argument.calls(is_builtin))
+            {
+                value = self.stable(frame, value);
+            }
+            values.push(value);
+        }
+
+        match name {
+            "input" => {
+                let cell = frame.allocate();
+                self.emit(&format!("in [{}]", frame.cell(cell)));
+                Ok(Operand::Temporary(cell))
+            }
+            "output" => {
+                self.emit(&format!("out {}", frame.format(values[0])));
+                frame.release(values[0]);
+                Ok(Operand::Value(0))
+            }
+            _ => {
+                for (idx, value) in values.into_iter().enumerate() {
+                    self.emit(&format!("mov {}, [{}]", frame.format(value), argument(idx)));
+                    frame.release(value);
+                }
+                self.arguments = self.arguments.max(arguments.len());
+                self.emit(&format!("call fn.{}", name));
+
+                let cell = frame.allocate();
+                self.emit(&format!("mov [call.result], [{}]", frame.cell(cell)));
+                Ok(Operand::Temporary(cell))
+            }
+        }
+    }
+
+    fn unary(
+        &mut self,
+        frame: &mut Frame<'u>,
+        operator: UnaryOperator,
+        operand: Operand<'u>,
+    ) -> Operand<'u> {
+        if let Operand::Value(value) = operand {
+            if let Some(value) = unary(operator, value) {
+                return Operand::Value(value);
+            }
+        }
+
+        let cell = frame.result(&[operand]);
+        let instruction = match operator {
+            UnaryOperator::Negate => format!("mul {}, -1", frame.format(operand)),
+            UnaryOperator::Not => format!("eq {}, 0", frame.format(operand)),
+        };
+        self.emit(&format!("{}, [{}]", instruction, frame.cell(cell)));
+
+        Operand::Temporary(cell)
+    }
+
+    fn binary(
+        &mut self,
+        frame: &mut Frame<'u>,
+        operator: BinaryOperator,
+        left: Operand<'u>,
+        right: Operand<'u>,
+    ) -> Operand<'u> {
+        if let (Operand::Value(left), Operand::Value(right)) = (left, right) {
+            if let Some(value) = binary(operator, left, right) {
+                return Operand::Value(value);
+            }
+        }
+        if operator == BinaryOperator::Subtract {
+            let right = self.unary(frame, UnaryOperator::Negate, right);
+            return self.binary(frame, BinaryOperator::Add, left, right);
+        }
+
+        let cell = frame.result(&[left, right]);
+        let destination = format!("[{}]", frame.cell(cell));
+        let (left, right) = (frame.format(left), frame.format(right));
+        let (instruction, negate) = match operator {
+            BinaryOperator::Add => (format!("add {}, {}", left, right), false),
+            BinaryOperator::Multiply => (format!("mul {}, {}", left, right), false),
+            BinaryOperator::Less => (format!("lt {}, {}", left, right), false),
+            BinaryOperator::Greater => (format!("lt {}, {}", right, left), false),
+            BinaryOperator::LessEqual => (format!("lt {}, {}", right, left), true),
+            BinaryOperator::GreaterEqual => (format!("lt {}, {}", left, right), true),
+            BinaryOperator::Equal => (format!("eq {}, {}", left, right), false),
+            BinaryOperator::NotEqual => (format!("eq {}, {}", left, right), true),
+            BinaryOperator::Divide | BinaryOperator::Remainder => {
+                self.divides = true;
+                self.emit(&format!("mov {}, [arg0]", left));
+                self.emit(&format!("mov {}, [arg1]", right));
+                self.emit("call divide");
+                let result = if operator == BinaryOperator::Divide {
+                    "res0"
+                } else {
+                    "res1"
+                };
+                self.emit(&format!("mov [{}], {}", result, destination));
+                return Operand::Temporary(cell);
+            }
+            BinaryOperator::Subtract | BinaryOperator::And | BinaryOperator::Or => {
+                unreachable!("`-`, `&&` and `||` are built from other operations")
+            }
+        };
+
+        self.emit(&format!("{}, {}", instruction, destination));
+        if negate {
+            self.emit(&format!("eq {}, 0, {}", destination, destination));
+        }
+
+        Operand::Temporary(cell)
+    }
+
+    /// Generates `&&` and `||`, which only evaluate their right operand when the left one does
+    /// not decide the result.
+    fn logical(
+        &mut self,
+        frame: &mut Frame<'u>,
+        operator: BinaryOperator,
+        left: &'u Expression,
+        right: &'u Expression,
+    ) -> Result<Operand<'u>, CompileError> {
+        let and = operator == BinaryOperator::And;
+        let left = self.expression(frame, left)?;
+
+        if let Operand::Value(value) = left {
+            if (value != 0) != and {
+                return Ok(Operand::Value(!and as i32));
+            }
+            let right = self.expression(frame, right)?;
+            return Ok(self.binary(frame, BinaryOperator::NotEqual, right, Operand::Value(0)));
+        }
+
+        // The cell holds the negated result until the end.
+        let cell = frame.result(&[left]);
+        let destination = format!("[{}]", frame.cell(cell));
+        let [end] = frame.labels([if and { "and" } else { "or" }]);
+        self.emit(&format!("eq {}, 0, {}", frame.format(left), destination));
+        self.emit(&format!(
+            "{} {}, {}",
+            if and { "jnz" } else { "jz" },
+            destination,
+            end
+        ));
+
+        let right = self.expression(frame, right)?;
+        self.emit(&format!("eq {}, 0, {}", frame.format(right), destination));
+        frame.release(right);
+        self.label(&end);
+        self.emit(&format!("eq {}, 0, {}", destination, destination));
+
+        Ok(Operand::Temporary(cell))
+    }
+}
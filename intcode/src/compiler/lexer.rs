@@ -0,0 +1,221 @@
+//! Splits source text into tokens.
+
+use super::{CompileError, Position};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Number(i32),
+    Identifier(String),
+    Fn,
+    Let,
+    If,
+    Else,
+    While,
+    Return,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Semicolon,
+    Assign,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Not,
+    And,
+    Or,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::Number(value) => return write!(f, "`{}`", value),
+            Token::Identifier(name) => return write!(f, "`{}`", name),
+            Token::End => return write!(f, "the end of the file"),
+            Token::Fn => "fn",
+            Token::Let => "let",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::Return => "return",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::Comma => ",",
+            Token::Semicolon => ";",
+            Token::Assign => "=",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Equal => "==",
+            Token::NotEqual => "!=",
+            Token::Not => "!",
+            Token::And => "&&",
+            Token::Or => "||",
+        };
+
+        write!(f, "`{}`", text)
+    }
+}
+
+/// Returns the tokens of `text` with where they start, ending with `Token::End`.
+pub fn tokenise(text: &str) -> Result<Vec<(Token, Position)>, CompileError> {
+    let mut tokens = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut column = 0;
+
+        while column < chars.len() {
+            let position = Position::new(idx + 1, column + 1);
+            let c = chars[column];
+            let next = chars.get(column + 1).copied();
+
+            if c.is_whitespace() {
+                column += 1;
+                continue;
+            }
+            if c == '/' && next == Some('/') {
+                break;
+            }
+
+            let start = column;
+            let token = if c.is_ascii_digit() {
+                while chars.get(column).is_some_and(char::is_ascii_alphanumeric) {
+                    column += 1;
+                }
+                let digits: String = chars[start..column].iter().collect();
+                let value = digits.parse().map_err(|_| {
+                    position.error(format!("`{}` is not a number that fits a cell", digits))
+                })?;
+
+                Token::Number(value)
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                while chars
+                    .get(column)
+                    .is_some_and(|&c| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    column += 1;
+                }
+                let word: String = chars[start..column].iter().collect();
+
+                match word.as_str() {
+                    "fn" => Token::Fn,
+                    "let" => Token::Let,
+                    "if" => Token::If,
+                    "else" => Token::Else,
+                    "while" => Token::While,
+                    "return" => Token::Return,
+                    _ => Token::Identifier(word),
+                }
+            } else {
+                let (token, len) = match (c, next) {
+                    ('<', Some('=')) => (Token::LessEqual, 2),
+                    ('>', Some('=')) => (Token::GreaterEqual, 2),
+                    ('=', Some('=')) => (Token::Equal, 2),
+                    ('!', Some('=')) => (Token::NotEqual, 2),
+                    ('&', Some('&')) => (Token::And, 2),
+                    ('|', Some('|')) => (Token::Or, 2),
+                    ('(', _) => (Token::LeftParen, 1),
+                    (')', _) => (Token::RightParen, 1),
+                    ('{', _) => (Token::LeftBrace, 1),
+                    ('}', _) => (Token::RightBrace, 1),
+                    (',', _) => (Token::Comma, 1),
+                    (';', _) => (Token::Semicolon, 1),
+                    ('=', _) => (Token::Assign, 1),
+                    ('+', _) => (Token::Plus, 1),
+                    ('-', _) => (Token::Minus, 1),
+                    ('*', _) => (Token::Star, 1),
+                    ('/', _) => (Token::Slash, 1),
+                    ('%', _) => (Token::Percent, 1),
+                    ('<', _) => (Token::Less, 1),
+                    ('>', _) => (Token::Greater, 1),
+                    ('!', _) => (Token::Not, 1),
+                    _ => return Err(position.error(format!("Unexpected character `{}`", c))),
+                };
+                column += len;
+
+                token
+            };
+
+            tokens.push((token, position));
+        }
+    }
+
+    let end = match text.lines().enumerate().last() {
+        Some((idx, line)) => Position::new(idx + 1, line.chars().count() + 1),
+        None => Position::new(1, 1),
+    };
+    tokens.push((Token::End, end));
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<Token> {
+        tokenise(text)
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    #[test]
+    fn operators_take_the_longest_match() {
+        assert_eq!(
+            kinds("a<=b<c!=!d // comment <"),
+            [
+                Token::Identifier("a".into()),
+                Token::LessEqual,
+                Token::Identifier("b".into()),
+                Token::Less,
+                Token::Identifier("c".into()),
+                Token::NotEqual,
+                Token::Not,
+                Token::Identifier("d".into()),
+                Token::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn positions_and_errors() {
+        let tokens = tokenise("fn main() {\n    let x_1 = 42;\n}").unwrap();
+
+        assert_eq!(tokens[5], (Token::Let, Position::new(2, 5)));
+        assert_eq!(
+            tokens[6],
+            (Token::Identifier("x_1".into()), Position::new(2, 9))
+        );
+        assert_eq!(tokens[8], (Token::Number(42), Position::new(2, 15)));
+        assert_eq!(
+            tokenise("let x = 3 # 4;").unwrap_err(),
+            Position::new(1, 11).error("Unexpected character `#`")
+        );
+        assert_eq!(
+            tokenise("\n  9999999999").unwrap_err(),
+            Position::new(2, 3).error("`9999999999` is not a number that fits a cell")
+        );
+    }
+}
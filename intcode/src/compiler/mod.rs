@@ -0,0 +1,497 @@
+//! Compiles a small structured language to Intcode, through the assembler.
+//!
+//! ```text
+//! // Greatest common divisor of two inputs.
+//! let calls = 0;
+//!
+//! fn gcd(a, b) {
+//!     calls = calls + 1;
+//!     if b == 0 {
+//!         return a;
+//!     }
+//!     return gcd(b, a % b);
+//! }
+//!
+//! fn main() {
+//!     output(gcd(input(), input()));
+//!     output(calls);
+//! }
+//! ```
+//!
+//! Every value is an integer that fits a cell. Programs are made of functions and of global
+//! variables, which start with the value of a constant expression. Running a program calls
+//! `main`, which takes no parameters.
+//!
+//! Statements:
+//!
+//! - `let name = expression;` declares a variable for the rest of its block, hiding any
+//!   variable of the same name declared before.
+//! - `name = expression;` assigns to a variable.
+//! - `if condition { ... } else if condition { ... } else { ... }` and
+//!   `while condition { ... }`, where conditions are true when they are not 0.
+//! - `return expression;` and `return;`, which returns 0, as does the end of a function.
+//! - `expression;`, usually a call.
+//!
+//! Expressions combine numbers, variables and calls with the operators below, from the
+//! loosest to the tightest binding. Comparisons and logical operators give 0 or 1, `&&` and `||`
+//! only evaluate their right operand when needed, and `/` and `%` round towards zero like
+//! Rust. Overflows and dividing by 0 make the program fail.
+//!
+//! - `||`
+//! - `&&`
+//! - `==`, `!=`
+//! - `<`, `<=`, `>`, `>=`
+//! - `+`, `-`
+//! - `*`, `/`, `%`
+//! - unary `-` and `!`
+//!
+//! `input()` reads a value and `output(value)` writes one. `//` starts a comment.
+//!
+//! Every call keeps its parameters, variables and temporaries on the stack of the assembler
+//! library, which holds 256 cells. A program that recurses too deeply fails at
+//! `stack.overflow`, and a function that needs more cells than the stack holds does not compile.
+
+mod codegen;
+mod lexer;
+mod parser;
+
+use super::assembler::{self, Assembly, Location};
+use parser::Parser;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const EXTENSION: &str = "icl";
+
+/// A line and column of a source file, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    fn error<M>(self, message: M) -> CompileError
+    where
+        M: Into<Cow<'static, str>>,
+    {
+        CompileError {
+            file: String::new(),
+            position: self,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub file: String,
+    pub position: Position,
+    pub message: Cow<'static, str>,
+}
+
+impl CompileError {
+    fn in_file(self, file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.position.line, self.position.column, self.message
+        )
+    }
+}
+
+impl Error for CompileError {}
+
+impl From<CompileError> for Cow<'static, str> {
+    fn from(error: CompileError) -> Self {
+        error.to_string().into()
+    }
+}
+
+/// Compiles `text` into assembly for `assembler::assemble`. `name` is the file errors refer to.
+pub fn compile_to_assembly(text: &str, name: &str) -> Result<String, CompileError> {
+    let lines = generate(text).map_err(|error| error.in_file(name))?;
+
+    Ok(lines.into_iter().map(|(line, _)| line + "\n").collect())
+}
+
+/// Compiles `text` into a program whose source map refers to the lines of `text` in the file
+/// `name`, and to the library for the routines it uses.
+pub fn compile(text: &str, name: &str) -> Result<Assembly, CompileError> {
+    let lines = generate(text).map_err(|error| error.in_file(name))?;
+    let assembly: String = lines
+        .iter()
+        .map(|(line, _)| format!("{}\n", line))
+        .collect();
+
+    // Lines of the generated assembly, which the assembler calls `<input>`, map to the line
+    // of `text` they were generated for.
+    let source_line = |location: &Location| match location.file.as_str() {
+        "<input>" => lines
+            .get(location.line.wrapping_sub(1))
+            .map(|(_, line)| Location::new(name, *line)),
+        _ => None,
+    };
+
+    let mut assembly = assembler::assemble(&assembly, Path::new(".")).map_err(|error| {
+        let line = source_line(&error.location).map_or(1, |location| location.line);
+        Position::new(line, 1)
+            .error(format!("Failed to assemble the program: {}", error.message))
+            .in_file(name)
+    })?;
+    assembly
+        .source_map
+        .map_locations(|location| source_line(location).unwrap_or_else(|| location.clone()));
+
+    Ok(assembly)
+}
+
+pub fn compile_file<P>(path: P) -> Result<Assembly, CompileError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|error| {
+        Position::new(1, 1)
+            .error(format!("Failed to read {}: {}", name, error))
+            .in_file(&name)
+    })?;
+
+    compile(&text, &name)
+}
+
+/// Returns the lines of assembly for `text`, with the line of `text` each was generated for.
+fn generate(text: &str) -> Result<Vec<(String, usize)>, CompileError> {
+    let unit = Parser::new(lexer::tokenise(text)?).parse()?;
+
+    codegen::generate(&unit, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{adapter::IteratorInput, programmable::ProgrammableOutput};
+    use crate::Program;
+
+    fn run(text: &str, inputs: Vec<i32>) -> Result<Vec<i32>, Cow<'static, str>> {
+        let memory = compile(text, "test.icl")?.memory;
+        let mut input = IteratorInput::new(inputs);
+        let mut output = ProgrammableOutput::new();
+        Program::new(memory, &mut input, &mut output).run()?;
+
+        Ok(output.output())
+    }
+
+    fn error(text: &str) -> String {
+        compile(text, "test.icl").unwrap_err().to_string()
+    }
+
+    #[test]
+    fn arithmetic_and_precedence() {
+        let text = "
+            fn main() {
+                let a = input();
+                let b = input();
+                output(a + b * 2 - -3);
+                output((a - b) * (a + b));
+                output(a / b);
+                output(a % b);
+                output(-a / b);
+                output(1 + 2 * 3 - 4 / 2);
+            }
+        ";
+
+        assert_eq!(run(text, vec![17, 5]).unwrap(), [30, 264, 3, 2, -3, 5]);
+        assert_eq!(run(text, vec![-7, 2]).unwrap(), [0, 45, -3, -1, 3, 5]);
+    }
+
+    #[test]
+    fn comparisons_and_logic() {
+        let text = "
+            fn main() {
+                let a = input();
+                let b = input();
+                output((a < b) + 2 * (a <= b) + 4 * (a > b) + 8 * (a >= b));
+                output((a == b) + 2 * (a != b) + 4 * !a);
+                output(a && b);
+                output(a || b);
+            }
+        ";
+
+        assert_eq!(run(text, vec![1, 2]).unwrap(), [3, 2, 1, 1]);
+        assert_eq!(run(text, vec![2, 2]).unwrap(), [10, 1, 1, 1]);
+        assert_eq!(run(text, vec![0, -2]).unwrap(), [12, 6, 0, 1]);
+        assert_eq!(run(text, vec![0, 0]).unwrap(), [10, 5, 0, 0]);
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let text = "
+            fn say(value) {
+                output(value);
+                return value;
+            }
+
+            fn main() {
+                let a = say(0) && say(1);
+                let b = say(2) || say(3);
+                let c = say(4) && say(0);
+                output(a + 2 * b + 4 * c);
+            }
+        ";
+
+        assert_eq!(run(text, vec![]).unwrap(), [0, 2, 4, 0, 2]);
+    }
+
+    #[test]
+    fn control_flow_and_scopes() {
+        let text = "
+            fn main() {
+                let n = input();
+                let sum = 0;
+                while n > 0 {
+                    if n % 3 == 0 {
+                        sum = sum + 100;
+                    } else if n % 3 == 1 {
+                        sum = sum + 10;
+                    } else {
+                        sum = sum + 1;
+                    }
+                    n = n - 1;
+                }
+                output(sum);
+            }
+        ";
+
+        assert_eq!(run(text, vec![7]).unwrap(), [232]);
+
+        let text = "
+            fn main() {
+                let x = 1;
+                if x {
+                    let x = x + 1;
+                    output(x);
+                }
+                output(x);
+            }
+        ";
+
+        assert_eq!(run(text, vec![]).unwrap(), [2, 1]);
+    }
+
+    #[test]
+    fn every_call_gets_a_frame() {
+        let text = "
+            let calls = 0;
+
+            fn fib(n) {
+                calls = calls + 1;
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn even(n) {
+                if n == 0 { return 1; }
+                return odd(n - 1);
+            }
+
+            fn odd(n) {
+                if n == 0 { return 0; }
+                return even(n - 1);
+            }
+
+            fn main() {
+                output(fib(input()));
+                output(calls);
+                output(even(7) * 10 + odd(7));
+            }
+        ";
+
+        assert_eq!(run(text, vec![10]).unwrap(), [55, 177, 1]);
+    }
+
+    #[test]
+    fn deep_recursion_fails_at_stack_overflow() {
+        let text = "
+            fn down(n) {
+                if n == 0 {
+                    return 0;
+                }
+                return down(n - 1) + n % 7;
+            }
+
+            fn main() {
+                output(down(input()));
+            }
+        ";
+        let expected = (1..=30).map(|n| n % 7).sum::<i32>();
+        assert_eq!(run(text, vec![30]).unwrap(), [expected]);
+
+        let assembly = compile(text, "test.icl").unwrap();
+        let mut input = IteratorInput::new(vec![1000]);
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(assembly.memory, &mut input, &mut output);
+
+        assert!(program.run().is_err());
+        assert_eq!(
+            program.instruction_pointer(),
+            assembly.labels["stack.overflow"]
+        );
+    }
+
+    #[test]
+    fn calls_see_globals_in_order() {
+        let text = "
+            let counter = 10;
+
+            fn bump() {
+                counter = counter + 1;
+                return counter;
+            }
+
+            fn pair(a, b) {
+                return a * 100 + b;
+            }
+
+            fn main() {
+                output(counter + bump());
+                output(pair(counter, bump()));
+            }
+        ";
+
+        assert_eq!(run(text, vec![]).unwrap(), [21, 1112]);
+    }
+
+    #[test]
+    fn division_of_the_smallest_value() {
+        let text = "
+            fn main() {
+                let x = input();
+                output(x / 1);
+                output(x / 2);
+                output(x % 2);
+                output(x / -7);
+                output(x % -7);
+            }
+        ";
+
+        assert_eq!(
+            run(text, vec![i32::MIN]).unwrap(),
+            [i32::MIN, i32::MIN / 2, 0, i32::MIN / -7, i32::MIN % -7]
+        );
+        assert!(run("fn main() { output(input() / -1); }", vec![i32::MIN]).is_err());
+    }
+
+    #[test]
+    fn runtime_failures() {
+        assert!(run("fn main() { output(1 / input()); }", vec![0]).is_err());
+        assert!(run(
+            "fn main() { let x = 2147483647; output(x + input()); }",
+            vec![1]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn source_map_refers_to_the_source() {
+        let text = "fn main() {\n    let x = input();\n    output(x * 2);\n}\n";
+        let assembly = compile(text, "double.icl").unwrap();
+        let source_map = &assembly.source_map;
+        let address = source_map.address("fn.main").unwrap();
+
+        assert_eq!(source_map.symbolise(1), "start+1");
+        // The code that allocates the frame, a `jnz` and an `arb`, belongs to the function.
+        assert_eq!(
+            source_map.location(address),
+            Some(&Location::new("double.icl", 1))
+        );
+        assert_eq!(
+            source_map.location(address + 5),
+            Some(&Location::new("double.icl", 2))
+        );
+        assert_eq!(
+            source_map.location(0),
+            Some(&Location::new("double.icl", 1))
+        );
+        assert!(compile_to_assembly(text, "double.icl")
+            .unwrap()
+            .contains("        # output(x * 2);\n"));
+    }
+
+    #[test]
+    fn errors_name_the_position() {
+        assert_eq!(
+            error("fn main() {\n    output(y);\n}"),
+            "test.icl:2:12: Unknown variable `y`"
+        );
+        assert_eq!(
+            error("fn main() {\n    y = 1;\n}"),
+            "test.icl:2:5: Unknown variable `y`"
+        );
+        assert_eq!(
+            error("fn main() { f(); }"),
+            "test.icl:1:13: Unknown function `f`"
+        );
+        assert_eq!(
+            error("fn f(a) {}\nfn main() { f(1, 2); }"),
+            "test.icl:2:13: `f` takes 1 argument but 2 were given"
+        );
+        assert_eq!(
+            error("fn main() { output(); }"),
+            "test.icl:1:13: `output` takes 1 argument but 0 were given"
+        );
+        assert_eq!(
+            error("fn f() {}"),
+            "test.icl:1:1: There is no `main` function"
+        );
+        assert_eq!(
+            error("fn main(a) {}"),
+            "test.icl:1:1: `main` cannot take parameters"
+        );
+        assert_eq!(
+            error("fn main() {}\nfn main() {}"),
+            "test.icl:2:1: `main` is already defined"
+        );
+        assert_eq!(
+            error("fn input() {}\nfn main() {}"),
+            "test.icl:1:1: `input` is a builtin function"
+        );
+        assert_eq!(
+            error("let x = input();\nfn main() {}"),
+            "test.icl:1:1: The value of `x` must be a constant"
+        );
+        assert_eq!(
+            error("fn f(a, a) {}\nfn main() {}"),
+            "test.icl:1:1: `a` is already a parameter"
+        );
+        let lets: String = (0..257)
+            .map(|idx| format!("let x{} = {};", idx, idx))
+            .collect();
+        assert_eq!(
+            error(&format!("fn main() {{}}\nfn f() {{ {} }}", lets)),
+            "test.icl:2:1: `f` needs 257 cells but the stack only holds 256"
+        );
+        assert_eq!(
+            error("fn main() {\n  let x = 1\n}"),
+            "test.icl:3:1: Expected `;`, found `}`"
+        );
+    }
+}
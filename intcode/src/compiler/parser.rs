@@ -0,0 +1,507 @@
+//! Parses tokens into a syntax tree, by recursive descent with one function per precedence
+//! level.
+
+use super::lexer::Token;
+use super::{CompileError, Position};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+/// A variable outside of any function, which starts with the value of a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub value: Expression,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Vec<Statement>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
+    Let {
+        name: String,
+        value: Expression,
+    },
+    Assign {
+        name: String,
+        value: Expression,
+    },
+    If {
+        condition: Expression,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+    },
+    Return(Option<Expression>),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(i32),
+    Variable {
+        name: String,
+        position: Position,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+        position: Position,
+    },
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Returns whether evaluating the expression calls a function other than a builtin.
+    pub fn calls(&self, builtin: impl Fn(&str) -> bool + Copy) -> bool {
+        match self {
+            Expression::Number(_) | Expression::Variable { .. } => false,
+            Expression::Call {
+                name, arguments, ..
+            } => !builtin(name) || arguments.iter().any(|argument| argument.calls(builtin)),
+            Expression::Unary(_, operand) => operand.calls(builtin),
+            Expression::Binary(_, left, right) => left.calls(builtin) || right.calls(builtin),
+        }
+    }
+}
+
+/// Binary operators from the loosest to the tightest binding, all of them left associative.
+const PRECEDENCE: &[&[(Token, BinaryOperator)]] = &[
+    &[(Token::Or, BinaryOperator::Or)],
+    &[(Token::And, BinaryOperator::And)],
+    &[
+        (Token::Equal, BinaryOperator::Equal),
+        (Token::NotEqual, BinaryOperator::NotEqual),
+    ],
+    &[
+        (Token::Less, BinaryOperator::Less),
+        (Token::LessEqual, BinaryOperator::LessEqual),
+        (Token::Greater, BinaryOperator::Greater),
+        (Token::GreaterEqual, BinaryOperator::GreaterEqual),
+    ],
+    &[
+        (Token::Plus, BinaryOperator::Add),
+        (Token::Minus, BinaryOperator::Subtract),
+    ],
+    &[
+        (Token::Star, BinaryOperator::Multiply),
+        (Token::Slash, BinaryOperator::Divide),
+        (Token::Percent, BinaryOperator::Remainder),
+    ],
+];
+
+pub struct Parser {
+    tokens: Vec<(Token, Position)>,
+    next: usize,
+}
+
+impl Parser {
+    /// Creates a parser for tokens ending with `Token::End`.
+    pub fn new(tokens: Vec<(Token, Position)>) -> Self {
+        Self { tokens, next: 0 }
+    }
+
+    pub fn parse(mut self) -> Result<Unit, CompileError> {
+        let mut unit = Unit {
+            globals: Vec::new(),
+            functions: Vec::new(),
+        };
+
+        loop {
+            let position = self.position();
+            match self.advance() {
+                Token::Fn => unit.functions.push(self.function(position)?),
+                Token::Let => {
+                    let (name, value) = self.binding()?;
+                    unit.globals.push(Global {
+                        name,
+                        value,
+                        position,
+                    });
+                }
+                Token::End => return Ok(unit),
+                token => {
+                    return Err(position.error(format!("Expected `fn` or `let`, found {}", token)))
+                }
+            }
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn position(&self) -> Position {
+        self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == token;
+        if matches {
+            self.advance();
+        }
+
+        matches
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), CompileError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&token.to_string()))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, CompileError> {
+        match self.peek().clone() {
+            Token::Identifier(name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> CompileError {
+        self.position()
+            .error(format!("Expected {}, found {}", expected, self.peek()))
+    }
+
+    fn function(&mut self, position: Position) -> Result<Function, CompileError> {
+        let name = self.identifier()?;
+        let mut parameters = Vec::new();
+
+        self.expect(Token::LeftParen)?;
+        if !self.eat(&Token::RightParen) {
+            loop {
+                parameters.push(self.identifier()?);
+                if self.eat(&Token::RightParen) {
+                    break;
+                }
+                self.expect(Token::Comma)?;
+            }
+        }
+
+        Ok(Function {
+            name,
+            parameters,
+            body: self.block()?,
+            position,
+        })
+    }
+
+    /// Parses `name = value;` after a `let`.
+    fn binding(&mut self) -> Result<(String, Expression), CompileError> {
+        let name = self.identifier()?;
+        self.expect(Token::Assign)?;
+        let value = self.expression()?;
+        self.expect(Token::Semicolon)?;
+
+        Ok((name, value))
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, CompileError> {
+        let mut statements = Vec::new();
+
+        self.expect(Token::LeftBrace)?;
+        while !self.eat(&Token::RightBrace) {
+            statements.push(self.statement()?);
+        }
+
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, CompileError> {
+        let position = self.position();
+
+        let kind = match self.peek() {
+            Token::Let => {
+                self.advance();
+                let (name, value) = self.binding()?;
+                StatementKind::Let { name, value }
+            }
+            Token::If => {
+                self.advance();
+                return self.if_statement(position);
+            }
+            Token::While => {
+                self.advance();
+                StatementKind::While {
+                    condition: self.expression()?,
+                    body: self.block()?,
+                }
+            }
+            Token::Return => {
+                self.advance();
+                let value = match self.peek() {
+                    Token::Semicolon => None,
+                    _ => Some(self.expression()?),
+                };
+                self.expect(Token::Semicolon)?;
+                StatementKind::Return(value)
+            }
+            Token::Identifier(_) if self.tokens[self.next + 1].0 == Token::Assign => {
+                let (name, value) = self.binding()?;
+                StatementKind::Assign { name, value }
+            }
+            _ => {
+                let expression = self.expression()?;
+                self.expect(Token::Semicolon)?;
+                StatementKind::Expression(expression)
+            }
+        };
+
+        Ok(Statement { kind, position })
+    }
+
+    /// Parses what follows an `if`, turning `else if` into an `if` nested in the `else` block.
+    fn if_statement(&mut self, position: Position) -> Result<Statement, CompileError> {
+        let condition = self.expression()?;
+        let then = self.block()?;
+        let otherwise = if !self.eat(&Token::Else) {
+            Vec::new()
+        } else if self.peek() == &Token::If {
+            let position = self.position();
+            self.advance();
+            vec![self.if_statement(position)?]
+        } else {
+            self.block()?
+        };
+
+        Ok(Statement {
+            kind: StatementKind::If {
+                condition,
+                then,
+                otherwise,
+            },
+            position,
+        })
+    }
+
+    fn expression(&mut self) -> Result<Expression, CompileError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, CompileError> {
+        let operators = match PRECEDENCE.get(level) {
+            Some(operators) => operators,
+            None => return self.unary(),
+        };
+
+        let mut left = self.binary(level + 1)?;
+        while let Some((_, operator)) = operators.iter().find(|(token, _)| token == self.peek()) {
+            self.advance();
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, CompileError> {
+        let operator = match self.peek() {
+            Token::Minus => UnaryOperator::Negate,
+            Token::Not => UnaryOperator::Not,
+            _ => return self.primary(),
+        };
+        self.advance();
+
+        Ok(Expression::Unary(operator, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expression, CompileError> {
+        let position = self.position();
+
+        match self.peek().clone() {
+            Token::Number(value) => {
+                self.advance();
+                Ok(Expression::Number(value))
+            }
+            Token::Identifier(name) => {
+                self.advance();
+                if !self.eat(&Token::LeftParen) {
+                    return Ok(Expression::Variable { name, position });
+                }
+
+                let mut arguments = Vec::new();
+                if !self.eat(&Token::RightParen) {
+                    loop {
+                        arguments.push(self.expression()?);
+                        if self.eat(&Token::RightParen) {
+                            break;
+                        }
+                        self.expect(Token::Comma)?;
+                    }
+                }
+
+                Ok(Expression::Call {
+                    name,
+                    arguments,
+                    position,
+                })
+            }
+            Token::LeftParen => {
+                self.advance();
+                let expression = self.expression()?;
+                self.expect(Token::RightParen)?;
+                Ok(expression)
+            }
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::lexer;
+    use super::*;
+
+    fn parse(text: &str) -> Result<Unit, CompileError> {
+        Parser::new(lexer::tokenise(text)?).parse()
+    }
+
+    fn expression(text: &str) -> Expression {
+        let unit = parse(&format!("let x = {};", text)).unwrap();
+        unit.globals[0].value.clone()
+    }
+
+    fn number(value: i32) -> Box<Expression> {
+        Box::new(Expression::Number(value))
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        use BinaryOperator::*;
+
+        assert_eq!(
+            expression("1 + 2 * 3 - 4"),
+            Expression::Binary(
+                Subtract,
+                Box::new(Expression::Binary(
+                    Add,
+                    number(1),
+                    Box::new(Expression::Binary(Multiply, number(2), number(3)))
+                )),
+                number(4)
+            )
+        );
+        assert_eq!(
+            expression("-(1 || 2) < 3 && !4"),
+            Expression::Binary(
+                And,
+                Box::new(Expression::Binary(
+                    Less,
+                    Box::new(Expression::Unary(
+                        UnaryOperator::Negate,
+                        Box::new(Expression::Binary(Or, number(1), number(2)))
+                    )),
+                    number(3)
+                )),
+                Box::new(Expression::Unary(UnaryOperator::Not, number(4)))
+            )
+        );
+    }
+
+    #[test]
+    fn statements() {
+        let unit = parse(
+            "
+            fn main(a, b) {
+                a = f(b, 1);
+                if a { return; } else if b { g(); } else { return a; }
+                while 0 {}
+            }
+            ",
+        )
+        .unwrap();
+        let function = &unit.functions[0];
+
+        assert_eq!(function.parameters, ["a", "b"]);
+        assert_eq!(function.body.len(), 3);
+        assert_eq!(function.body[1].position, Position::new(4, 17));
+        match &function.body[1].kind {
+            StatementKind::If { otherwise, .. } => {
+                assert!(matches!(otherwise[0].kind, StatementKind::If { .. }))
+            }
+            kind => panic!("Unexpected statement {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = |text| parse(text).unwrap_err();
+
+        assert_eq!(
+            error("fn main() {\n    let x = 1\n}"),
+            Position::new(3, 1).error("Expected `;`, found `}`")
+        );
+        assert_eq!(
+            error("fn main() { x = (1 + ; }"),
+            Position::new(1, 22).error("Expected an expression, found `;`")
+        );
+        assert_eq!(
+            error("fn main( { }"),
+            Position::new(1, 10).error("Expected a name, found `{`")
+        );
+        assert_eq!(
+            error("fn main() {"),
+            Position::new(1, 12).error("Expected an expression, found the end of the file")
+        );
+        assert_eq!(
+            error("main() {}"),
+            Position::new(1, 1).error("Expected `fn` or `let`, found `main`")
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Execution coverage, collected by `Program::with_coverage` over any number of runs.
+
+use super::{source_map::SourceMap, validator, Operation};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Hit,
+    /// A conditional jump that always went the same way.
+    Partial,
+    Missed,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Status::Hit => "hit",
+            Status::Partial => "partial",
+            Status::Missed => "missed",
+        };
+
+        f.pad(status)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Hits {
+    count: usize,
+    /// Every instruction word executed at the address, with the instruction as it was then,
+    /// which matters for code the program wrote.
+    variants: Vec<(i32, String)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Coverage {
+    executed: BTreeMap<usize, Hits>,
+    /// How often each conditional jump was taken and not taken.
+    branches: BTreeMap<usize, (usize, usize)>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `instruction`, which starts with `word`, is about to be executed at
+    /// `address`.
+    pub fn hit<D>(&mut self, address: usize, word: i32, instruction: &D)
+    where
+        D: fmt::Display + ?Sized,
+    {
+        let hits = self.executed.entry(address).or_default();
+
+        hits.count += 1;
+        if hits.variants.iter().all(|(known, _)| *known != word) {
+            hits.variants.push((word, instruction.to_string()));
+        }
+    }
+
+    /// Records which way the conditional jump at `address` went.
+    pub fn branch(&mut self, address: usize, taken: bool) {
+        let (taken_count, not_taken_count) = self.branches.entry(address).or_insert((0, 0));
+
+        if taken {
+            *taken_count += 1;
+        } else {
+            *not_taken_count += 1;
+        }
+    }
+
+    pub fn hits(&self, address: usize) -> usize {
+        self.executed.get(&address).map_or(0, |hits| hits.count)
+    }
+
+    pub fn status(&self, address: usize) -> Status {
+        match self.branches.get(&address) {
+            _ if self.hits(address) == 0 => Status::Missed,
+            Some((0, _)) | Some((_, 0)) => Status::Partial,
+            _ => Status::Hit,
+        }
+    }
+
+    /// Lists every instruction that was executed or is reachable from one, with its status.
+    ///
+    /// `memory` is the program as loaded; instructions that were executed are shown as they
+    /// were at the time.
+    pub fn listing(&self, memory: &[i32]) -> String {
+        self.listing_with(memory, None)
+    }
+
+    /// Lists instructions like `listing`, below their labels and with their source lines.
+    pub fn source_listing(&self, memory: &[i32], source_map: &SourceMap) -> String {
+        self.listing_with(memory, Some(source_map))
+    }
+
+    fn listing_with(&self, memory: &[i32], source_map: Option<&SourceMap>) -> String {
+        let mut entry_points = vec![0];
+        entry_points.extend(self.executed.keys().copied());
+        let mut addresses = validator::validate_from(memory, &entry_points).instructions;
+        addresses.extend(self.executed.keys().copied());
+
+        let mut listing = String::new();
+        let (mut hit, mut partial, mut missed) = (0, 0, 0);
+        for address in addresses {
+            let status = self.status(address);
+            match status {
+                Status::Hit => hit += 1,
+                Status::Partial => partial += 1,
+                Status::Missed => missed += 1,
+            }
+
+            let loaded = memory
+                .get(address..)
+                .and_then(|words| Operation::from_slice(words).ok());
+            let text = match (self.executed.get(&address), source_map, loaded) {
+                // Instructions that never changed can be shown with their addresses symbolised.
+                (Some(hits), Some(source_map), Some(operation))
+                    if hits
+                        .variants
+                        .iter()
+                        .all(|(word, _)| *word == memory[address]) =>
+                {
+                    source_map.operation(&operation)
+                }
+                (Some(hits), _, _) => hits
+                    .variants
+                    .iter()
+                    .map(|(_, text)| text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+                (None, Some(source_map), Some(operation)) => source_map.operation(&operation),
+                (None, None, Some(operation)) => operation.to_string(),
+                (None, _, None) => format!("?? {}", memory[address]),
+            };
+
+            if let Some(source_map) = source_map {
+                for label in source_map.labels(address) {
+                    writeln!(listing, "{}:", label).unwrap();
+                }
+            }
+            write!(
+                listing,
+                "{:>7} {:>8} {:>6}  {}",
+                status,
+                self.hits(address),
+                address,
+                text
+            )
+            .unwrap();
+            if let Some((taken, not_taken)) = self.branches.get(&address) {
+                write!(listing, "  (taken {}, not taken {})", taken, not_taken).unwrap();
+            }
+            if let Some(location) = source_map.and_then(|source_map| source_map.location(address)) {
+                write!(listing, "  # {}", location).unwrap();
+            }
+            listing.push('\n');
+        }
+
+        writeln!(
+            listing,
+            "{} hit, {} partial, {} missed",
+            hit, partial, missed
+        )
+        .unwrap();
+
+        listing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::Program;
+
+    /// Outputs 0 if the input was 0 and 1 otherwise, from day 5.
+    fn is_non_zero() -> Vec<i32> {
+        vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9]
+    }
+
+    fn run(coverage: &mut Coverage, memory: Vec<i32>, input: i32, expected: i32) {
+        let mut input = UnitTestInput::new(vec![input]);
+        let mut output = UnitTestOutput::new(vec![expected]);
+        let mut program = Program::new(memory, &mut input, &mut output).with_coverage(coverage);
+
+        program.run().unwrap();
+    }
+
+    #[test]
+    fn single_run_leaves_branch_partial() {
+        let mut coverage = Coverage::new();
+        run(&mut coverage, is_non_zero(), 0, 0);
+
+        assert_eq!(coverage.hits(0), 1);
+        assert_eq!(coverage.status(2), Status::Partial);
+        assert_eq!(coverage.status(5), Status::Missed);
+        assert_eq!(coverage.status(9), Status::Hit);
+    }
+
+    #[test]
+    fn runs_accumulate() {
+        let mut coverage = Coverage::new();
+        run(&mut coverage, is_non_zero(), 0, 0);
+        run(&mut coverage, is_non_zero(), 5, 1);
+
+        assert_eq!(coverage.hits(9), 2);
+        assert_eq!(coverage.status(2), Status::Hit);
+        assert_eq!(coverage.status(5), Status::Hit);
+    }
+
+    #[test]
+    fn listing_marks_every_instruction() {
+        let mut coverage = Coverage::new();
+        run(&mut coverage, is_non_zero(), 0, 0);
+
+        assert_eq!(
+            coverage.listing(&is_non_zero()),
+            "    hit        1      0  in [12]
+partial        1      2  jz [12], [15]  (taken 1, not taken 0)
+ missed        0      5  add [13], [14], [13]
+    hit        1      9  out [13]
+    hit        1     11  halt
+3 hit, 1 partial, 1 missed
+"
+        );
+    }
+
+    #[test]
+    fn listing_shows_rewritten_code_as_executed() {
+        // Turns the invalid opcode 1100 at address 4 into 1101 before running it.
+        let memory = vec![1101, 1, 1100, 4, 1100, 2, 3, 0, 99];
+        let mut coverage = Coverage::new();
+        let mut input = UnitTestInput::new(Vec::new());
+        let mut output = UnitTestOutput::new(Vec::new());
+        Program::new(memory.clone(), &mut input, &mut output)
+            .with_coverage(&mut coverage)
+            .run()
+            .unwrap();
+
+        assert!(coverage
+            .listing(&memory)
+            .contains("      4  add 2, 3, [0]\n"));
+    }
+
+    #[test]
+    fn source_listing_shows_labels_and_lines() {
+        let text = "
+                    in [value]
+                    jz [value], skip
+                    out 1
+            skip:   out 0
+                    halt
+            value:  .word 0
+        ";
+        let assembly = crate::assembler::assemble(text, std::path::Path::new(".")).unwrap();
+        let mut coverage = Coverage::new();
+        run(&mut coverage, assembly.memory.clone(), 0, 0);
+
+        assert_eq!(
+            coverage.source_listing(&assembly.memory, &assembly.source_map),
+            "    hit        1      0  in [value]  # <input>:2
+partial        1      2  jz [value], skip  (taken 1, not taken 0)  # <input>:3
+ missed        0      5  out 1  # <input>:4
+skip:
+    hit        1      7  out 0  # <input>:5
+    hit        1      9  halt  # <input>:6
+3 hit, 1 partial, 1 missed
+"
+        );
+    }
+}
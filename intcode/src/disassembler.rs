@@ -0,0 +1,136 @@
+//! Lists a program as assembly, one instruction or run of data per line.
+//!
+//! Without a source map, the instructions reachable from address 0 are decoded and every other
+//! word is data. Runs of data that repeat one value are listed as `.zero` or `.fill`. With
+//! one, the lines it marks as code are decoded instead, addresses are shown relative to
+//! labels and every line that starts a source line names it.
+
+use super::{source_map::SourceMap, validator, Operation};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// The most data words listed on one line.
+const WORDS_PER_LINE: usize = 8;
+
+pub fn disassemble(memory: &[i32], source_map: Option<&SourceMap>) -> String {
+    let reachable: BTreeSet<usize> = match source_map {
+        Some(_) => BTreeSet::new(),
+        None => validator::validate(memory).instructions,
+    };
+    let is_code = |address: usize| match source_map {
+        Some(source_map) => source_map.line(address).is_some_and(|line| line.code),
+        None => reachable.contains(&address),
+    };
+    // Where data has to stop, so that labels and source lines can be shown.
+    let is_boundary = |address: usize| {
+        is_code(address)
+            || source_map.is_some_and(|source_map| {
+                source_map.starts_line(address) || source_map.labels(address).next().is_some()
+            })
+    };
+
+    let mut listing = String::new();
+    let mut address = 0;
+    while address < memory.len() {
+        if let Some(source_map) = source_map {
+            for label in source_map.labels(address) {
+                writeln!(listing, "{}:", label).unwrap();
+            }
+        }
+
+        let operation = Some(address)
+            .filter(|&address| is_code(address))
+            .and_then(|address| Operation::from_slice(&memory[address..]).ok());
+        let (text, len) = match operation {
+            Some(operation) => {
+                let text = match source_map {
+                    Some(source_map) => source_map.operation(&operation),
+                    None => operation.to_string(),
+                };
+                (text, operation.op_len())
+            }
+            None => {
+                let end = (address + 1..memory.len())
+                    .find(|&address| is_boundary(address))
+                    .unwrap_or(memory.len());
+                let words = &memory[address..end];
+
+                if words.len() > 1 && words.iter().all(|&word| word == 0) {
+                    (format!(".zero {}", words.len()), words.len())
+                } else if words.len() > 1 && words.iter().all(|&word| word == words[0]) {
+                    (format!(".fill {}, {}", words.len(), words[0]), words.len())
+                } else {
+                    let words = &words[..words.len().min(WORDS_PER_LINE)];
+                    let values: Vec<_> = words.iter().map(i32::to_string).collect();
+                    (format!(".word {}", values.join(", ")), words.len())
+                }
+            }
+        };
+
+        let location = source_map
+            .filter(|source_map| source_map.starts_line(address))
+            .and_then(|source_map| source_map.location(address));
+        match location {
+            Some(location) => writeln!(listing, "{:>8}  {:<40}  # {}", address, text, location),
+            None => writeln!(listing, "{:>8}  {}", address, text),
+        }
+        .unwrap();
+
+        address += len;
+    }
+
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+    use std::path::Path;
+
+    #[test]
+    fn without_source_map() {
+        let memory = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+
+        assert_eq!(
+            disassemble(&memory, None),
+            "       0  in [12]
+       2  jz [12], [15]
+       5  add [13], [14], [13]
+       9  out [13]
+      11  halt
+      12  .word -1, 0, 1, 9
+"
+        );
+    }
+
+    #[test]
+    fn with_source_map() {
+        let text = "
+            start:  in [value]
+                    jz [value], start
+                    halt
+            value:  .word 5
+            buffer: .zero 3
+            ones:   .fill 4, 1
+                    arb [rb - 2]
+        ";
+        let assembly = assembler::assemble(text, Path::new(".")).unwrap();
+
+        assert_eq!(
+            disassemble(&assembly.memory, Some(&assembly.source_map)),
+            "start:
+       0  in [value]                                # <input>:2
+       2  jz [value], start                         # <input>:3
+       5  halt                                      # <input>:4
+value:
+       6  .word 5                                   # <input>:5
+buffer:
+       7  .zero 3                                   # <input>:6
+ones:
+      10  .fill 4, 1                                # <input>:7
+      14  arb [rb - 2]                              # <input>:8
+"
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Custom instructions that embedders add to the instruction set.
+//!
+//! An extension reads a fixed number of parameters, which honour the usual position, immediate
+//! and relative modes, and may write a result to a destination, which cannot be in immediate
+//! mode just like the destination of `add`. Its closure runs on the host, so an
+//! extension is also how Intcode programs call into Rust, for example to log a value or to
+//! read a clock.
+
+use super::operations::{Parameter, ParameterMode, ToParameter};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Opcodes that belong to the built-in instruction set and cannot be replaced.
+pub const BUILT_IN_OPCODES: [i32; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+/// The most parameters, including the destination, that the mode digits of an opcode can cover.
+pub const MAX_ARITY: usize = 7;
+
+type Handler<'a> = Box<dyn FnMut(&[i32]) -> Result<Option<i32>, Cow<'static, str>> + 'a>;
+
+pub struct Extension<'a> {
+    mnemonic: &'static str,
+    parameters: usize,
+    destination: bool,
+    handler: Handler<'a>,
+}
+
+impl<'a> Extension<'a> {
+    /// An instruction that passes the values of its `parameters` to `handler`.
+    ///
+    /// The handler may return a value only when the instruction has a destination, and must
+    /// return one when it does.
+    pub fn new<F>(mnemonic: &'static str, parameters: usize, handler: F) -> Self
+    where
+        F: FnMut(&[i32]) -> Result<Option<i32>, Cow<'static, str>> + 'a,
+    {
+        Self {
+            mnemonic,
+            parameters,
+            destination: false,
+            handler: Box::new(handler),
+        }
+    }
+
+    /// Adds a destination address after the parameters, where the result is stored.
+    pub fn with_destination(mut self) -> Self {
+        self.destination = true;
+        self
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.mnemonic
+    }
+
+    /// The length of the instruction, including the opcode.
+    pub fn op_len(&self) -> usize {
+        1 + self.parameters + usize::from(self.destination)
+    }
+
+    /// Decodes the operands of the instruction that starts with `words[0]`.
+    pub fn decode(&self, words: &[i32]) -> Result<Decoded, Cow<'static, str>> {
+        if words.len() < self.op_len() {
+            return Err("Invalid instruction".into());
+        }
+
+        let mode = |idx: u32| ParameterMode::try_from((words[0] / 10i32.pow(idx + 2)) % 10);
+        let parameters = words[1..=self.parameters]
+            .iter()
+            .enumerate()
+            .map(|(idx, word)| Ok(word.to_parameter(&mode(idx as u32)?)))
+            .collect::<Result<Vec<_>, Cow<'static, str>>>()?;
+
+        let destination = if self.destination {
+            let mode = mode(self.parameters as u32)?;
+            if mode == ParameterMode::Immediate {
+                return Err("Immediate is an invalid mode for as a destination".into());
+            }
+            Some(words[self.parameters + 1].to_parameter(&mode))
+        } else {
+            None
+        };
+
+        Ok(Decoded {
+            mnemonic: self.mnemonic,
+            parameters,
+            destination,
+        })
+    }
+
+    /// Runs the handler with the loaded parameter values.
+    pub fn execute(&mut self, values: &[i32]) -> Result<Option<i32>, Cow<'static, str>> {
+        match ((self.handler)(values)?, self.destination) {
+            (Some(value), true) => Ok(Some(value)),
+            (None, false) => Ok(None),
+            (Some(_), false) => {
+                Err(format!("Extension '{}' has no destination", self.mnemonic).into())
+            }
+            (None, true) => {
+                Err(format!("Extension '{}' did not produce a value", self.mnemonic).into())
+            }
+        }
+    }
+}
+
+/// The operands of an extension instruction, formatted like a built-in `Operation`.
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    pub mnemonic: &'static str,
+    pub parameters: Vec<Parameter>,
+    pub destination: Option<Parameter>,
+}
+
+impl Decoded {
+    /// The length of the instruction, including the opcode.
+    pub fn op_len(&self) -> usize {
+        1 + self.parameters.len() + usize::from(self.destination.is_some())
+    }
+}
+
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+
+        let mut separator = " ";
+        for parameter in self.parameters.iter().chain(&self.destination) {
+            write!(f, "{}{}", separator, parameter)?;
+            separator = ", ";
+        }
+
+        Ok(())
+    }
+}
+
+/// Looks up and runs extension instructions for a `Program`.
+pub trait ExtensionSet {
+    /// Decodes the instruction at the start of `words`, or returns `None` when it is not an
+    /// extension.
+    fn decode(&self, words: &[i32]) -> Option<Result<Decoded, Cow<'static, str>>>;
+
+    /// Runs the extension for the instruction word `word` with the loaded parameter values.
+    fn execute(&mut self, word: i32, values: &[i32]) -> Result<Option<i32>, Cow<'static, str>>;
+}
+
+/// The extensions known to a program, by opcode.
+#[derive(Default)]
+pub struct Extensions<'a> {
+    by_opcode: HashMap<i32, Extension<'a>>,
+}
+
+impl<'a> Extensions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        opcode: i32,
+        extension: Extension<'a>,
+    ) -> Result<(), Cow<'static, str>> {
+        if !(1..100).contains(&opcode) || BUILT_IN_OPCODES.contains(&opcode) {
+            return Err(format!("Opcode {} is not available for extensions", opcode).into());
+        }
+        if extension.op_len() - 1 > MAX_ARITY {
+            return Err(format!(
+                "Extension '{}' has more than {} parameters",
+                extension.mnemonic, MAX_ARITY
+            )
+            .into());
+        }
+        if self.by_opcode.contains_key(&opcode) {
+            return Err(format!("Opcode {} is already registered", opcode).into());
+        }
+
+        self.by_opcode.insert(opcode, extension);
+        Ok(())
+    }
+
+    /// The extension for the instruction word `word`, which still includes its modes.
+    pub fn get(&self, word: i32) -> Option<&Extension<'a>> {
+        self.by_opcode.get(&(word % 100))
+    }
+}
+
+impl ExtensionSet for Extensions<'_> {
+    fn decode(&self, words: &[i32]) -> Option<Result<Decoded, Cow<'static, str>>> {
+        Some(self.get(*words.first()?)?.decode(words))
+    }
+
+    fn execute(&mut self, word: i32, values: &[i32]) -> Result<Option<i32>, Cow<'static, str>> {
+        match self.by_opcode.get_mut(&(word % 100)) {
+            Some(extension) => extension.execute(values),
+            None => Err(format!("Operation '{}' is not an extension", word % 100).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::Program;
+    use std::cell::{Cell, RefCell};
+
+    fn run(memory: Vec<i32>, mut extensions: Extensions) -> Result<Vec<i32>, Cow<'static, str>> {
+        let mut input = UnitTestInput::new(Vec::new());
+        let mut output = UnitTestOutput::new(Vec::new());
+        let mut program =
+            Program::new(memory, &mut input, &mut output).with_extensions(&mut extensions);
+
+        program.run()?;
+        Ok(program.memory().to_vec())
+    }
+
+    #[test]
+    fn decode_modes_and_destination() {
+        let extension = Extension::new("clamp", 3, |_| Ok(Some(0))).with_destination();
+
+        assert_eq!(
+            extension.decode(&[1042, 7, 8, 9, 10]),
+            Ok(Decoded {
+                mnemonic: "clamp",
+                parameters: vec![
+                    Parameter::Address(7),
+                    Parameter::Value(8),
+                    Parameter::Address(9)
+                ],
+                destination: Some(Parameter::Address(10)),
+            })
+        );
+        assert_eq!(
+            extension.decode(&[1042, 7, 8, 9, 10]).unwrap().to_string(),
+            "clamp [7], 8, [9], [10]"
+        );
+        assert_eq!(
+            extension
+                .decode(&[220042, 7, 8, 9, 10])
+                .unwrap()
+                .to_string(),
+            "clamp [7], [8], [rb + 9], [rb + 10]"
+        );
+        assert!(extension.decode(&[100042, 7, 8, 9, 10]).is_err());
+        assert!(extension.decode(&[42, 7, 8, 9]).is_err());
+    }
+
+    #[test]
+    fn host_call_computes_a_value() {
+        let mut extensions = Extensions::new();
+        extensions
+            .register(
+                20,
+                Extension::new("max", 2, |values| Ok(values.iter().copied().max()))
+                    .with_destination(),
+            )
+            .unwrap();
+
+        let memory = run(vec![1020, 7, 5, 0, 99, 0, 0, 12], extensions).unwrap();
+
+        assert_eq!(memory[0], 12);
+    }
+
+    #[test]
+    fn host_call_reaches_host_state() {
+        let log = RefCell::new(Vec::new());
+        let clock = Cell::new(100);
+        let mut extensions = Extensions::new();
+        extensions
+            .register(
+                40,
+                Extension::new("log", 1, |values| {
+                    log.borrow_mut().push(values[0]);
+                    Ok(None)
+                }),
+            )
+            .unwrap();
+        extensions
+            .register(
+                41,
+                Extension::new("time", 0, |_| {
+                    clock.set(clock.get() + 1);
+                    Ok(Some(clock.get()))
+                })
+                .with_destination(),
+            )
+            .unwrap();
+
+        run(vec![41, 9, 140, 3, 40, 9, 41, 9, 99, 0], extensions).unwrap();
+
+        assert_eq!(*log.borrow(), [3, 101]);
+        assert_eq!(clock.get(), 102);
+    }
+
+    #[test]
+    fn handler_errors_stop_the_program() {
+        let mut extensions = Extensions::new();
+        extensions
+            .register(
+                50,
+                Extension::new("fail", 0, |_| Err("Host failure".into())),
+            )
+            .unwrap();
+
+        assert_eq!(run(vec![50, 99], extensions), Err("Host failure".into()));
+    }
+
+    #[test]
+    fn results_must_match_the_destination() {
+        let mut extensions = Extensions::new();
+        extensions
+            .register(50, Extension::new("value", 0, |_| Ok(Some(1))))
+            .unwrap();
+
+        assert!(run(vec![50, 99], extensions).is_err());
+    }
+
+    #[test]
+    fn built_in_and_duplicate_opcodes_are_rejected() {
+        let mut extensions = Extensions::new();
+
+        assert!(extensions
+            .register(1, Extension::new("add", 0, |_| Ok(None)))
+            .is_err());
+        assert!(extensions
+            .register(99, Extension::new("halt", 0, |_| Ok(None)))
+            .is_err());
+        assert!(extensions
+            .register(100, Extension::new("big", 0, |_| Ok(None)))
+            .is_err());
+        assert!(extensions
+            .register(9, Extension::new("arb", 0, |_| Ok(None)))
+            .is_err());
+        assert!(extensions
+            .register(10, Extension::new("wide", 8, |_| Ok(None)))
+            .is_err());
+        assert!(extensions
+            .register(10, Extension::new("ok", 0, |_| Ok(None)))
+            .is_ok());
+        assert!(extensions
+            .register(10, Extension::new("again", 0, |_| Ok(None)))
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_opcodes_still_fail() {
+        assert!(run(vec![42, 99], Extensions::new()).is_err());
+    }
+}
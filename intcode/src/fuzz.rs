@@ -0,0 +1,444 @@
+//! Random program generation and differential testing of the ways we have to run a program.
+//!
+//! Generated programs are a run of random instructions followed by `99`, with a block of data
+//! cells after the code. Every position parameter and destination points into the data, and
+//! every immediate jump target is the start of an instruction, so programs never overwrite
+//! their own code. A candidate is only kept when it halts cleanly within the step budget; its
+//! inputs are whatever random values it asked for along the way.
+//!
+//! `check` runs a generated program through `Program`, through `Program` after `optimise`,
+//! through the residual of `Specialiser` and through a `threaded::pipeline`, and reports the
+//! first disagreement in outputs or in the final data cells.
+//!
+//! There is no transpiler in this tree, so `check` has nothing to compare on that front. The
+//! day_02 `run_intcode` only handles opcodes 1, 2 and 99 in position mode and lives in a binary
+//! crate, so it is not part of `check` either. It is only compared, on programs generated with
+//! those opcodes and modes, by the single test `agrees_with_shared_program` in day_02.
+
+use super::{
+    io::{adapter::FunctionInput, programmable::ProgrammableOutput},
+    minimiser::Case,
+    optimiser::optimise,
+    specialiser::Specialiser,
+    threaded, Program, State,
+};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Range;
+
+/// How many steps a program may take under any of the implementations being compared.
+const MAX_CHECK_STEPS: usize = 1_000_000;
+
+/// A small xorshift generator, so that a seed reproduces the same programs everywhere.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A value in `0..bound`, which must not be empty.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// A value in `range`, which must not be empty.
+    pub fn range(&mut self, range: &Range<i32>) -> i32 {
+        let len = (i64::from(range.end) - i64::from(range.start)) as u64;
+        (i64::from(range.start) + (self.next_u64() % len) as i64) as i32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generated {
+    pub case: Case,
+    /// The cells that hold data, which every implementation has to leave in the same state.
+    pub data: Range<usize>,
+}
+
+impl fmt::Display for Generated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.case)?;
+        write!(f, "let data = {:?};", self.data)
+    }
+}
+
+pub struct Generator {
+    instructions: usize,
+    data_cells: usize,
+    opcodes: Vec<i32>,
+    immediate: bool,
+    values: Range<i32>,
+    max_steps: usize,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self {
+            instructions: 16,
+            data_cells: 8,
+            opcodes: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            immediate: true,
+            values: -20..20,
+            max_steps: 1000,
+        }
+    }
+
+    /// The most instructions before the final halt.
+    pub fn instructions(mut self, instructions: usize) -> Self {
+        self.instructions = instructions.max(1);
+        self
+    }
+
+    pub fn data_cells(mut self, data_cells: usize) -> Self {
+        self.data_cells = data_cells.max(1);
+        self
+    }
+
+    /// The opcodes to pick from, which must be built-in opcodes other than 99.
+    pub fn opcodes(mut self, opcodes: &[i32]) -> Self {
+        assert!(
+            !opcodes.is_empty() && opcodes.iter().all(|opcode| (1..=8).contains(opcode)),
+            "Only the opcodes 1 to 8 can be generated"
+        );
+        self.opcodes = opcodes.to_vec();
+        self
+    }
+
+    /// Generates position mode parameters only, like the programs of day 2.
+    pub fn position_only(mut self) -> Self {
+        self.immediate = false;
+        self
+    }
+
+    /// The range of immediate values, initial data and inputs.
+    pub fn values(mut self, values: Range<i32>) -> Self {
+        self.values = values;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Generates candidates until one halts cleanly within the step budget.
+    pub fn generate(&self, rng: &mut Rng) -> Generated {
+        loop {
+            let (memory, data) = self.candidate(rng);
+            if let Some(inputs) = self.accept(&memory, rng) {
+                return Generated {
+                    case: Case::new(memory, inputs),
+                    data,
+                };
+            }
+        }
+    }
+
+    fn candidate(&self, rng: &mut Rng) -> (Vec<i32>, Range<usize>) {
+        let opcodes: Vec<i32> = (0..rng.below(self.instructions) + 1)
+            .map(|_| self.opcodes[rng.below(self.opcodes.len())])
+            .collect();
+
+        let mut starts = Vec::new();
+        let mut code_len = 0;
+        for opcode in &opcodes {
+            starts.push(code_len);
+            code_len += shape(*opcode).0 + 1;
+        }
+        starts.push(code_len);
+        let data = code_len + 1..code_len + 1 + self.data_cells;
+
+        let mut memory = Vec::with_capacity(data.end);
+        for opcode in opcodes {
+            let (parameters, has_destination, is_jump) = shape(opcode);
+            let mut word = opcode;
+            let mut operands = Vec::new();
+
+            for idx in 0..parameters {
+                let is_destination = has_destination && idx == parameters - 1;
+                let immediate = self.immediate && !is_destination && rng.below(2) == 0;
+
+                operands.push(if !immediate {
+                    (data.start + rng.below(self.data_cells)) as i32
+                } else if is_jump && idx == 1 {
+                    starts[rng.below(starts.len())] as i32
+                } else {
+                    rng.range(&self.values)
+                });
+                if immediate {
+                    word += 100 * 10i32.pow(idx as u32);
+                }
+            }
+
+            memory.push(word);
+            memory.extend(operands);
+        }
+        memory.push(99);
+        memory.extend((0..self.data_cells).map(|_| rng.range(&self.values)));
+
+        (memory, data)
+    }
+
+    /// Runs a candidate with random inputs, returning the inputs it read if it halted cleanly.
+    fn accept(&self, memory: &[i32], rng: &mut Rng) -> Option<Vec<i32>> {
+        let inputs = RefCell::new(Vec::new());
+        let mut input = FunctionInput::new(|| {
+            let value = rng.range(&self.values);
+            inputs.borrow_mut().push(value);
+            Some(value)
+        });
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(memory.to_vec(), &mut input, &mut output);
+
+        for _ in 0..self.max_steps {
+            match program.step() {
+                Ok(State::Running) => {}
+                Ok(State::Halted) => return Some(inputs.take()),
+                Err(_) => return None,
+            }
+        }
+
+        None
+    }
+}
+
+/// The number of parameters of an opcode, whether the last one is a destination and whether
+/// it is a jump.
+fn shape(opcode: i32) -> (usize, bool, bool) {
+    match opcode {
+        1 | 2 | 7 | 8 => (3, true, false),
+        3 => (1, true, false),
+        4 => (1, false, false),
+        5 | 6 => (2, false, true),
+        _ => (0, false, false),
+    }
+}
+
+/// The outputs and the final memory of a run.
+type Outcome = (Vec<i32>, Vec<i32>);
+
+fn execute(
+    memory: Vec<i32>,
+    entry_point: usize,
+    inputs: &[i32],
+) -> Result<Outcome, Cow<'static, str>> {
+    let mut inputs = inputs.iter().copied();
+    let mut input = FunctionInput::new(|| inputs.next());
+    let mut output = ProgrammableOutput::new();
+    let mut program = Program::with_entry_point(memory, entry_point, &mut input, &mut output);
+
+    for _ in 0..MAX_CHECK_STEPS {
+        if program.step()? == State::Halted {
+            let memory = program.memory().to_vec();
+            return Ok((output.output(), memory));
+        }
+    }
+
+    Err(format!("No halt within {} steps", MAX_CHECK_STEPS).into())
+}
+
+/// Compares `other` to the reference run, which must have the same outputs and leave the same
+/// value in every data cell.
+fn compare(
+    name: &str,
+    generated: &Generated,
+    reference: &Outcome,
+    other: Result<Outcome, Cow<'static, str>>,
+) -> Result<(), Cow<'static, str>> {
+    let (outputs, memory) = other.map_err(|error| format!("{} failed: {}", name, error))?;
+
+    if outputs != reference.0 {
+        return Err(format!("{} wrote {:?} instead of {:?}", name, outputs, reference.0).into());
+    }
+
+    for address in generated.data.clone() {
+        let value = memory.get(address);
+        if value != Some(&reference.1[address]) {
+            return Err(format!(
+                "{} left {:?} at address {} instead of {}",
+                name, value, address, reference.1[address]
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `generated` through every implementation and describes the first disagreement.
+pub fn check(generated: &Generated) -> Result<(), Cow<'static, str>> {
+    let Case { memory, inputs } = &generated.case;
+    let reference =
+        execute(memory.clone(), 0, inputs).map_err(|error| format!("Program failed: {}", error))?;
+
+    compare(
+        "The optimised program",
+        generated,
+        &reference,
+        execute(optimise(memory), 0, inputs),
+    )?;
+
+    let specialised = Specialiser::new()
+        .specialise(memory, &[], inputs)
+        .and_then(|residual| execute(residual.memory, residual.entry_point, &residual.inputs));
+    compare(
+        "The specialised program",
+        generated,
+        &reference,
+        specialised,
+    )?;
+
+    let completion = threaded::pipeline(vec![(memory.clone(), inputs.clone())])
+        .map_err(|error| format!("The threaded pipeline failed: {}", error))?;
+    if completion.outputs != reference.0 {
+        return Err(format!(
+            "The threaded pipeline wrote {:?} instead of {:?}",
+            completion.outputs, reference.0
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// A generated program on which the implementations disagree.
+#[derive(Debug)]
+pub struct Disagreement {
+    pub seed: u64,
+    pub iteration: usize,
+    pub generated: Generated,
+    pub message: Cow<'static, str>,
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Seed {}, program {}: {}",
+            self.seed, self.iteration, self.message
+        )?;
+        write!(f, "{}", self.generated)
+    }
+}
+
+/// Checks `iterations` programs generated from `seed`.
+pub fn fuzz(generator: &Generator, seed: u64, iterations: usize) -> Result<(), Disagreement> {
+    let mut rng = Rng::new(seed);
+
+    for iteration in 0..iterations {
+        let generated = generator.generate(&mut rng);
+
+        if let Err(message) = check(&generated) {
+            return Err(Disagreement {
+                seed,
+                iteration,
+                generated,
+                message,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator;
+
+    #[test]
+    fn rng_is_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+
+        for _ in 0..100 {
+            let value = a.range(&(-5..5));
+            assert_eq!(value, b.range(&(-5..5)));
+            assert!((-5..5).contains(&value));
+        }
+    }
+
+    #[test]
+    fn generated_programs_are_valid_and_halt() {
+        let generator = Generator::new();
+        let mut rng = Rng::new(7);
+
+        for _ in 0..200 {
+            let generated = generator.generate(&mut rng);
+            let Case { memory, inputs } = &generated.case;
+
+            assert!(validator::validate(memory).is_valid(), "{}", generated);
+            assert!(execute(memory.clone(), 0, inputs).is_ok(), "{}", generated);
+            assert_eq!(generated.data.end, memory.len());
+        }
+    }
+
+    #[test]
+    fn restricted_generator() {
+        let generator = Generator::new()
+            .opcodes(&[1, 2])
+            .position_only()
+            .values(0..10);
+        let mut rng = Rng::new(3);
+
+        for _ in 0..50 {
+            let generated = generator.generate(&mut rng);
+            let code = &generated.case.memory[..generated.data.start];
+
+            assert!(generated.case.inputs.is_empty());
+            for instruction in code.chunks(4) {
+                assert!(matches!(instruction[0], 1 | 2 | 99));
+            }
+        }
+    }
+
+    #[test]
+    fn implementations_agree() {
+        if let Err(disagreement) = fuzz(&Generator::new(), 1, 300) {
+            panic!("{}", disagreement);
+        }
+    }
+
+    #[test]
+    fn disagreements_are_reported() {
+        let generated = Generated {
+            case: Case::new(vec![1101, 1, 2, 5, 99, 0], vec![]),
+            data: 5..6,
+        };
+        let reference = (vec![], vec![1101, 1, 2, 5, 99, 3]);
+
+        assert!(compare(
+            "Broken",
+            &generated,
+            &reference,
+            Ok((vec![], vec![1101, 1, 2, 5, 99, 4]))
+        )
+        .is_err());
+        assert!(compare(
+            "Broken",
+            &generated,
+            &reference,
+            Ok((vec![1], reference.1.clone()))
+        )
+        .is_err());
+        assert!(compare("Same", &generated, &reference, Ok(reference.clone())).is_ok());
+    }
+}
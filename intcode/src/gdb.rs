@@ -0,0 +1,718 @@
+//! A stub for the GDB remote serial protocol, so a standard debugger front end can drive a
+//! `Program` over a socket.
+//!
+//! GDB addresses bytes, so Intcode cell `n` is exposed as the 4 little-endian bytes starting
+//! at address `4 * n`. The target has two 32-bit registers: `pc`, the byte address of the
+//! instruction pointer, and `rb`, the relative base. Unlike `pc`, `rb` is a cell index rather
+//! than a byte address, so it reads the same as it does to `arb`.
+//!
+//! A running program stops with `SIGINT` when the debugger sends an interrupt, which it polls
+//! for every few thousand steps.
+//!
+//! With a source map, `monitor where` shows where the program is in its source and
+//! `monitor break <label>` stops at a label, since GDB itself knows nothing about them.
+
+use super::{
+    io::{LineReader, LineWriter},
+    source_map::SourceMap,
+    Operation, Program, State,
+};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+const CELL_SIZE: usize = 4;
+const REGISTERS: usize = 2;
+
+/// How many steps a running program takes between checks for an interrupt.
+const POLL_INTERVAL: usize = 4096;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.intcode.core">
+    <reg name="pc" bitsize="32" type="code_ptr" regnum="0"/>
+    <reg name="rb" bitsize="32" type="int" regnum="1"/>
+  </feature>
+</target>
+"#;
+
+/// Stop replies, named after the signals GDB expects.
+const SIGTRAP: &str = "S05";
+const SIGINT: &str = "S02";
+const SIGILL: &str = "S04";
+
+pub struct GdbStub<'p, 'a, Input, Output>
+where
+    Input: LineReader,
+    Output: LineWriter,
+{
+    program: &'p mut Program<'a, Input, Output>,
+    breakpoints: BTreeSet<usize>,
+    halted: bool,
+    source_map: Option<&'p SourceMap>,
+}
+
+enum Reply {
+    Packet(String),
+    Close(Option<String>),
+}
+
+impl<'p, 'a, Input, Output> GdbStub<'p, 'a, Input, Output>
+where
+    Input: LineReader,
+    Output: LineWriter,
+{
+    pub fn new(program: &'p mut Program<'a, Input, Output>) -> Self {
+        Self {
+            program,
+            breakpoints: BTreeSet::new(),
+            halted: false,
+            source_map: None,
+        }
+    }
+
+    /// Uses `source_map` for the `where` and `break` monitor commands.
+    pub fn with_source_map(mut self, source_map: &'p SourceMap) -> Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// Answers packets from `stream` until the debugger detaches, kills the program or hangs
+    /// up.
+    pub fn serve(&mut self, stream: TcpStream) -> Result<(), Cow<'static, str>> {
+        let mut stream = BufReader::new(stream);
+
+        while let Some(packet) = read_packet(&mut stream).map_err(io_error)? {
+            // A failed poll stops the program, and the next read or write reports the error.
+            let mut interrupted = || poll_interrupt(&mut stream).unwrap_or(true);
+
+            match self.handle(&packet, &mut interrupted) {
+                Reply::Packet(reply) => write_packet(stream.get_mut(), &reply),
+                Reply::Close(Some(reply)) => return write_packet(stream.get_mut(), &reply),
+                Reply::Close(None) => return Ok(()),
+            }?;
+        }
+
+        Ok(())
+    }
+
+    /// Answers `packet`, calling `interrupted` now and then while the program runs to check
+    /// whether the debugger wants it stopped.
+    fn handle(&mut self, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Reply {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => self.stop_reply(),
+            Some(b'g') => self.read_registers(),
+            Some(b'G') => self.write_registers(&packet[1..]),
+            Some(b'p') => self.read_register(&packet[1..]),
+            Some(b'P') => self.write_register(&packet[1..]),
+            Some(b'm') => self.read_memory(&packet[1..]),
+            Some(b'M') => self.write_memory(&packet[1..]),
+            Some(b'Z') => self.breakpoint(&packet[1..], true),
+            Some(b'z') => self.breakpoint(&packet[1..], false),
+            Some(b's') => self.resume(true, interrupted),
+            Some(b'c') => self.resume(false, interrupted),
+            Some(b'H') => Some("OK".into()),
+            Some(b'D') => return Reply::Close(Some("OK".into())),
+            Some(b'k') => return Reply::Close(None),
+            _ if packet.starts_with("qRcmd,") => self.monitor(&packet["qRcmd,".len()..]),
+            _ => self.query(packet),
+        };
+
+        Reply::Packet(reply.unwrap_or_else(|| "E01".into()))
+    }
+
+    fn query(&self, packet: &str) -> Option<String> {
+        let reply = match packet {
+            _ if packet.starts_with("qSupported") => "PacketSize=4000;qXfer:features:read+",
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            _ if packet.starts_with("qXfer:features:read:target.xml:") => {
+                let (offset, length) =
+                    parse_pair(&packet["qXfer:features:read:target.xml:".len()..], ',')?;
+                let offset = offset.min(TARGET_XML.len());
+                let end = offset.saturating_add(length).min(TARGET_XML.len());
+                let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+
+                return Some(format!("{}{}", marker, &TARGET_XML[offset..end]));
+            }
+            // An empty reply tells GDB the packet is not supported.
+            _ => "",
+        };
+
+        Some(reply.into())
+    }
+
+    /// Runs a `monitor` command, whose text GDB sends hex encoded and prints the reply of.
+    fn monitor(&mut self, data: &str) -> Option<String> {
+        let command = String::from_utf8(decode_bytes(data)?).ok()?;
+        let mut words = command.split_whitespace();
+
+        let text = match (words.next(), words.next(), words.next()) {
+            (Some("where"), None, _) => self.location(),
+            (Some("break"), Some(target), None) => {
+                let address = target.parse().ok().or_else(|| {
+                    self.source_map
+                        .and_then(|source_map| source_map.address(target))
+                });
+                match address {
+                    Some(address) => {
+                        self.breakpoints.insert(address);
+                        format!("Breakpoint at {}\n", self.symbolise(address))
+                    }
+                    None => format!("Unknown label `{}`\n", target),
+                }
+            }
+            _ => "Commands: where, break <label|address>\n".to_string(),
+        };
+
+        Some(text.bytes().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Describes the instruction pointer, the instruction there and its source line.
+    fn location(&self) -> String {
+        let address = self.program.instruction_pointer();
+        let operation = self
+            .program
+            .memory()
+            .get(address..)
+            .and_then(|words| Operation::from_slice(words).ok());
+        let instruction = match (operation, self.source_map) {
+            (Some(operation), Some(source_map)) => source_map.operation(&operation),
+            (Some(operation), None) => operation.to_string(),
+            (None, _) => "??".to_string(),
+        };
+        let location = self
+            .source_map
+            .and_then(|source_map| source_map.location(address))
+            .map(|location| format!("  # {}", location))
+            .unwrap_or_default();
+
+        format!("{}: {}{}\n", self.symbolise(address), instruction, location)
+    }
+
+    fn symbolise(&self, address: usize) -> String {
+        match self.source_map {
+            Some(source_map) => source_map.symbolise(address),
+            None => address.to_string(),
+        }
+    }
+
+    fn stop_reply(&self) -> Option<String> {
+        Some(if self.halted { "W00" } else { SIGTRAP }.into())
+    }
+
+    fn registers(&self) -> [i32; REGISTERS] {
+        [
+            (self.program.instruction_pointer() * CELL_SIZE) as i32,
+            self.program.relative_base(),
+        ]
+    }
+
+    fn read_registers(&self) -> Option<String> {
+        Some(
+            self.registers()
+                .iter()
+                .map(|value| encode(*value))
+                .collect(),
+        )
+    }
+
+    fn read_register(&self, number: &str) -> Option<String> {
+        let number = usize::from_str_radix(number, 16).ok()?;
+
+        self.registers().get(number).map(|value| encode(*value))
+    }
+
+    fn write_registers(&mut self, data: &str) -> Option<String> {
+        if data.len() != REGISTERS * CELL_SIZE * 2 {
+            return None;
+        }
+
+        let (pc, rb) = data.split_at(CELL_SIZE * 2);
+        let (pc, rb) = (decode(pc)?, decode(rb)?);
+        self.set_register(0, pc)?;
+        self.set_register(1, rb)
+    }
+
+    fn write_register(&mut self, data: &str) -> Option<String> {
+        let (number, value) = data.split_at(data.find('=')?);
+        let number = usize::from_str_radix(number, 16).ok()?;
+
+        self.set_register(number, decode(&value[1..])?)
+    }
+
+    fn set_register(&mut self, number: usize, value: i32) -> Option<String> {
+        match number {
+            0 if value >= 0 && (value as usize).is_multiple_of(CELL_SIZE) => {
+                self.program
+                    .set_instruction_pointer(value as usize / CELL_SIZE);
+                self.halted = false;
+            }
+            1 => self.program.set_relative_base(value),
+            _ => return None,
+        }
+
+        Some("OK".into())
+    }
+
+    fn read_memory(&self, data: &str) -> Option<String> {
+        let (address, length) = parse_pair(data, ',')?;
+        let bytes: Vec<u8> = self
+            .program
+            .memory()
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .skip(address)
+            .take(length)
+            .collect();
+
+        if bytes.is_empty() && length > 0 {
+            return None;
+        }
+
+        Some(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    fn write_memory(&mut self, data: &str) -> Option<String> {
+        let (range, bytes) = data.split_at(data.find(':')?);
+        let (address, length) = parse_pair(range, ',')?;
+        let bytes = decode_bytes(&bytes[1..])?;
+        let memory = self.program.memory_mut();
+
+        if bytes.len() != length || address.checked_add(length)? > memory.len() * CELL_SIZE {
+            return None;
+        }
+
+        for (offset, byte) in bytes.into_iter().enumerate() {
+            let address = address + offset;
+            let cell = &mut memory[address / CELL_SIZE];
+            let mut cell_bytes = cell.to_le_bytes();
+            cell_bytes[address % CELL_SIZE] = byte;
+            *cell = i32::from_le_bytes(cell_bytes);
+        }
+
+        Some("OK".into())
+    }
+
+    fn breakpoint(&mut self, data: &str, insert: bool) -> Option<String> {
+        let mut parts = data.split(',');
+        let kind = parts.next()?;
+        let address = usize::from_str_radix(parts.next()?, 16).ok()?;
+
+        // Software and hardware breakpoints behave the same, watchpoints are not supported.
+        if kind != "0" && kind != "1" {
+            return Some(String::new());
+        }
+        if !address.is_multiple_of(CELL_SIZE) {
+            return None;
+        }
+
+        if insert {
+            self.breakpoints.insert(address / CELL_SIZE);
+        } else {
+            self.breakpoints.remove(&(address / CELL_SIZE));
+        }
+
+        Some("OK".into())
+    }
+
+    fn resume(
+        &mut self,
+        single_step: bool,
+        interrupted: &mut dyn FnMut() -> bool,
+    ) -> Option<String> {
+        if self.halted {
+            return Some("W00".into());
+        }
+
+        let mut steps = 0;
+        loop {
+            steps += 1;
+
+            match self.program.step() {
+                Ok(State::Halted) => {
+                    self.halted = true;
+                    return Some("W00".into());
+                }
+                Ok(State::Running) => {}
+                Err(_) => return Some(SIGILL.into()),
+            }
+
+            if single_step
+                || self
+                    .breakpoints
+                    .contains(&self.program.instruction_pointer())
+            {
+                return Some(SIGTRAP.into());
+            }
+            if steps % POLL_INTERVAL == 0 && interrupted() {
+                return Some(SIGINT.into());
+            }
+        }
+    }
+}
+
+fn io_error(error: io::Error) -> Cow<'static, str> {
+    format!("Debugger connection failed: {}", error).into()
+}
+
+fn parse_pair(data: &str, separator: char) -> Option<(usize, usize)> {
+    let (first, second) = data.split_at(data.find(separator)?);
+
+    Some((
+        usize::from_str_radix(first, 16).ok()?,
+        usize::from_str_radix(&second[1..], 16).ok()?,
+    ))
+}
+
+fn encode(value: i32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode(data: &str) -> Option<i32> {
+    let bytes = decode_bytes(data)?;
+    if bytes.len() != CELL_SIZE {
+        return None;
+    }
+
+    Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn decode_bytes(data: &str) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..data.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(data.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, u8::wrapping_add)
+}
+
+/// Checks, without blocking, whether the debugger has sent an interrupt (`\x03`) since the
+/// last packet. Anything before the next packet is consumed, and a closed connection counts
+/// as an interrupt.
+fn poll_interrupt(stream: &mut BufReader<TcpStream>) -> io::Result<bool> {
+    if stream.buffer().is_empty() {
+        stream.get_ref().set_nonblocking(true)?;
+        let filled = stream.fill_buf().map(|buffer| buffer.len());
+        stream.get_ref().set_nonblocking(false)?;
+
+        match filled {
+            Ok(0) => return Ok(true),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(false),
+            filled => filled?,
+        };
+    }
+
+    let pending = stream.buffer();
+    let skipped = pending
+        .iter()
+        .position(|&byte| byte == b'$')
+        .unwrap_or(pending.len());
+    let interrupted = pending[..skipped].contains(&0x03);
+    stream.consume(skipped);
+
+    Ok(interrupted)
+}
+
+/// Reads the next `$data#checksum` packet, skipping acknowledgements and interrupts.
+/// Each packet is acknowledged with `+`, or with `-` to ask for a resend if its checksum is
+/// wrong.
+///
+/// Returns `None` once the connection is closed.
+fn read_packet<S>(reader: &mut BufReader<S>) -> io::Result<Option<String>>
+where
+    S: Read + Write,
+{
+    loop {
+        let mut skipped = Vec::new();
+        if reader.read_until(b'$', &mut skipped)? == 0 || skipped.last() != Some(&b'$') {
+            return Ok(None);
+        }
+
+        let mut packet = Vec::new();
+        reader.read_until(b'#', &mut packet)?;
+        if packet.pop() != Some(b'#') {
+            return Ok(None);
+        }
+
+        let mut sum = [0; 2];
+        reader.read_exact(&mut sum)?;
+
+        let packet = String::from_utf8_lossy(&packet).into_owned();
+        let expected = std::str::from_utf8(&sum)
+            .ok()
+            .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+        if expected == Some(checksum(&packet)) {
+            reader.get_mut().write_all(b"+")?;
+            return Ok(Some(packet));
+        }
+        reader.get_mut().write_all(b"-")?;
+    }
+}
+
+fn write_packet<W>(writer: &mut W, data: &str) -> Result<(), Cow<'static, str>>
+where
+    W: Write,
+{
+    write!(writer, "${}#{:02x}", data, checksum(data))
+        .and_then(|_| writer.flush())
+        .map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    /// Outputs its input doubled, then halts.
+    fn doubler() -> Vec<i32> {
+        vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]
+    }
+
+    fn reply<I, O>(stub: &mut GdbStub<'_, '_, I, O>, packet: &str) -> String
+    where
+        I: LineReader,
+        O: LineWriter,
+    {
+        match stub.handle(packet, &mut || false) {
+            Reply::Packet(reply) | Reply::Close(Some(reply)) => reply,
+            Reply::Close(None) => String::new(),
+        }
+    }
+
+    #[test]
+    fn step_and_read_registers() {
+        let mut input = UnitTestInput::new(vec![21]);
+        let mut output = UnitTestOutput::new(vec![42]);
+        let mut program = Program::new(doubler(), &mut input, &mut output);
+        let mut stub = GdbStub::new(&mut program);
+
+        assert_eq!(reply(&mut stub, "?"), "S05");
+        assert_eq!(reply(&mut stub, "g"), "0000000000000000");
+        assert_eq!(reply(&mut stub, "s"), "S05");
+        assert_eq!(reply(&mut stub, "p0"), "08000000");
+        assert_eq!(reply(&mut stub, "c"), "W00");
+        assert_eq!(reply(&mut stub, "?"), "W00");
+    }
+
+    #[test]
+    fn breakpoints_stop_continue() {
+        let mut input = UnitTestInput::new(vec![21]);
+        let mut output = UnitTestOutput::new(vec![42]);
+        let mut program = Program::new(doubler(), &mut input, &mut output);
+        let mut stub = GdbStub::new(&mut program);
+
+        assert_eq!(reply(&mut stub, "Z0,18,4"), "OK");
+        assert_eq!(reply(&mut stub, "c"), "S05");
+        assert_eq!(reply(&mut stub, "p0"), "18000000");
+        assert_eq!(reply(&mut stub, "z0,18,4"), "OK");
+        assert_eq!(reply(&mut stub, "c"), "W00");
+    }
+
+    #[test]
+    fn monitor_commands_use_the_source_map() {
+        let assembly = crate::assembler::assemble(
+            "
+                    in [value]
+            double: mul [value], 2, [value]
+                    out [value]
+                    halt
+            value:  .word 0
+            ",
+            std::path::Path::new("."),
+        )
+        .unwrap();
+        let mut input = UnitTestInput::new(vec![21]);
+        let mut output = UnitTestOutput::new(vec![42]);
+        let mut program = Program::new(assembly.memory, &mut input, &mut output);
+        let mut stub = GdbStub::new(&mut program).with_source_map(&assembly.source_map);
+        let monitor = |stub: &mut GdbStub<'_, '_, _, _>, command: &str| {
+            let command: String = command
+                .bytes()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let reply = decode_bytes(&reply(stub, &format!("qRcmd,{}", command))).unwrap();
+            String::from_utf8(reply).unwrap()
+        };
+
+        assert_eq!(monitor(&mut stub, "break double"), "Breakpoint at double\n");
+        assert_eq!(reply(&mut stub, "c"), "S05");
+        assert_eq!(
+            monitor(&mut stub, "where"),
+            "double: mul [value], 2, [value]  # <input>:3\n"
+        );
+        assert_eq!(
+            monitor(&mut stub, "break nowhere"),
+            "Unknown label `nowhere`\n"
+        );
+        assert!(monitor(&mut stub, "help").starts_with("Commands:"));
+        assert_eq!(reply(&mut stub, "c"), "W00");
+    }
+
+    #[test]
+    fn read_and_write_memory() {
+        let mut input = UnitTestInput::new(vec![1]);
+        let mut output = UnitTestOutput::new(vec![-1]);
+        let mut program = Program::new(doubler(), &mut input, &mut output);
+        let mut stub = GdbStub::new(&mut program);
+
+        assert_eq!(reply(&mut stub, "m8,6"), "ea0300000900");
+        // Changes the factor from 2 to -1.
+        assert_eq!(reply(&mut stub, "M10,4:ffffffff"), "OK");
+        assert_eq!(reply(&mut stub, "m10,4"), "ffffffff");
+        assert_eq!(reply(&mut stub, "m100,4"), "E01");
+        assert_eq!(reply(&mut stub, "M100,4:00000000"), "E01");
+        assert_eq!(reply(&mut stub, "Mffffffffffffffff,1:00"), "E01");
+        assert_eq!(reply(&mut stub, "c"), "W00");
+    }
+
+    #[test]
+    fn write_instruction_pointer() {
+        let (mut input, mut output) = (UnitTestInput::new(vec![]), UnitTestOutput::new(vec![]));
+        let mut program = Program::new(doubler(), &mut input, &mut output);
+        let mut stub = GdbStub::new(&mut program);
+
+        assert_eq!(reply(&mut stub, "P0=20000000"), "OK");
+        assert_eq!(reply(&mut stub, "P0=02000000"), "E01");
+        assert_eq!(reply(&mut stub, "g"), "2000000000000000");
+        assert_eq!(reply(&mut stub, "s"), "W00");
+    }
+
+    #[test]
+    fn relative_base_register() {
+        let (mut input, mut output) = (UnitTestInput::new(vec![]), UnitTestOutput::new(vec![]));
+        // arb 5; arb -2; halt
+        let mut program = Program::new(vec![109, 5, 109, -2, 99], &mut input, &mut output);
+        let mut stub = GdbStub::new(&mut program);
+
+        assert_eq!(reply(&mut stub, "s"), "S05");
+        assert_eq!(reply(&mut stub, "p1"), "05000000");
+        assert_eq!(reply(&mut stub, "P1=0a000000"), "OK");
+        assert_eq!(reply(&mut stub, "s"), "S05");
+        assert_eq!(reply(&mut stub, "g"), "1000000008000000");
+        assert_eq!(reply(&mut stub, "G0000000007000000"), "OK");
+        assert_eq!(reply(&mut stub, "p1"), "07000000");
+        assert_eq!(reply(&mut stub, "p0"), "00000000");
+    }
+
+    #[test]
+    fn target_description() {
+        let (mut input, mut output) = (UnitTestInput::new(vec![]), UnitTestOutput::new(vec![]));
+        let mut program = Program::new(doubler(), &mut input, &mut output);
+        let stub = GdbStub::new(&mut program);
+
+        let first = stub.query("qXfer:features:read:target.xml:0,10").unwrap();
+        let rest = stub
+            .query("qXfer:features:read:target.xml:10,1000")
+            .unwrap();
+
+        assert_eq!(first, format!("m{}", &TARGET_XML[..16]));
+        assert_eq!(rest, format!("l{}", &TARGET_XML[16..]));
+        assert_eq!(stub.query("vMustReplyEmpty"), Some(String::new()));
+    }
+
+    #[test]
+    fn packets_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut input = UnitTestInput::new(vec![4]);
+            let mut output = UnitTestOutput::new(vec![8]);
+            let mut program = Program::new(doubler(), &mut input, &mut output);
+
+            GdbStub::new(&mut program).serve(stream).unwrap();
+        });
+
+        let mut client = BufReader::new(TcpStream::connect(address).unwrap());
+        let mut exchange = |packet: &str| {
+            write_packet(client.get_mut(), packet).unwrap();
+            read_packet(&mut client).unwrap().unwrap()
+        };
+
+        assert_eq!(
+            exchange("qSupported:multiprocess+"),
+            "PacketSize=4000;qXfer:features:read+"
+        );
+        assert_eq!(exchange("c"), "W00");
+        assert_eq!(exchange("D"), "OK");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn interrupts_stop_a_running_program() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let (mut input, mut output) = (UnitTestInput::new(vec![]), UnitTestOutput::new(vec![]));
+            // Loops forever.
+            let mut program = Program::new(vec![1105, 1, 0], &mut input, &mut output);
+
+            GdbStub::new(&mut program).serve(stream).unwrap();
+        });
+
+        let mut client = BufReader::new(TcpStream::connect(address).unwrap());
+        write_packet(client.get_mut(), "c").unwrap();
+        client.get_mut().write_all(b"\x03").unwrap();
+
+        assert_eq!(read_packet(&mut client).unwrap(), Some("S02".into()));
+        write_packet(client.get_mut(), "D").unwrap();
+        assert_eq!(read_packet(&mut client).unwrap(), Some("OK".into()));
+
+        server.join().unwrap();
+    }
+
+    /// A connection that reads from a fixed buffer and records what is written to it.
+    struct Recorded<'a> {
+        input: &'a [u8],
+        output: Vec<u8>,
+    }
+
+    impl Read for Recorded<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buffer)
+        }
+    }
+
+    impl Write for Recorded<'_> {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            self.output.write(buffer)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn bad_checksums_ask_for_a_resend() {
+        let mut stream = BufReader::new(Recorded {
+            input: b"+$g#00$g#67",
+            output: Vec::new(),
+        });
+
+        assert_eq!(read_packet(&mut stream).unwrap(), Some("g".into()));
+        assert_eq!(stream.get_ref().output, b"-+");
+        assert_eq!(read_packet(&mut stream).unwrap(), None);
+    }
+}
@@ -0,0 +1,416 @@
+//! Declarative test cases for Intcode programs, kept in `.ictest` files.
+//!
+//! A file holds one program and any number of cases that run it:
+//!
+//! ```text
+//! # Outputs 999 below 8, 1000 for 8 and 1001 above 8.
+//! program: 3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+//! program: 1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+//! program: 999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+//!
+//! case: below eight
+//! input: 7
+//! output: 999
+//!
+//! case: eight
+//! input: 8
+//! output: 1000
+//! memory: 20=1000, 21=8
+//! ```
+//!
+//! `program:` lines are concatenated, and `program-file:` loads a program relative to the test
+//! file instead, assembling it when its name ends in `.asm` and compiling it when it ends in
+//! `.icl`. Every case runs the program from scratch. Like `UnitTestInput` and
+//! `UnitTestOutput`, a case fails when it reads more inputs than listed or leaves some unread,
+//! and when its outputs differ from the listed ones in any way. `memory:` lists cells that
+//! must hold the given values after the program halted, and `max-steps:` overrides the step
+//! limit.
+
+use super::{
+    assembler, compiler,
+    io::{LineReader, LineWriter},
+    loader, Program, State,
+};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const EXTENSION: &str = "ictest";
+const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    /// The line of the `case:` header, counted from 1.
+    pub line: usize,
+    pub inputs: Vec<i32>,
+    pub outputs: Vec<i32>,
+    pub memory: Vec<(usize, i32)>,
+    pub max_steps: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFile {
+    pub program: Vec<i32>,
+    pub cases: Vec<TestCase>,
+}
+
+impl TestFile {
+    /// Parses a test file, loading `program-file:` paths relative to `directory`.
+    pub fn parse(text: &str, directory: &Path) -> Result<Self, Cow<'static, str>> {
+        let mut program_text = String::new();
+        let mut program_file = None;
+        let mut cases: Vec<TestCase> = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("Line {}: expected `key: value`", line_number))?;
+            let error = |message: &str| format!("Line {}: {}", line_number, message);
+
+            match (key, cases.last_mut()) {
+                ("program", None) => {
+                    program_text.push_str(value);
+                    program_text.push('\n');
+                }
+                ("program-file", None) => program_file = Some(directory.join(value)),
+                ("case", _) => cases.push(TestCase {
+                    name: value.to_string(),
+                    line: line_number,
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                    memory: Vec::new(),
+                    max_steps: DEFAULT_MAX_STEPS,
+                }),
+                ("input", Some(case)) => case
+                    .inputs
+                    .extend(loader::parse(value).map_err(|parse| error(&parse.to_string()))?),
+                ("output", Some(case)) => case
+                    .outputs
+                    .extend(loader::parse(value).map_err(|parse| error(&parse.to_string()))?),
+                ("memory", Some(case)) => {
+                    for cell in value.split(',') {
+                        let parsed = cell.split_once('=').and_then(|(address, value)| {
+                            Some((address.trim().parse().ok()?, value.trim().parse().ok()?))
+                        });
+                        case.memory.push(parsed.ok_or_else(|| {
+                            error(&format!("invalid memory cell `{}`", cell.trim()))
+                        })?);
+                    }
+                }
+                ("max-steps", Some(case)) => {
+                    case.max_steps = value.parse().map_err(|_| error("invalid step limit"))?
+                }
+                ("program" | "program-file", Some(_)) => {
+                    return Err(error("the program has to come before the first case").into())
+                }
+                (_, None) => return Err(error(&format!("`{}` outside of a case", key)).into()),
+                _ => return Err(error(&format!("unknown key `{}`", key)).into()),
+            }
+        }
+
+        let program = match program_file {
+            Some(_) if !program_text.is_empty() => {
+                return Err("Use either `program` or `program-file`, not both".into())
+            }
+            Some(path) if path.extension().is_some_and(|extension| extension == "asm") => {
+                assembler::assemble_file(&path)?.memory
+            }
+            Some(path)
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == compiler::EXTENSION) =>
+            {
+                compiler::compile_file(&path)?.memory
+            }
+            Some(path) => {
+                loader::load(&path).map_err(|error| format!("{}: {}", path.display(), error))?
+            }
+            None if program_text.is_empty() => return Err("No program".into()),
+            None => loader::parse(&program_text).map_err(|error| error.to_string())?,
+        };
+
+        Ok(Self { program, cases })
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+
+        Self::parse(&text, path.parent().unwrap_or_else(|| Path::new(".")))
+    }
+
+    /// Runs one case against a fresh copy of the program.
+    pub fn run(&self, case: &TestCase) -> Result<(), Cow<'static, str>> {
+        let mut input = ExpectedInput {
+            inputs: &case.inputs,
+            current: 0,
+        };
+        let mut output = ExpectedOutput {
+            expected: &case.outputs,
+            current: 0,
+            mismatch: None,
+        };
+        let mut program = Program::new(self.program.clone(), &mut input, &mut output);
+
+        let mut halted = false;
+        for _ in 0..case.max_steps {
+            if program.step()? == State::Halted {
+                halted = true;
+                break;
+            }
+        }
+        if !halted {
+            return Err(format!("No halt within {} steps", case.max_steps).into());
+        }
+
+        let memory = program.memory().to_vec();
+        if let Some(mismatch) = output.mismatch {
+            return Err(mismatch);
+        }
+        if output.current < case.outputs.len() {
+            return Err(format!(
+                "Not all output was written, got {:?}",
+                &case.outputs[..output.current]
+            )
+            .into());
+        }
+        if input.current < case.inputs.len() {
+            return Err(format!(
+                "Not all input was read, {} of {} values",
+                input.current,
+                case.inputs.len()
+            )
+            .into());
+        }
+
+        for (address, expected) in &case.memory {
+            match memory.get(*address) {
+                Some(value) if value == expected => {}
+                value => {
+                    return Err(format!(
+                        "Expected {} at address {}, got {:?}",
+                        expected, address, value
+                    )
+                    .into())
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct ExpectedInput<'a> {
+    inputs: &'a [i32],
+    current: usize,
+}
+
+impl LineReader for ExpectedInput<'_> {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        let value = *self
+            .inputs
+            .get(self.current)
+            .ok_or("Attempted to read too many times")?;
+        self.current += 1;
+
+        Ok(value)
+    }
+}
+
+/// Remembers the first unexpected output, since writing cannot fail.
+struct ExpectedOutput<'a> {
+    expected: &'a [i32],
+    current: usize,
+    mismatch: Option<Cow<'static, str>>,
+}
+
+impl LineWriter for ExpectedOutput<'_> {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        if self.mismatch.is_some() {
+            return Ok(());
+        }
+
+        self.mismatch = match self.expected.get(self.current) {
+            Some(expected) if *expected == value => None,
+            Some(expected) => Some(
+                format!(
+                    "Invalid output {}, expected {:?}, got {:?}",
+                    self.current + 1,
+                    expected,
+                    value
+                )
+                .into(),
+            ),
+            None => Some(format!("Attempted to write too many times, got {}", value).into()),
+        };
+        self.current += 1;
+
+        Ok(())
+    }
+}
+
+/// Finds every `.ictest` file in `path`, which may also be a single file, sorted by path.
+pub fn discover<P>(path: P) -> Result<Vec<PathBuf>, Cow<'static, str>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let entries = fs::read_dir(path)
+        .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+    for entry in entries {
+        let entry = entry.map_err(|error| error.to_string())?.path();
+
+        if entry.is_dir() {
+            files.extend(discover(&entry)?);
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+        {
+            files.push(entry);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// A case together with whether it passed.
+pub type Outcome = (TestCase, Result<(), Cow<'static, str>>);
+
+/// The outcome of every case in a file, or why the file could not be used at all.
+pub fn run_file<P>(path: P) -> Result<Vec<Outcome>, Cow<'static, str>>
+where
+    P: AsRef<Path>,
+{
+    let file = TestFile::load(path)?;
+
+    Ok(file
+        .cases
+        .iter()
+        .map(|case| (case.clone(), file.run(case)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPARE_TO_EIGHT: &str = "
+# Outputs 999 below 8, 1000 for 8 and 1001 above 8.
+program: 3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+program: 1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+program: 999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+
+case: below eight
+input: 7
+output: 999
+
+case: eight
+input: 8
+output: 1000
+memory: 20=1000, 21=8
+";
+
+    fn parse(text: &str) -> Result<TestFile, Cow<'static, str>> {
+        TestFile::parse(text, Path::new("."))
+    }
+
+    fn failure(text: &str) -> Cow<'static, str> {
+        let file = parse(text).unwrap();
+        file.run(&file.cases[0]).unwrap_err()
+    }
+
+    #[test]
+    fn parse_program_and_cases() {
+        let file = parse(COMPARE_TO_EIGHT).unwrap();
+
+        assert_eq!(file.program.len(), 47);
+        assert_eq!(file.cases.len(), 2);
+        assert_eq!(file.cases[1].name, "eight");
+        assert_eq!(file.cases[1].line, 11);
+        assert_eq!(file.cases[1].memory, [(20, 1000), (21, 8)]);
+    }
+
+    #[test]
+    fn passing_cases() {
+        let file = parse(COMPARE_TO_EIGHT).unwrap();
+
+        for case in &file.cases {
+            assert_eq!(file.run(case), Ok(()));
+        }
+    }
+
+    #[test]
+    fn wrong_output() {
+        assert_eq!(
+            failure("program: 104,1,104,2,99\ncase: c\noutput: 1,3"),
+            "Invalid output 2, expected 3, got 2"
+        );
+    }
+
+    #[test]
+    fn too_many_and_too_few_outputs() {
+        assert!(failure("program: 104,1,104,2,99\ncase: c\noutput: 1").contains("too many"));
+        assert!(failure("program: 104,1,99\ncase: c\noutput: 1,2").contains("Not all output"));
+    }
+
+    #[test]
+    fn too_many_and_too_few_inputs() {
+        assert!(failure("program: 3,0,3,0,99\ncase: c\ninput: 1").contains("too many"));
+        assert!(failure("program: 3,0,99\ncase: c\ninput: 1,2").contains("Not all input"));
+    }
+
+    #[test]
+    fn wrong_memory() {
+        assert_eq!(
+            failure("program: 1101,1,1,0,99\ncase: c\nmemory: 0=3"),
+            "Expected 3 at address 0, got Some(2)"
+        );
+    }
+
+    #[test]
+    fn step_limit() {
+        assert!(failure("program: 1105,1,0\ncase: c\nmax-steps: 10").contains("No halt"));
+    }
+
+    #[test]
+    fn malformed_files() {
+        assert!(parse("case: c").is_err());
+        assert!(parse("program: 99\ninput: 1").is_err());
+        assert!(parse("program: 99\ncase: c\nprogram: 99").is_err());
+        assert!(parse("program: 99\ncase: c\ninput: x").is_err());
+        assert!(parse("program: 99\ncase: c\nmemory: 1").is_err());
+        assert!(parse("program: 99\ncase: c\nexpect: 1").is_err());
+        assert!(parse("program 99").is_err());
+    }
+
+    #[test]
+    fn checked_in_files_pass() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("ictests");
+        let files = discover(&directory).unwrap();
+        assert!(!files.is_empty());
+
+        for path in files {
+            for (case, result) in run_file(&path).unwrap() {
+                assert_eq!(result, Ok(()), "{}: {}", path.display(), case.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use super::{LineReader, LineWriter};
+use std::borrow::Cow;
+
+/// Reads inputs from an iterator, failing once it is exhausted.
+pub struct IteratorInput<I> {
+    inputs: I,
+}
+
+impl<I> IteratorInput<I>
+where
+    I: Iterator<Item = i32>,
+{
+    pub fn new<T>(inputs: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            inputs: inputs.into_iter(),
+        }
+    }
+}
+
+impl<I> LineReader for IteratorInput<I>
+where
+    I: Iterator<Item = i32>,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        self.inputs
+            .next()
+            .ok_or_else(|| "Input iterator was exhausted".into())
+    }
+}
+
+/// Asks a closure for every input, failing once it returns `None`.
+///
+/// The closure can share state with a `FunctionOutput`, which lets a controller decide on the
+/// next input based on what the program wrote so far.
+pub struct FunctionInput<F> {
+    next: F,
+}
+
+impl<F> FunctionInput<F>
+where
+    F: FnMut() -> Option<i32>,
+{
+    pub fn new(next: F) -> Self {
+        Self { next }
+    }
+}
+
+impl<F> LineReader for FunctionInput<F>
+where
+    F: FnMut() -> Option<i32>,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        (self.next)().ok_or_else(|| "Input function returned no value".into())
+    }
+}
+
+/// Passes every output to a closure.
+pub struct FunctionOutput<F> {
+    write: F,
+}
+
+impl<F> FunctionOutput<F>
+where
+    F: FnMut(i32),
+{
+    pub fn new(write: F) -> Self {
+        Self { write }
+    }
+}
+
+impl<F> LineWriter for FunctionOutput<F>
+where
+    F: FnMut(i32),
+{
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        (self.write)(value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use std::cell::Cell;
+
+    /// Doubles every input until it reads 0.
+    fn doubler() -> Vec<i32> {
+        vec![
+            3, 15, 1006, 15, 14, 1002, 15, 2, 16, 4, 16, 1105, 1, 0, 99, 0, 0,
+        ]
+    }
+
+    #[test]
+    fn iterator_input() {
+        let mut input = IteratorInput::new((1..4).chain(Some(0)));
+        let mut outputs = Vec::new();
+        let mut output = FunctionOutput::new(|value| outputs.push(value));
+
+        let result = Program::new(doubler(), &mut input, &mut output).run();
+
+        assert!(result.is_ok());
+        assert_eq!(outputs, [2, 4, 6]);
+    }
+
+    #[test]
+    fn exhausted_iterator_fails() {
+        let mut input = IteratorInput::new(vec![1]);
+
+        assert_eq!(input.read_line(), Ok(1));
+        assert!(input.read_line().is_err());
+    }
+
+    #[test]
+    fn input_depends_on_previous_output() {
+        let last = Cell::new(1);
+        let mut input = FunctionInput::new(|| match last.get() {
+            value if value > 100 => Some(0),
+            value => Some(value),
+        });
+        let mut output = FunctionOutput::new(|value| last.set(value));
+
+        let result = Program::new(doubler(), &mut input, &mut output).run();
+
+        assert!(result.is_ok());
+        assert_eq!(last.get(), 128);
+    }
+
+    #[test]
+    fn function_without_value_fails() {
+        let mut input = FunctionInput::new(|| None);
+
+        assert!(input.read_line().is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use super::{LineReader, LineWriter};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, StdinLock, Stdout, Write};
+
+/// Feeds text to a program one character code per read.
+///
+/// Text is read a line at a time, and every line reaches the program terminated by a single
+/// `\n`, whatever line ending the source used.
+pub struct AsciiReader<R> {
+    source: R,
+    line: VecDeque<i32>,
+}
+
+impl<R> AsciiReader<R>
+where
+    R: BufRead,
+{
+    pub fn new(source: R) -> Self {
+        Self {
+            source,
+            line: VecDeque::new(),
+        }
+    }
+}
+
+impl<'a> AsciiReader<&'a [u8]> {
+    pub fn from_text(text: &'a str) -> Self {
+        Self::new(text.as_bytes())
+    }
+}
+
+impl AsciiReader<StdinLock<'static>> {
+    pub fn stdin() -> Self {
+        Self::new(io::stdin().lock())
+    }
+}
+
+impl<R> LineReader for AsciiReader<R>
+where
+    R: BufRead,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        if self.line.is_empty() {
+            let mut buffer = String::new();
+            let read = self
+                .source
+                .read_line(&mut buffer)
+                .map_err(|error| format!("Failed to read text: {}", error))?;
+            if read == 0 {
+                return Err("Text input was exhausted".into());
+            }
+
+            let line = buffer.trim_end_matches(&['\r', '\n'][..]);
+            self.line
+                .extend(line.chars().chain(Some('\n')).map(|c| c as i32));
+        }
+
+        Ok(self.line.pop_front().unwrap())
+    }
+}
+
+/// Prints the character codes a program writes as text.
+///
+/// Text is kept until its line ends, and is written along with the `\n`, when `flush` is
+/// called or when the writer is dropped. Values outside of ASCII are not characters, so they
+/// are written as a decimal number on a line of their own, like `StdoutWriter` would, after
+/// ending the line that was still pending.
+pub struct AsciiWriter<W>
+where
+    W: Write,
+{
+    sink: W,
+    line: Vec<u8>,
+    numbers: Vec<i32>,
+}
+
+impl<W> AsciiWriter<W>
+where
+    W: Write,
+{
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink,
+            line: Vec::new(),
+            numbers: Vec::new(),
+        }
+    }
+
+    /// The values that were not ASCII, in the order they were written.
+    pub fn numbers(&self) -> &[i32] {
+        &self.numbers
+    }
+
+    /// Writes the pending text, even though its line has not ended.
+    pub fn flush(&mut self) -> Result<(), Cow<'static, str>> {
+        let result = self
+            .sink
+            .write_all(&self.line)
+            .and_then(|_| self.sink.flush());
+        self.line.clear();
+
+        result.map_err(|error| format!("Failed to write text output: {}", error).into())
+    }
+}
+
+impl AsciiWriter<Vec<u8>> {
+    pub fn buffer() -> Self {
+        Self::new(Vec::new())
+    }
+
+    /// The text that was written so far, without the pending line.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.sink)
+    }
+}
+
+impl AsciiWriter<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W> LineWriter for AsciiWriter<W>
+where
+    W: Write,
+{
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        if (0..=127).contains(&value) {
+            self.line.push(value as u8);
+            if value == i32::from(b'\n') {
+                self.flush()?;
+            }
+            return Ok(());
+        }
+
+        self.numbers.push(value);
+        if !self.line.is_empty() {
+            self.line.push(b'\n');
+        }
+        self.line.extend(format!("{}\n", value).bytes());
+        self.flush()
+    }
+}
+
+impl<W> Drop for AsciiWriter<W>
+where
+    W: Write,
+{
+    fn drop(&mut self) {
+        // There is no one left to report a failure to.
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_text_one_character_at_a_time() {
+        let mut reader = AsciiReader::from_text("NOT A J\r\nWALK");
+
+        let mut read = Vec::new();
+        while let Ok(value) = reader.read_line() {
+            read.push(value);
+        }
+
+        let expected: Vec<i32> = "NOT A J\nWALK\n".bytes().map(i32::from).collect();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn reading_past_the_end_fails() {
+        let mut reader = AsciiReader::from_text("");
+
+        assert!(reader.read_line().is_err());
+    }
+
+    #[test]
+    fn write_text_and_numbers() {
+        let mut writer = AsciiWriter::buffer();
+
+        for value in "..#\n".bytes() {
+            writer.write_line(i32::from(value)).unwrap();
+        }
+        writer.write_line(1_234_567).unwrap();
+        writer.write_line(-1).unwrap();
+
+        assert_eq!(writer.text(), "..#\n1234567\n-1\n");
+        assert_eq!(writer.numbers(), [1_234_567, -1]);
+    }
+
+    #[test]
+    fn text_is_written_a_line_at_a_time() {
+        let mut writer = AsciiWriter::buffer();
+
+        for value in "ab\ncd".bytes() {
+            writer.write_line(i32::from(value)).unwrap();
+        }
+        assert_eq!(writer.text(), "ab\n");
+
+        writer.flush().unwrap();
+        assert_eq!(writer.text(), "ab\ncd");
+    }
+
+    #[test]
+    fn number_ends_the_pending_line() {
+        let mut writer = AsciiWriter::buffer();
+
+        for value in "ab".bytes() {
+            writer.write_line(i32::from(value)).unwrap();
+        }
+        writer.write_line(1000).unwrap();
+
+        assert_eq!(writer.text(), "ab\n1000\n");
+    }
+
+    #[test]
+    fn pending_line_is_written_when_dropped() {
+        let mut sink = Vec::new();
+        let mut writer = AsciiWriter::new(&mut sink);
+        for value in "ab".bytes() {
+            writer.write_line(i32::from(value)).unwrap();
+        }
+        drop(writer);
+
+        assert_eq!(sink, b"ab");
+    }
+}
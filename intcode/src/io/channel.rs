@@ -0,0 +1,118 @@
+use super::{LineReader, LineWriter};
+use std::borrow::Cow;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+pub struct ChannelReader {
+    receiver: Receiver<i32>,
+    closed: bool,
+}
+
+impl ChannelReader {
+    pub fn new(receiver: Receiver<i32>) -> Self {
+        Self {
+            receiver,
+            closed: false,
+        }
+    }
+
+    /// Whether a read failed because every sender was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Takes every value that was sent but never read.
+    pub fn drain(&mut self) -> Vec<i32> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+impl LineReader for ChannelReader {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        self.receiver.recv().map_err(|_| {
+            self.closed = true;
+            "Input channel was closed".into()
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ChannelWriter {
+    sender: Sender<i32>,
+    closed: bool,
+}
+
+impl ChannelWriter {
+    pub fn new(sender: Sender<i32>) -> Self {
+        Self {
+            sender,
+            closed: false,
+        }
+    }
+
+    /// Whether a write failed because the receiver was dropped.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+impl LineWriter for ChannelWriter {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        self.sender.send(value).map_err(|_| {
+            self.closed = true;
+            "Output channel was closed".into()
+        })
+    }
+}
+
+pub fn channel() -> (ChannelWriter, ChannelReader) {
+    let (sender, receiver) = mpsc::channel();
+
+    (ChannelWriter::new(sender), ChannelReader::new(receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_read() {
+        let (mut writer, mut reader) = channel();
+        writer.write_line(1).unwrap();
+        writer.write_line(2).unwrap();
+
+        assert_eq!(reader.read_line(), Ok(1));
+        assert_eq!(reader.read_line(), Ok(2));
+        assert!(!reader.is_closed());
+    }
+
+    #[test]
+    fn read_after_writer_dropped() {
+        let (mut writer, mut reader) = channel();
+        writer.write_line(1).unwrap();
+        drop(writer);
+
+        assert_eq!(reader.read_line(), Ok(1));
+        assert!(reader.read_line().is_err());
+        assert!(reader.is_closed());
+    }
+
+    #[test]
+    fn write_after_reader_dropped() {
+        let (mut writer, reader) = channel();
+        drop(reader);
+
+        assert!(writer.write_line(1).is_err());
+        assert!(writer.is_closed());
+    }
+
+    #[test]
+    fn drain_unread_values() {
+        let (mut writer, mut reader) = channel();
+        writer.write_line(1).unwrap();
+        writer.write_line(2).unwrap();
+        writer.write_line(3).unwrap();
+
+        assert_eq!(reader.read_line(), Ok(1));
+        assert_eq!(reader.drain(), [2, 3]);
+    }
+}
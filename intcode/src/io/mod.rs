@@ -0,0 +1,55 @@
+pub mod adapter;
+pub mod ascii;
+pub mod channel;
+pub mod programmable;
+pub mod recording;
+pub mod socket;
+pub mod stdio;
+#[cfg(test)]
+pub mod testing;
+
+use std::borrow::Cow;
+
+pub trait LineReader {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>>;
+}
+
+pub trait LineWriter {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>>;
+}
+
+impl<R> LineReader for Box<R>
+where
+    R: LineReader + ?Sized,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        (**self).read_line()
+    }
+}
+
+impl<W> LineWriter for Box<W>
+where
+    W: LineWriter + ?Sized,
+{
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        (**self).write_line(value)
+    }
+}
+
+impl<R> LineReader for &mut R
+where
+    R: LineReader + ?Sized,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        (**self).read_line()
+    }
+}
+
+impl<W> LineWriter for &mut W
+where
+    W: LineWriter + ?Sized,
+{
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        (**self).write_line(value)
+    }
+}
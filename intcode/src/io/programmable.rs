@@ -1,4 +1,5 @@
 use super::{LineReader, LineWriter};
+use std::borrow::Cow;
 
 pub struct ProgrammableInput {
     current: usize,
@@ -12,17 +13,18 @@ impl ProgrammableInput {
 }
 
 impl LineReader for ProgrammableInput {
-    fn read_line(&mut self) -> i32 {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
         let value = *self
             .inputs
             .get(self.current)
-            .expect("Attempted to read past end");
+            .ok_or("Attempted to read past end")?;
         self.current += 1;
 
-        value
+        Ok(value)
     }
 }
 
+#[derive(Default)]
 pub struct ProgrammableOutput {
     output: Vec<i32>,
 }
@@ -38,7 +40,9 @@ impl ProgrammableOutput {
 }
 
 impl LineWriter for ProgrammableOutput {
-    fn write_line(&mut self, value: i32) {
-        self.output.push(value)
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        self.output.push(value);
+
+        Ok(())
     }
 }
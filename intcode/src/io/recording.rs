@@ -0,0 +1,574 @@
+use super::{LineReader, LineWriter};
+use crate::{Program, State};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Something a program read or wrote, stamped with the number of instructions that were
+/// executed before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Input {
+        step: usize,
+        value: i32,
+    },
+    Output {
+        step: usize,
+        value: i32,
+    },
+    /// A read or write that failed, such as a read after the input ran out, which ends the run.
+    Failed {
+        step: usize,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Input { step, value } => write!(f, "{} in {}", step, value),
+            Event::Output { step, value } => write!(f, "{} out {}", step, value),
+            Event::Failed { step } => write!(f, "{} failed", step),
+        }
+    }
+}
+
+/// Every input and output of one run, in order.
+///
+/// Sessions are saved as text with one event per line, for example `12 in 5`, `20 out 7` or
+/// `25 failed`. Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    events: Vec<Event>,
+}
+
+impl Session {
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Cow<'static, str>> {
+        let events = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(idx, line)| {
+                let invalid = || format!("Invalid session event on line {}: {}", idx + 1, line);
+                let parts: Vec<_> = line.split_whitespace().collect();
+                let step = parts[0].parse().map_err(|_| invalid())?;
+
+                match parts[1..] {
+                    ["in", value] => Ok(Event::Input {
+                        step,
+                        value: value.parse().map_err(|_| invalid())?,
+                    }),
+                    ["out", value] => Ok(Event::Output {
+                        step,
+                        value: value.parse().map_err(|_| invalid())?,
+                    }),
+                    ["failed"] => Ok(Event::Failed { step }),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { events })
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        let text = fs::read_to_string(path.as_ref()).map_err(|error| {
+            format!(
+                "Failed to read session {}: {}",
+                path.as_ref().display(),
+                error
+            )
+        })?;
+
+        Self::parse(&text)
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<(), Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path.as_ref(), self.to_string()).map_err(|error| {
+            format!(
+                "Failed to write session {}: {}",
+                path.as_ref().display(),
+                error
+            )
+            .into()
+        })
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The events a recorder has seen so far, which can also be written to a file as they happen,
+/// so that a run that never finishes still leaves its session behind.
+#[derive(Default)]
+pub struct Journal {
+    events: Vec<Event>,
+    file: Option<(File, PathBuf)>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a journal that writes every event to a new session file at `path`.
+    pub fn create<P>(path: P) -> Result<Self, Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path)
+            .map_err(|error| format!("Failed to create session {}: {}", path.display(), error))?;
+
+        Ok(Self {
+            events: Vec::new(),
+            file: Some((file, path)),
+        })
+    }
+
+    fn push(&mut self, event: Event) -> Result<(), Cow<'static, str>> {
+        self.events.push(event);
+
+        match &mut self.file {
+            Some((file, path)) => writeln!(file, "{}", event).map_err(|error| {
+                format!("Failed to write session {}: {}", path.display(), error).into()
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn into_session(self) -> Session {
+        Session {
+            events: self.events,
+        }
+    }
+}
+
+/// Passes every value through to `inner` and adds it to a journal, stamped with the number of
+/// instructions the clock says were executed before it. A reader and a writer that share a
+/// clock and a journal record one session. A read or write that fails is recorded as well, as
+/// it is where the run ends.
+pub struct Recorder<'a, T> {
+    inner: T,
+    clock: &'a Cell<usize>,
+    journal: &'a RefCell<Journal>,
+}
+
+impl<'a, T> Recorder<'a, T> {
+    pub fn new(inner: T, clock: &'a Cell<usize>, journal: &'a RefCell<Journal>) -> Self {
+        Self {
+            inner,
+            clock,
+            journal,
+        }
+    }
+}
+
+impl<T> LineReader for Recorder<'_, T>
+where
+    T: LineReader,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        let step = self.clock.get();
+        let value = self.inner.read_line().inspect_err(|_| {
+            // The failure to read matters more than a failure to record it.
+            let _ = self.journal.borrow_mut().push(Event::Failed { step });
+        })?;
+        self.journal
+            .borrow_mut()
+            .push(Event::Input { step, value })?;
+
+        Ok(value)
+    }
+}
+
+impl<T> LineWriter for Recorder<'_, T>
+where
+    T: LineWriter,
+{
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        let step = self.clock.get();
+        self.journal
+            .borrow_mut()
+            .push(Event::Output { step, value })?;
+        self.inner.write_line(value).inspect_err(|_| {
+            let _ = self.journal.borrow_mut().push(Event::Failed { step });
+        })
+    }
+}
+
+/// Runs `memory` with `input` and `output` and records every value that passes through them.
+///
+/// The session is returned even if the run failed, since that is usually the run worth
+/// keeping.
+pub fn record<Input, Output>(
+    memory: Vec<i32>,
+    input: &mut Input,
+    output: &mut Output,
+) -> (Result<(), Cow<'static, str>>, Session)
+where
+    Input: LineReader,
+    Output: LineWriter,
+{
+    let clock = Cell::new(0);
+    let journal = RefCell::new(Journal::new());
+    let mut input = Recorder::new(input, &clock, &journal);
+    let mut output = Recorder::new(output, &clock, &journal);
+
+    let result = run_with_clock(memory, &mut input, &mut output, &clock);
+
+    (result, journal.into_inner().into_session())
+}
+
+/// Checks a run against a session: the program reads the recorded inputs, and every read and
+/// write has to happen at the step it was recorded at, according to the clock. A read or write
+/// that failed in the session fails again at the same step.
+pub struct Replay<'a> {
+    session: &'a Session,
+    clock: &'a Cell<usize>,
+    next: Cell<usize>,
+    mismatch: RefCell<Option<String>>,
+    failed: Cell<bool>,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(session: &'a Session, clock: &'a Cell<usize>) -> Self {
+        Self {
+            session,
+            clock,
+            next: Cell::new(0),
+            mismatch: RefCell::new(None),
+            failed: Cell::new(false),
+        }
+    }
+
+    /// Returns a reader or a writer for the run, which share the position in the session.
+    pub fn replayer(&self) -> Replayer<'_> {
+        Replayer { replay: self }
+    }
+
+    /// Whether the run failed where the session recorded a failure, which makes that failure
+    /// part of the expected run.
+    pub fn failed_as_recorded(&self) -> bool {
+        self.failed.get()
+    }
+
+    /// Returns the first difference between the run and the session. A run that halted must
+    /// also have used every event of the session.
+    pub fn check(&self, halted: bool) -> Result<(), Cow<'static, str>> {
+        if let Some(mismatch) = self.mismatch.borrow().clone() {
+            return Err(mismatch.into());
+        }
+
+        match self.session.events.get(self.next.get()) {
+            Some(event) if halted => {
+                Err(format!("Program halted but the session expected {:?}", event).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn next_event(&self) -> Option<Event> {
+        let event = self.session.events.get(self.next.get()).copied();
+        self.next.set(self.next.get() + 1);
+        event
+    }
+
+    /// Replays a failure the session recorded at `step` next, if there is one.
+    fn recorded_failure(&self, step: usize) -> Option<Cow<'static, str>> {
+        match self.session.events.get(self.next.get()) {
+            Some(Event::Failed { step: recorded }) if *recorded == step => {
+                self.next.set(self.next.get() + 1);
+                self.failed.set(true);
+                Some(format!("The session failed at step {}", step).into())
+            }
+            _ => None,
+        }
+    }
+
+    fn fail(&self, mismatch: String) -> Cow<'static, str> {
+        self.mismatch
+            .borrow_mut()
+            .get_or_insert(mismatch)
+            .clone()
+            .into()
+    }
+}
+
+pub struct Replayer<'a> {
+    replay: &'a Replay<'a>,
+}
+
+impl LineReader for Replayer<'_> {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        let step = self.replay.clock.get();
+        if let Some(failure) = self.replay.recorded_failure(step) {
+            return Err(failure);
+        }
+
+        match self.replay.next_event() {
+            Some(Event::Input {
+                step: recorded,
+                value,
+            }) if recorded == step => Ok(value),
+            Some(expected) => Err(self.replay.fail(format!(
+                "Expected {:?} but the program read input at step {}",
+                expected, step
+            ))),
+            None => Err(self.replay.fail(format!(
+                "Expected the session to end but the program read input at step {}",
+                step
+            ))),
+        }
+    }
+}
+
+impl LineWriter for Replayer<'_> {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        let step = self.replay.clock.get();
+        let actual = Event::Output { step, value };
+
+        match self.replay.next_event() {
+            Some(expected) if expected == actual => match self.replay.recorded_failure(step) {
+                Some(failure) => Err(failure),
+                None => Ok(()),
+            },
+            Some(expected) => Err(self
+                .replay
+                .fail(format!("Expected {:?} but got {:?}", expected, actual))),
+            None => Err(self
+                .replay
+                .fail(format!("Expected the session to end but got {:?}", actual))),
+        }
+    }
+}
+
+/// Runs `memory` on the inputs of `session` and checks that it reads and writes exactly what
+/// was recorded, at the same steps. A run that fails where the session failed matches it.
+pub fn replay(memory: Vec<i32>, session: &Session) -> Result<(), Cow<'static, str>> {
+    let clock = Cell::new(0);
+    let replay = Replay::new(session, &clock);
+
+    let result = run_with_clock(
+        memory,
+        &mut replay.replayer(),
+        &mut replay.replayer(),
+        &clock,
+    );
+    replay.check(result.is_ok())?;
+    if replay.failed_as_recorded() {
+        return Ok(());
+    }
+
+    result
+}
+
+fn run_with_clock<Input, Output>(
+    memory: Vec<i32>,
+    input: &mut Input,
+    output: &mut Output,
+    clock: &Cell<usize>,
+) -> Result<(), Cow<'static, str>>
+where
+    Input: LineReader,
+    Output: LineWriter,
+{
+    let mut program = Program::new(memory, input, output);
+
+    while program.step()? == State::Running {
+        clock.set(clock.get() + 1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::programmable::{ProgrammableInput, ProgrammableOutput};
+
+    /// Prints every input plus one until it reads 0.
+    fn increment() -> Vec<i32> {
+        vec![
+            3, 15, 1006, 15, 14, 1001, 15, 1, 16, 4, 16, 1105, 1, 0, 99, 0, 0,
+        ]
+    }
+
+    fn recorded_session() -> Session {
+        let mut input = ProgrammableInput::new(vec![4, 9, 0]);
+        let mut output = ProgrammableOutput::new();
+
+        let (result, session) = record(increment(), &mut input, &mut output);
+
+        assert!(result.is_ok());
+        assert_eq!(output.output(), [5, 10]);
+        session
+    }
+
+    #[test]
+    fn record_inputs_and_outputs() {
+        let session = recorded_session();
+
+        assert_eq!(
+            session.events(),
+            [
+                Event::Input { step: 0, value: 4 },
+                Event::Output { step: 3, value: 5 },
+                Event::Input { step: 5, value: 9 },
+                Event::Output { step: 8, value: 10 },
+                Event::Input { step: 10, value: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn text_roundtrip() {
+        let session = recorded_session();
+
+        let text = session.to_string();
+
+        assert_eq!(text.lines().next(), Some("0 in 4"));
+        assert_eq!(Session::parse(&text), Ok(session));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_events() {
+        assert!(Session::parse("0 in x").is_err());
+        assert!(Session::parse("0 sideways 1").is_err());
+        assert!(Session::parse("0 failed 1").is_err());
+        assert!(Session::parse("0 in").is_err());
+        assert_eq!(Session::parse("# comment\n\n"), Ok(Session::default()));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("intcode-session-{}", std::process::id()));
+        let session = recorded_session();
+
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Ok(session));
+    }
+
+    #[test]
+    fn journal_writes_events_as_they_happen() {
+        let path = std::env::temp_dir().join(format!("intcode-journal-{}", std::process::id()));
+        let clock = Cell::new(0);
+        let journal = RefCell::new(Journal::create(&path).unwrap());
+        let mut input = Recorder::new(ProgrammableInput::new(vec![4]), &clock, &journal);
+        let mut output = Recorder::new(ProgrammableOutput::new(), &clock, &journal);
+
+        // The program fails on its second read, before anything could save the session.
+        let result = run_with_clock(increment(), &mut input, &mut output, &clock);
+        let written = Session::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(
+            written.unwrap().events(),
+            [
+                Event::Input { step: 0, value: 4 },
+                Event::Output { step: 3, value: 5 },
+                Event::Failed { step: 5 },
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_accepts_the_recorded_failure() {
+        let mut input = ProgrammableInput::new(vec![4]);
+        let mut output = ProgrammableOutput::new();
+        let (result, session) = record(increment(), &mut input, &mut output);
+
+        assert!(result.is_err());
+        assert_eq!(session.events().last(), Some(&Event::Failed { step: 5 }));
+        assert_eq!(Session::parse(&session.to_string()), Ok(session));
+        assert_eq!(
+            replay(
+                increment(),
+                &Session::parse("0 in 4\n3 out 5\n5 failed\n").unwrap()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn replay_detects_a_failure_at_another_step() {
+        let session = Session::parse("0 in 4\n3 out 5\n4 failed\n").unwrap();
+
+        assert_eq!(
+            replay(increment(), &session),
+            Err("Expected Failed { step: 4 } but the program read input at step 5".into())
+        );
+    }
+
+    #[test]
+    fn replay_matching_program() {
+        assert_eq!(replay(increment(), &recorded_session()), Ok(()));
+    }
+
+    #[test]
+    fn replay_detects_different_output() {
+        let mut program = increment();
+        program[7] = 2;
+
+        assert!(replay(program, &recorded_session()).is_err());
+    }
+
+    #[test]
+    fn replay_detects_extra_input() {
+        let session = Session::parse("0 in 4\n3 out 5\n").unwrap();
+
+        assert_eq!(
+            replay(increment(), &session),
+            Err("Expected the session to end but the program read input at step 5".into())
+        );
+    }
+
+    #[test]
+    fn replay_detects_input_at_another_step() {
+        let session = Session::parse("0 in 4\n4 out 5\n").unwrap();
+
+        assert_eq!(
+            replay(increment(), &session),
+            Err(
+                "Expected Output { step: 4, value: 5 } but got Output { step: 3, value: 5 }".into()
+            )
+        );
+
+        let session = Session::parse("1 in 4\n").unwrap();
+
+        assert_eq!(
+            replay(increment(), &session),
+            Err("Expected Input { step: 1, value: 4 } but the program read input at step 0".into())
+        );
+    }
+
+    #[test]
+    fn replay_detects_missing_events() {
+        let session = Session::parse(&format!("{}20 out 1\n", recorded_session())).unwrap();
+
+        assert!(replay(increment(), &session).is_err());
+    }
+}
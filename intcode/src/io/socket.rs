@@ -0,0 +1,135 @@
+use super::{LineReader, LineWriter};
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+/// A connected socket whose two directions can be used independently.
+pub trait Stream: Read + Write + Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl Stream for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+}
+
+/// Reads one decimal value per line from a socket, like `StdinReader` does from stdin.
+pub struct SocketReader<S>
+where
+    S: Read,
+{
+    reader: BufReader<S>,
+    buffer: String,
+}
+
+impl<S> LineReader for SocketReader<S>
+where
+    S: Read,
+{
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        self.buffer.clear();
+        let read = self
+            .reader
+            .read_line(&mut self.buffer)
+            .map_err(|error| format!("Failed to read from socket: {}", error))?;
+        if read == 0 {
+            return Err("Socket was closed".into());
+        }
+
+        let line = self.buffer.trim();
+        line.parse()
+            .map_err(|_| format!("Input was not of the type i32: {}", line).into())
+    }
+}
+
+/// Writes one decimal value per line to a socket.
+pub struct SocketWriter<S>
+where
+    S: Write,
+{
+    writer: S,
+}
+
+impl<S> LineWriter for SocketWriter<S>
+where
+    S: Write,
+{
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        // A single write keeps every value in one segment.
+        self.writer
+            .write_all(format!("{}\n", value).as_bytes())
+            .and_then(|_| self.writer.flush())
+            .map_err(|error| format!("Failed to write output to socket: {}", error).into())
+    }
+}
+
+pub fn split<S>(stream: S) -> io::Result<(SocketReader<S>, SocketWriter<S>)>
+where
+    S: Stream,
+{
+    let writer = stream.try_clone()?;
+
+    Ok((
+        SocketReader {
+            reader: BufReader::new(stream),
+            buffer: String::with_capacity(32),
+        },
+        SocketWriter { writer },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn tcp_roundtrip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        let (mut client_reader, mut client_writer) = split(client).unwrap();
+        let (mut server_reader, mut server_writer) = split(server).unwrap();
+
+        client_writer.write_line(-42).unwrap();
+        assert_eq!(server_reader.read_line(), Ok(-42));
+
+        server_writer.write_line(7).unwrap();
+        assert_eq!(client_reader.read_line(), Ok(7));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_roundtrip() {
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let (_, mut client_writer) = split(client).unwrap();
+        let (mut server_reader, _) = split(server).unwrap();
+
+        client_writer.write_line(1).unwrap();
+        client_writer.write_line(2).unwrap();
+        assert_eq!(server_reader.read_line(), Ok(1));
+        assert_eq!(server_reader.read_line(), Ok(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn closed_socket_fails() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (mut server_reader, _) = split(server).unwrap();
+        drop(client);
+
+        assert!(server_reader.read_line().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use super::{LineReader, LineWriter};
+use std::borrow::Cow;
+use std::io::{BufRead, Write};
+
+pub struct StdinReader {
+    buffer: String,
+}
+
+impl StdinReader {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::with_capacity(32),
+        }
+    }
+}
+
+impl Default for StdinReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineReader for StdinReader {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        let stdin = std::io::stdin();
+        let mut lock = stdin.lock();
+
+        self.buffer.clear();
+        lock.read_line(&mut self.buffer)
+            .map_err(|error| format!("Failed to read line from stdin: {}", error))?;
+
+        let line = self.buffer.trim();
+        line.parse()
+            .map_err(|_| format!("Input was not of the type i32: {:?}", line).into())
+    }
+}
+
+#[derive(Default)]
+pub struct StdoutWriter;
+
+impl StdoutWriter {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl LineWriter for StdoutWriter {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        let stdout = std::io::stdout();
+        let mut stdout_lock = stdout.lock();
+
+        writeln!(stdout_lock, "{}", value)
+            .map_err(|error| format!("Failed to write output to stdout: {}", error).into())
+    }
+}
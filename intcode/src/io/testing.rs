@@ -1,4 +1,5 @@
 use super::{LineReader, LineWriter};
+use std::borrow::Cow;
 
 pub struct UnitTestInput {
     current: usize,
@@ -24,14 +25,14 @@ impl Drop for UnitTestInput {
 }
 
 impl LineReader for UnitTestInput {
-    fn read_line(&mut self) -> i32 {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
         let value = *self
             .inputs
             .get(self.current)
             .expect("Attempted to read too many times");
         self.current += 1;
 
-        value
+        Ok(value)
     }
 }
 
@@ -58,7 +59,7 @@ impl UnitTestOutput {
 }
 
 impl LineWriter for UnitTestOutput {
-    fn write_line(&mut self, value: i32) {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
         let expected_value = *self
             .expected_outputs
             .get(self.current)
@@ -71,6 +72,8 @@ impl LineWriter for UnitTestOutput {
         );
 
         self.current += 1;
+
+        Ok(())
     }
 }
 
@@ -89,11 +92,11 @@ mod tests {
     #[test]
     fn read_correct() {
         let mut input = UnitTestInput::new(vec![1, 2, 3, 4, 5]);
-        assert_eq!(input.read_line(), 1);
-        assert_eq!(input.read_line(), 2);
-        assert_eq!(input.read_line(), 3);
-        assert_eq!(input.read_line(), 4);
-        assert_eq!(input.read_line(), 5);
+        assert_eq!(input.read_line(), Ok(1));
+        assert_eq!(input.read_line(), Ok(2));
+        assert_eq!(input.read_line(), Ok(3));
+        assert_eq!(input.read_line(), Ok(4));
+        assert_eq!(input.read_line(), Ok(5));
         input.assert_finished();
     }
 
@@ -101,10 +104,10 @@ mod tests {
     #[should_panic]
     fn read_not_all_are_read() {
         let mut input = UnitTestInput::new(vec![1, 2, 3, 4, 5]);
-        assert_eq!(input.read_line(), 1);
-        assert_eq!(input.read_line(), 2);
-        assert_eq!(input.read_line(), 3);
-        assert_eq!(input.read_line(), 4);
+        assert_eq!(input.read_line(), Ok(1));
+        assert_eq!(input.read_line(), Ok(2));
+        assert_eq!(input.read_line(), Ok(3));
+        assert_eq!(input.read_line(), Ok(4));
         input.assert_finished();
     }
 
@@ -112,28 +115,28 @@ mod tests {
     #[should_panic]
     fn read_too_many_times() {
         let mut input = UnitTestInput::new(vec![1, 2, 3]);
-        assert_eq!(input.read_line(), 1);
-        assert_eq!(input.read_line(), 2);
-        assert_eq!(input.read_line(), 3);
-        assert_eq!(input.read_line(), 4);
-        assert_eq!(input.read_line(), 5);
+        assert_eq!(input.read_line(), Ok(1));
+        assert_eq!(input.read_line(), Ok(2));
+        assert_eq!(input.read_line(), Ok(3));
+        assert_eq!(input.read_line(), Ok(4));
+        assert_eq!(input.read_line(), Ok(5));
         input.assert_finished();
     }
 
     #[test]
     fn write_correct() {
         let mut output = UnitTestOutput::new(vec![1, 2, 3]);
-        output.write_line(1);
-        output.write_line(2);
-        output.write_line(3);
+        output.write_line(1).unwrap();
+        output.write_line(2).unwrap();
+        output.write_line(3).unwrap();
     }
 
     #[test]
     #[should_panic]
     fn write_not_enough_output() {
         let mut output = UnitTestOutput::new(vec![1, 2, 3]);
-        output.write_line(1);
-        output.write_line(2);
+        output.write_line(1).unwrap();
+        output.write_line(2).unwrap();
         output.assert_finished();
     }
 
@@ -141,8 +144,8 @@ mod tests {
     #[should_panic]
     fn write_incorrect_values() {
         let mut output = UnitTestOutput::new(vec![1, 2, 3]);
-        output.write_line(1);
-        output.write_line(3);
+        output.write_line(1).unwrap();
+        output.write_line(3).unwrap();
         output.assert_finished();
     }
 }
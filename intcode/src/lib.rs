@@ -0,0 +1,28 @@
+pub mod assembler;
+pub mod asynchronous;
+pub mod compiler;
+pub mod coverage;
+pub mod disassembler;
+pub mod extensions;
+pub mod fuzz;
+pub mod gdb;
+pub mod ictest;
+pub mod io;
+pub mod linker;
+pub mod loader;
+pub mod minimiser;
+pub mod network;
+pub mod operations;
+pub mod optimiser;
+pub mod program;
+pub mod protection;
+pub mod search;
+pub mod server;
+pub mod source_map;
+pub mod specialiser;
+pub mod symbolic;
+pub mod threaded;
+pub mod validator;
+
+pub use operations::Operation;
+pub use program::{Program, State};
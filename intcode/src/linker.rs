@@ -0,0 +1,371 @@
+//! Links objects written by the assembler into one program.
+//!
+//! Modules are placed one after the other in the order given, so the first one starts at
+//! address 0, followed by the library modules any of them included. Every relocation is then
+//! patched with the address of its module or of the global symbol it names, and the source
+//! lines of all modules are combined into one source map. The map of the result lists where
+//! every symbol ended up, one per line, as the address, `global` or `local`, the symbol and its
+//! module:
+//!
+//! ```text
+//! 0 global start main
+//! 12 local loop main
+//! 40 global print_int <print>
+//! ```
+
+use super::assembler::{self, object::Object};
+use super::source_map::SourceMap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapSymbol {
+    pub address: usize,
+    pub name: String,
+    pub module: String,
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map {
+    /// Every symbol, ordered by address.
+    pub symbols: Vec<MapSymbol>,
+}
+
+impl Map {
+    pub fn parse(text: &str) -> Result<Self, Cow<'static, str>> {
+        let mut map = Map::default();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(4, ' ');
+            let symbol = (|| {
+                let address = fields.next()?.parse().ok()?;
+                let global = match fields.next()? {
+                    "global" => true,
+                    "local" => false,
+                    _ => return None,
+                };
+                let name = fields.next()?.to_string();
+                let module = fields.next()?.to_string();
+
+                Some(MapSymbol {
+                    address,
+                    name,
+                    module,
+                    global,
+                })
+            })();
+
+            map.symbols.push(symbol.ok_or_else(|| {
+                format!(
+                    "Line {}: expected `address global|local name module`",
+                    idx + 1
+                )
+            })?);
+        }
+
+        map.symbols.sort_by_key(|symbol| symbol.address);
+        Ok(map)
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+
+        Self::parse(&text).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for symbol in &self.symbols {
+            let visibility = if symbol.global { "global" } else { "local" };
+            writeln!(
+                f,
+                "{} {} {} {}",
+                symbol.address, visibility, symbol.name, symbol.module
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linked {
+    pub memory: Vec<i32>,
+    pub map: Map,
+    pub source_map: SourceMap,
+}
+
+/// Links `objects` and the library modules they need.
+pub fn link(mut objects: Vec<Object>) -> Result<Linked, Cow<'static, str>> {
+    if objects.is_empty() {
+        return Err("Nothing to link".into());
+    }
+
+    // Library modules can include other modules, which then have to be linked too.
+    let mut next = 0;
+    while next < objects.len() {
+        for library in objects[next].libraries.clone() {
+            let name = format!("<{}>", library);
+            if objects.iter().all(|object| object.name != name) {
+                objects.push(assembler::library_object(&library)?);
+            }
+        }
+        next += 1;
+    }
+
+    let mut bases = Vec::with_capacity(objects.len());
+    let mut globals: HashMap<&str, (usize, &str)> = HashMap::new();
+    let mut map = Map::default();
+    let mut len = 0;
+    for object in &objects {
+        bases.push(len);
+
+        for symbol in &object.symbols {
+            let address = len + symbol.offset;
+            if symbol.global {
+                if let Some((_, module)) = globals.insert(&symbol.name, (address, &object.name)) {
+                    return Err(format!(
+                        "`{}` is defined by both {} and {}",
+                        symbol.name, module, object.name
+                    )
+                    .into());
+                }
+            }
+
+            map.symbols.push(MapSymbol {
+                address,
+                name: symbol.name.clone(),
+                module: object.name.clone(),
+                global: symbol.global,
+            });
+        }
+
+        len += object.words.len();
+    }
+    map.symbols.sort_by_key(|symbol| symbol.address);
+
+    let mut source_map = SourceMap::new(len);
+    for symbol in &map.symbols {
+        source_map.add_label(symbol.address, &symbol.name);
+    }
+
+    let mut memory = Vec::with_capacity(len);
+    for (object, base) in objects.iter().zip(bases) {
+        for (offset, line) in &object.lines {
+            source_map.add_line(base + offset, line.location.clone(), line.code);
+        }
+
+        let mut words: Vec<i64> = object.words.iter().map(|&word| i64::from(word)).collect();
+
+        for relocation in &object.relocations {
+            let address = match &relocation.symbol {
+                Some(symbol) => match globals.get(symbol.as_str()) {
+                    Some((address, _)) => *address,
+                    None => {
+                        return Err(
+                            format!("Undefined symbol `{}` in {}", symbol, object.name).into()
+                        )
+                    }
+                },
+                None => base,
+            };
+
+            let address = address as i64;
+            words[relocation.offset] += if relocation.negative {
+                -address
+            } else {
+                address
+            };
+        }
+
+        for (offset, word) in words.into_iter().enumerate() {
+            memory.push(i32::try_from(word).map_err(|_| {
+                format!(
+                    "The value at address {} does not fit a cell after linking",
+                    base + offset
+                )
+            })?);
+        }
+    }
+
+    Ok(Linked {
+        memory,
+        map,
+        source_map,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{adapter::IteratorInput, programmable::ProgrammableOutput};
+    use crate::Program;
+
+    fn object(text: &str, name: &str) -> Object {
+        assembler::assemble_object(text, Path::new("."), name).unwrap()
+    }
+
+    fn run(memory: Vec<i32>) -> Vec<i32> {
+        let mut input = IteratorInput::new(Vec::new());
+        let mut output = ProgrammableOutput::new();
+        Program::new(memory, &mut input, &mut output).run().unwrap();
+
+        output.output()
+    }
+
+    #[test]
+    fn cross_module_labels_are_resolved() {
+        let main = object(
+            "
+            .global start
+            start:  out [answer]
+                    jnz 1, finish
+            value:  .word 7
+            ",
+            "main",
+        );
+        let other = object(
+            "
+            .global answer, finish
+            finish: out [value]
+                    halt
+            answer: .word 42
+            value:  .word -1
+            ",
+            "other",
+        );
+
+        let linked = link(vec![main, other]).unwrap();
+
+        assert_eq!(linked.memory, [4, 9, 1105, 1, 6, 7, 4, 10, 99, 42, -1]);
+        assert_eq!(linked.source_map.symbolise(7), "finish+1");
+        assert_eq!(linked.source_map.location(8).unwrap().line, 4);
+        assert_eq!(run(linked.memory), [42, -1]);
+        assert_eq!(
+            linked.map.to_string(),
+            "0 global start main
+5 local value main
+6 global finish other
+9 global answer other
+10 local value other
+"
+        );
+    }
+
+    #[test]
+    fn libraries_are_linked_once() {
+        let main = object(
+            "
+                    mov 12, [arg0]
+                    call print_int
+                    call print_twice
+                    halt
+            .include <print>
+            ",
+            "main",
+        );
+        let other = object(
+            "
+            .global print_twice
+            print_twice:
+                    mov -3, [arg0]
+                    call print_int
+                    mov -3, [arg0]
+                    call print_int
+                    ret
+            .include <std>
+            ",
+            "other",
+        );
+
+        let linked = link(vec![main, other]).unwrap();
+        let modules: Vec<_> = linked
+            .map
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.name == "arg0")
+            .map(|symbol| symbol.module.as_str())
+            .collect();
+
+        assert_eq!(modules, ["<core>"]);
+        // "12-3-3"
+        assert_eq!(run(linked.memory), [49, 50, 45, 51, 45, 51]);
+    }
+
+    #[test]
+    fn objects_survive_their_text_form() {
+        let main = object(".global start\nstart: jnz 1, elsewhere\n", "main");
+        let other = object(".global elsewhere\nelsewhere: halt\n", "other");
+        let reparsed = Object::parse(&main.to_string()).unwrap();
+
+        assert_eq!(reparsed, main);
+        assert_eq!(
+            link(vec![reparsed, other]).unwrap().memory,
+            [1105, 1, 3, 99]
+        );
+    }
+
+    #[test]
+    fn link_errors() {
+        let undefined = object("out [missing]\n", "main");
+        let first = object(".global twice\ntwice: halt\n", "first");
+        let second = object(".global twice\ntwice: halt\n", "second");
+
+        assert_eq!(
+            link(vec![undefined]).unwrap_err(),
+            "Undefined symbol `missing` in main"
+        );
+        assert_eq!(
+            link(vec![first, second]).unwrap_err(),
+            "`twice` is defined by both first and second"
+        );
+        assert!(link(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn local_labels_are_not_visible() {
+        let main = object("jnz 1, hidden\n", "main");
+        let other = object("hidden: halt\n", "other");
+
+        assert!(link(vec![main, other]).is_err());
+    }
+
+    #[test]
+    fn map_round_trip() {
+        let map = Map {
+            symbols: vec![
+                MapSymbol {
+                    address: 0,
+                    name: "start".to_string(),
+                    module: "my module".to_string(),
+                    global: true,
+                },
+                MapSymbol {
+                    address: 7,
+                    name: "loop".to_string(),
+                    module: "<core>".to_string(),
+                    global: false,
+                },
+            ],
+        };
+
+        assert_eq!(Map::parse(&map.to_string()), Ok(map));
+        assert!(Map::parse("0 exported start main").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! Reads Intcode programs from text or from a compact binary image.
+//!
+//! Text programs are comma separated values. They may span multiple lines, end lines with a
+//! trailing comma and contain comments that start with `#` and run to the end of the line.
+//!
+//! Images start with `IMAGE_MAGIC`, followed by the number of cells and every cell, all as
+//! LEB128 varints. Cells are zigzag encoded first, so small negative values stay small.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const IMAGE_MAGIC: &[u8; 4] = b"ICv1";
+
+/// A value that could not be parsed, with its 1-based position in the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(
+                f,
+                "Missing value at line {}, column {}",
+                self.line, self.column
+            )
+        } else {
+            write!(
+                f,
+                "Invalid value `{}` at line {}, column {}",
+                self.token, self.line, self.column
+            )
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+    Image(Cow<'static, str>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "Failed to read program: {}", error),
+            LoadError::Parse(error) => error.fmt(f),
+            LoadError::Image(error) => write!(f, "Invalid program image: {}", error),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(error) => Some(error),
+            LoadError::Parse(error) => Some(error),
+            LoadError::Image(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(error: ParseError) -> Self {
+        LoadError::Parse(error)
+    }
+}
+
+impl From<LoadError> for Cow<'static, str> {
+    fn from(error: LoadError) -> Self {
+        error.to_string().into()
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<i32>, ParseError> {
+    let mut memory = Vec::new();
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let tokens: Vec<_> = line.split(',').collect();
+        let mut column = 1;
+
+        for (token_idx, token) in tokens.iter().enumerate() {
+            let trimmed = token.trim();
+            let token_column = column + token.chars().count() - token.trim_start().chars().count();
+            column += token.chars().count() + 1;
+
+            // Blank lines and a trailing comma leave an empty last token.
+            if trimmed.is_empty() && token_idx == tokens.len() - 1 {
+                continue;
+            }
+
+            match trimmed.parse() {
+                Ok(value) => memory.push(value),
+                Err(_) => {
+                    return Err(ParseError {
+                        line: line_idx + 1,
+                        column: token_column,
+                        token: trimmed.to_string(),
+                    })
+                }
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+/// Loads a program from a file, which can hold either text or an image.
+pub fn load<P>(path: P) -> Result<Vec<i32>, LoadError>
+where
+    P: AsRef<Path>,
+{
+    let bytes = fs::read(path)?;
+
+    if bytes.starts_with(IMAGE_MAGIC) {
+        return read_image(&bytes);
+    }
+
+    let text = String::from_utf8(bytes).map_err(|error| {
+        let valid = &error.as_bytes()[..error.utf8_error().valid_up_to()];
+        let valid = String::from_utf8_lossy(valid);
+        let line = valid.lines().count().max(1);
+        let column = valid.lines().last().map_or(0, |line| line.chars().count()) + 1;
+
+        LoadError::Parse(ParseError {
+            line,
+            column,
+            token: String::from("<invalid UTF-8>"),
+        })
+    })?;
+
+    Ok(parse(&text)?)
+}
+
+pub fn read_image(bytes: &[u8]) -> Result<Vec<i32>, LoadError> {
+    let mut bytes = bytes
+        .strip_prefix(&IMAGE_MAGIC[..])
+        .ok_or_else(|| LoadError::Image("missing magic bytes".into()))?
+        .iter();
+
+    let len = read_varint(&mut bytes)?;
+    let mut memory = Vec::with_capacity(len.min(1 << 20) as usize);
+    for _ in 0..len {
+        let value = read_varint(&mut bytes)?;
+        memory.push(((value >> 1) as i32) ^ -((value & 1) as i32));
+    }
+
+    if bytes.next().is_some() {
+        return Err(LoadError::Image(
+            "trailing bytes after the last cell".into(),
+        ));
+    }
+
+    Ok(memory)
+}
+
+pub fn write_image<W>(memory: &[i32], writer: &mut W) -> io::Result<()>
+where
+    W: Write,
+{
+    let mut bytes = IMAGE_MAGIC.to_vec();
+
+    write_varint(&mut bytes, memory.len() as u32);
+    for value in memory {
+        write_varint(&mut bytes, ((value << 1) ^ (value >> 31)) as u32);
+    }
+
+    writer.write_all(&bytes)
+}
+
+fn read_varint<'a, I>(bytes: &mut I) -> Result<u32, LoadError>
+where
+    I: Iterator<Item = &'a u8>,
+{
+    let mut value = 0u32;
+
+    for shift in (0..35).step_by(7) {
+        let byte = bytes
+            .next()
+            .ok_or_else(|| LoadError::Image("unexpected end of image".into()))?;
+        value |= u32::from(byte & 0x7f)
+            .checked_shl(shift)
+            .filter(|shifted| shifted >> shift == u32::from(byte & 0x7f))
+            .ok_or_else(|| LoadError::Image("value does not fit in 32 bits".into()))?;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(LoadError::Image("value does not fit in 32 bits".into()))
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_single_line() {
+        assert_eq!(
+            parse("1,9,10,3,2,3,11,0,99,30,40,50\n"),
+            Ok(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50])
+        );
+    }
+
+    #[test]
+    fn parse_lines_comments_and_trailing_commas() {
+        let text = "# Adds two numbers\n1, 5, 6, 0,  # add\n\n99,\n 30, -40 # operands\n";
+
+        assert_eq!(parse(text), Ok(vec![1, 5, 6, 0, 99, 30, -40]));
+    }
+
+    #[test]
+    fn invalid_token_is_positioned() {
+        let text = "1,0,0,0,\n99, 1x ,3";
+
+        assert_eq!(
+            parse(text),
+            Err(ParseError {
+                line: 2,
+                column: 5,
+                token: "1x".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn empty_value_is_positioned() {
+        let error = parse("1,,2").unwrap_err();
+
+        assert_eq!((error.line, error.column), (1, 3));
+        assert_eq!(error.to_string(), "Missing value at line 1, column 3");
+    }
+
+    #[test]
+    fn out_of_range_value_is_rejected() {
+        assert_eq!(parse("99999999999").unwrap_err().token, "99999999999");
+    }
+
+    #[test]
+    fn image_roundtrip() {
+        let memory = vec![0, 1, -1, 99, 1002, i32::MAX, i32::MIN, 63, -64, 64];
+        let mut image = Vec::new();
+
+        write_image(&memory, &mut image).unwrap();
+
+        assert!(image.starts_with(IMAGE_MAGIC));
+        assert_eq!(read_image(&image).unwrap(), memory);
+    }
+
+    #[test]
+    fn image_is_compact() {
+        let memory = vec![1; 1000];
+        let mut image = Vec::new();
+
+        write_image(&memory, &mut image).unwrap();
+
+        assert_eq!(image.len(), IMAGE_MAGIC.len() + 2 + 1000);
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        let mut image = Vec::new();
+        write_image(&[1, 2, 3], &mut image).unwrap();
+
+        assert!(read_image(&image[..image.len() - 1]).is_err());
+        assert!(read_image(&[image.as_slice(), &[0]].concat()).is_err());
+        assert!(read_image(b"1,2,3").is_err());
+        assert!(
+            read_image(&[&IMAGE_MAGIC[..], &[1, 0xff, 0xff, 0xff, 0xff, 0x7f]].concat()).is_err()
+        );
+    }
+
+    #[test]
+    fn load_detects_the_format() {
+        let directory = std::env::temp_dir();
+        let text_path = directory.join(format!("intcode-loader-{}.txt", std::process::id()));
+        let image_path = directory.join(format!("intcode-loader-{}.bin", std::process::id()));
+
+        fs::write(&text_path, "3,0,4,0,99\n").unwrap();
+        let mut image = fs::File::create(&image_path).unwrap();
+        write_image(&[3, 0, 4, 0, 99], &mut image).unwrap();
+        drop(image);
+
+        let text = load(&text_path);
+        let image = load(&image_path);
+        fs::remove_file(&text_path).unwrap();
+        fs::remove_file(&image_path).unwrap();
+
+        assert_eq!(text.unwrap(), [3, 0, 4, 0, 99]);
+        assert_eq!(image.unwrap(), [3, 0, 4, 0, 99]);
+    }
+
+    #[test]
+    fn load_missing_file() {
+        assert!(matches!(
+            load("/this/file/does/not/exist"),
+            Err(LoadError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,935 @@
+use intcode::assembler::{
+    self,
+    object::{self, Object},
+};
+use intcode::compiler;
+use intcode::coverage::Coverage;
+use intcode::disassembler;
+use intcode::fuzz::{self, Generator};
+use intcode::gdb::GdbStub;
+use intcode::io::{
+    adapter::IteratorInput,
+    ascii::{AsciiReader, AsciiWriter},
+    programmable::ProgrammableOutput,
+    recording::{Journal, Recorder, Replay, Session},
+    socket::Stream,
+    stdio::{StdinReader, StdoutWriter},
+    LineReader, LineWriter,
+};
+use intcode::minimiser::{self, Case};
+use intcode::source_map::SourceMap;
+use intcode::{ictest, linker, loader, server, validator, Operation, Program, State};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    net::{UnixListener, UnixStream},
+};
+use std::path::Path;
+use std::process;
+use std::thread;
+
+const USAGE: &str = "Usage:
+    intcode run <program> [options]
+        --input <values>        Comma separated inputs instead of reading stdin
+        --ascii                 Exchange text instead of one decimal value per line
+        --set <address>=<value> Patch a memory cell before running, may be repeated
+        --max-steps <count>     Stop after executing this many instructions
+        --trace                 Print every instruction to stderr before executing it
+        --profile               Print execution counts to stderr after running
+        --protect-code          Fault on writes to reachable code and on running data
+        --source-map <path>     Show labels and source lines in traces and profiles
+        --record <path>         Write every input and output to a session file as it happens
+        --replay <path>         Take the inputs from a session file and fail as soon as the run
+                                reads, writes or fails differently, or at another step
+    intcode serve <program> [--tcp <address> | --unix <path>]
+    intcode gdb <program> [--tcp <address>] [--source-map <path>]
+                                Serve the program to every client, or to one debugger, on a
+                                loopback address or a unix socket
+    intcode image <program> <destination>
+    intcode assemble <source> [<destination>] [--source-map <path>]
+                                Assemble a program and print it, or write it as text, as an
+                                image for destinations ending in .img or as an object for the
+                                linker for destinations ending in .icobj, and optionally write
+                                its source map
+    intcode link <object>... [--output <destination>] [--map <path>] [--source-map <path>]
+                                Link objects, or .asm files assembled as objects, into one
+                                program and optionally write where every symbol ended up
+    intcode compile <source> [<destination>] [--source-map <path>]
+                                Compile a program like `assemble`, or into assembly for
+                                destinations ending in .asm
+    intcode disassemble <program> [--source-map <path>]
+                                List the instructions and data of a program
+    intcode check <program>...  Report invalid instructions reachable from address 0
+    intcode minimise <program> [--input <values>] [--error <text>] [--max-steps <count>]
+                                Shrink a failing program and its inputs while the run still
+                                fails with an error containing the text, default any error
+    intcode fuzz [--seed <number>] [--iterations <count>]
+                                Run random programs through every implementation and report
+                                the first one on which they disagree
+    intcode test <path>...      Run the cases of every .ictest file in the files or directories
+    intcode coverage <program> [--source-map <path>] <values>...
+                                Run once per comma separated list of inputs and print which
+                                instructions and branches the runs covered
+
+Programs are read from text, from binary images written by `intcode image`, from assembly in
+files ending in .asm and from source files ending in .icl, which are compiled. Source maps are
+written by `intcode assemble`, `intcode compile` and `intcode link`; programs assembled or
+compiled from source use their own unless another one is given.
+
+Exit codes: 0 when the program halted, 1 when it failed or did not pass the check, 2 for
+invalid arguments and 3 when the step limit was reached.";
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_STEP_LIMIT: i32 = 3;
+
+struct Failure {
+    code: i32,
+    message: Cow<'static, str>,
+}
+
+impl Failure {
+    fn usage() -> Self {
+        Self {
+            code: EXIT_USAGE,
+            message: USAGE.into(),
+        }
+    }
+}
+
+impl From<Cow<'static, str>> for Failure {
+    fn from(message: Cow<'static, str>) -> Self {
+        Self {
+            code: EXIT_FAILURE,
+            message,
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Cow::from(message).into()
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Self {
+            code: EXIT_FAILURE,
+            message: error.to_string().into(),
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("run") => run(&args[1..]),
+        Some("serve") => serve(&args[1..]),
+        Some("gdb") => gdb(&args[1..]),
+        Some("image") => image(&args[1..]),
+        Some("assemble") => assemble(&args[1..]),
+        Some("link") => link(&args[1..]),
+        Some("compile") => compile(&args[1..]),
+        Some("disassemble") => disassemble(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("coverage") => coverage(&args[1..]),
+        Some("minimise") => minimise(&args[1..]),
+        Some("fuzz") => fuzz(&args[1..]),
+        Some("test") => test(&args[1..]),
+        _ => Err(Failure::usage()),
+    };
+
+    if let Err(failure) = result {
+        eprintln!("{}", failure.message);
+        process::exit(failure.code);
+    }
+}
+
+#[derive(Default)]
+struct RunOptions {
+    input: Option<String>,
+    ascii: bool,
+    patches: Vec<(usize, i32)>,
+    max_steps: Option<usize>,
+    trace: bool,
+    profile: bool,
+    protect_code: bool,
+    source_map: Option<String>,
+    record: Option<String>,
+    replay: Option<String>,
+}
+
+impl RunOptions {
+    fn parse(args: &[String]) -> Option<Self> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--input" => options.input = Some(args.next()?.clone()),
+                "--ascii" => options.ascii = true,
+                "--set" => {
+                    let (address, value) = args.next()?.split_once('=')?;
+                    options
+                        .patches
+                        .push((address.trim().parse().ok()?, value.trim().parse().ok()?));
+                }
+                "--max-steps" => options.max_steps = Some(args.next()?.parse().ok()?),
+                "--trace" => options.trace = true,
+                "--profile" => options.profile = true,
+                "--protect-code" => options.protect_code = true,
+                "--source-map" => options.source_map = Some(args.next()?.clone()),
+                "--record" => options.record = Some(args.next()?.clone()),
+                "--replay" => options.replay = Some(args.next()?.clone()),
+                _ => return None,
+            }
+        }
+
+        Some(options)
+    }
+
+    fn reader(&self) -> Result<Box<dyn LineReader>, Failure> {
+        Ok(match (&self.input, self.ascii) {
+            (Some(input), true) => Box::new(AsciiReader::new(io::Cursor::new(input.clone()))),
+            (Some(input), false) => Box::new(IteratorInput::new(parse_values(input)?)),
+            (None, true) => Box::new(AsciiReader::stdin()),
+            (None, false) => Box::new(StdinReader::new()),
+        })
+    }
+
+    fn writer(&self) -> Box<dyn LineWriter> {
+        if self.ascii {
+            Box::new(AsciiWriter::stdout())
+        } else {
+            Box::new(StdoutWriter::new())
+        }
+    }
+}
+
+/// Execution counts per instruction address and per kind of instruction.
+#[derive(Default)]
+struct Profile {
+    steps: usize,
+    addresses: HashMap<usize, (usize, String)>,
+    mnemonics: BTreeMap<&'static str, usize>,
+    source_map: Option<SourceMap>,
+}
+
+impl Profile {
+    fn record(&mut self, address: usize, operation: &Operation) {
+        self.steps += 1;
+        *self.mnemonics.entry(operation.mnemonic()).or_insert(0) += 1;
+        if !self.addresses.contains_key(&address) {
+            let text = self.describe(address, operation);
+            self.addresses.insert(address, (0, text));
+        }
+        self.addresses.get_mut(&address).unwrap().0 += 1;
+    }
+
+    /// Formats the operation at `address`, with its source line when there is a source map.
+    fn describe(&self, address: usize, operation: &Operation) -> String {
+        let source_map = match &self.source_map {
+            Some(source_map) => source_map,
+            None => return operation.to_string(),
+        };
+
+        let text = format!(
+            "{}: {}",
+            source_map.symbolise(address),
+            source_map.operation(operation)
+        );
+        match source_map.location(address) {
+            Some(location) => format!("{:<40}  # {}", text, location),
+            None => text,
+        }
+    }
+
+    fn print(&self) {
+        eprintln!("Executed {} instructions", self.steps);
+        for (mnemonic, count) in &self.mnemonics {
+            eprintln!("{:>8} {:>12}", mnemonic, count);
+        }
+
+        let mut hottest: Vec<_> = self.addresses.iter().collect();
+        hottest.sort_by_key(|(address, (count, _))| (std::cmp::Reverse(*count), **address));
+        eprintln!("Hottest instructions:");
+        for (address, (count, operation)) in hottest.into_iter().take(10) {
+            eprintln!("{:>8} {:>12}  {}", address, count, operation);
+        }
+    }
+}
+
+/// Splits the arguments of `run` into the program path and its options.
+fn run_arguments(args: &[String]) -> Result<(&String, RunOptions), Failure> {
+    let (path, options) = args.split_first().ok_or_else(Failure::usage)?;
+
+    Ok((path, RunOptions::parse(options).ok_or_else(Failure::usage)?))
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    let (path, options) = run_arguments(args)?;
+
+    let (mut memory, source_map) = load_program_with_source_map(path, &options.source_map)?;
+    for (address, value) in &options.patches {
+        match memory.get_mut(*address) {
+            Some(cell) => *cell = *value,
+            None => {
+                return Err(format!(
+                    "Cannot set address {}, the program is only {} long",
+                    address,
+                    memory.len()
+                )
+                .into())
+            }
+        }
+    }
+
+    // Sessions stamp every event with the number of instructions executed before it.
+    let clock = Cell::new(0);
+    let session = options.replay.as_ref().map(Session::load).transpose()?;
+    let replay = session.as_ref().map(|session| Replay::new(session, &clock));
+    let journal = match &options.record {
+        Some(path) => Some(RefCell::new(Journal::create(path)?)),
+        None => None,
+    };
+
+    let (mut input, mut output): (Box<dyn LineReader + '_>, Box<dyn LineWriter + '_>) =
+        match &replay {
+            Some(replay) => (Box::new(replay.replayer()), Box::new(replay.replayer())),
+            None => (options.reader()?, options.writer()),
+        };
+    if let Some(journal) = &journal {
+        input = Box::new(Recorder::new(input, &clock, journal));
+        output = Box::new(Recorder::new(output, &clock, journal));
+    }
+    let mut program = Program::new(memory, &mut input, &mut output);
+    let mut profile = Profile {
+        source_map,
+        ..Profile::default()
+    };
+    if options.protect_code {
+        for (range, protection) in validator::protections(program.memory()) {
+            program.protect(range, protection);
+        }
+    }
+
+    let result = loop {
+        let address = program.instruction_pointer();
+        clock.set(profile.steps);
+        if options.max_steps == Some(profile.steps) {
+            break Err(Failure {
+                code: EXIT_STEP_LIMIT,
+                message: format!(
+                    "Step limit of {} reached at address {}",
+                    profile.steps, address
+                )
+                .into(),
+            });
+        }
+
+        if options.trace || options.profile {
+            if let Ok(operation) = program.next_operation() {
+                if options.trace {
+                    eprintln!("{:>8}  {}", address, profile.describe(address, &operation));
+                }
+                profile.record(address, &operation);
+            }
+        } else {
+            profile.steps += 1;
+        }
+
+        match program.step() {
+            Ok(State::Running) => {}
+            Ok(State::Halted) => break Ok(()),
+            Err(error) => break Err(format!("Error at address {}: {}", address, error).into()),
+        }
+    };
+
+    if options.profile {
+        profile.print();
+    }
+    if let Some(replay) = &replay {
+        replay.check(result.is_ok())?;
+        if replay.failed_as_recorded() {
+            return Ok(());
+        }
+    }
+
+    result
+}
+
+fn serve(args: &[String]) -> Result<(), Failure> {
+    let (path, listen) = match args {
+        [path] => (path, None),
+        [path, flag, address] => (path, Some((flag.as_str(), address.as_str()))),
+        _ => return Err(Failure::usage()),
+    };
+    let memory = load_program(path)?;
+
+    match listen {
+        None | Some(("--tcp", _)) => {
+            let address = listen.map_or("127.0.0.1:0", |(_, address)| address);
+            let listener = bind_loopback(address)?;
+            eprintln!("Listening on {}", listener.local_addr()?);
+
+            for stream in listener.incoming() {
+                spawn_connection(stream?, memory.clone());
+            }
+        }
+        #[cfg(unix)]
+        Some(("--unix", path)) => {
+            let listener = bind_unix(path)?;
+            eprintln!("Listening on {}", path);
+
+            for stream in listener.incoming() {
+                spawn_connection(stream?, memory.clone());
+            }
+        }
+        Some(_) => return Err(Failure::usage()),
+    }
+
+    Ok(())
+}
+
+/// Waits for a single debugger to attach, while the program itself talks to stdin and stdout.
+fn gdb(args: &[String]) -> Result<(), Failure> {
+    let (path, options) = args.split_first().ok_or_else(Failure::usage)?;
+    let (mut address, mut source_map) = ("127.0.0.1:1234", None);
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            ("--tcp", Some(value)) => address = value.as_str(),
+            ("--source-map", Some(value)) => source_map = Some(value.clone()),
+            _ => return Err(Failure::usage()),
+        }
+    }
+    let (memory, source_map) = load_program_with_source_map(path, &source_map)?;
+
+    let listener = bind_loopback(address)?;
+    eprintln!("Waiting for gdb on {}", listener.local_addr()?);
+    let (stream, _) = listener.accept()?;
+
+    let mut input = StdinReader::new();
+    let mut output = StdoutWriter::new();
+    let mut program = Program::new(memory, &mut input, &mut output);
+    let mut stub = GdbStub::new(&mut program);
+    if let Some(source_map) = &source_map {
+        stub = stub.with_source_map(source_map);
+    }
+    stub.serve(stream)?;
+
+    Ok(())
+}
+
+/// Listens on `address` if it only accepts connections from this machine, since whoever
+/// connects gets to run the program or to control it.
+fn bind_loopback(address: &str) -> Result<TcpListener, Failure> {
+    let addresses: Vec<_> = address.to_socket_addrs()?.collect();
+    if addresses.is_empty() || !addresses.iter().all(|address| address.ip().is_loopback()) {
+        return Err(format!(
+            "Refusing to listen on {}, which is not a loopback address",
+            address
+        )
+        .into());
+    }
+
+    Ok(TcpListener::bind(&addresses[..])?)
+}
+
+/// Listens on a unix socket at `path`, replacing a socket that a server which is no longer
+/// running left behind.
+#[cfg(unix)]
+fn bind_unix(path: &str) -> Result<UnixListener, Failure> {
+    let is_socket =
+        fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+    if is_socket && UnixStream::connect(path).is_err() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(UnixListener::bind(path)?)
+}
+
+fn spawn_connection<S>(stream: S, memory: Vec<i32>)
+where
+    S: Stream + Send + 'static,
+{
+    thread::spawn(move || {
+        if let Err(error) = server::serve_connection(stream, memory) {
+            eprintln!("Connection failed: {}", error);
+        }
+    });
+}
+
+fn parse_values(values: &str) -> Result<Vec<i32>, Failure> {
+    values
+        .split(',')
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| value.trim().parse::<i32>().map_err(|_| Failure::usage()))
+        .collect()
+}
+
+fn load_program(path: &str) -> Result<Vec<i32>, Cow<'static, str>> {
+    if path.ends_with(".asm") {
+        return Ok(assembler::assemble_file(path)?.memory);
+    }
+    if is_source(path) {
+        return Ok(compiler::compile_file(path)?.memory);
+    }
+
+    loader::load(path).map_err(|error| format!("{}: {}", path, error).into())
+}
+
+/// Loads a program with the source map at `source_map`, or with the one the assembler made for
+/// it when it is an `.asm` file.
+fn load_program_with_source_map(
+    path: &str,
+    source_map: &Option<String>,
+) -> Result<(Vec<i32>, Option<SourceMap>), Cow<'static, str>> {
+    let assembly = if path.ends_with(".asm") {
+        Some(assembler::assemble_file(path)?)
+    } else if is_source(path) {
+        Some(compiler::compile_file(path)?)
+    } else {
+        None
+    };
+    let (memory, assembled) = if let Some(assembly) = assembly {
+        (assembly.memory, Some(assembly.source_map))
+    } else {
+        (load_program(path)?, None)
+    };
+
+    match source_map {
+        Some(source_map) => Ok((memory, Some(SourceMap::load(source_map)?))),
+        None => Ok((memory, assembled)),
+    }
+}
+
+/// Assembles a program into text, into an image when the destination ends in `.img` or into
+/// an object for the linker when it ends in `.icobj`.
+fn assemble(args: &[String]) -> Result<(), Failure> {
+    let (args, source_map) = match args {
+        [args @ .., flag, path] if flag == "--source-map" => (args, Some(path)),
+        _ => (args, None),
+    };
+    let (source, destination) = match args {
+        [source] => (source, None),
+        [source, destination] => (source, Some(destination)),
+        _ => return Err(Failure::usage()),
+    };
+
+    if let Some(destination) = destination.filter(|destination| is_object(destination)) {
+        // Objects carry their source lines, the linker writes the map of the whole program.
+        if source_map.is_some() {
+            return Err(Failure::usage());
+        }
+        let object = assembler::assemble_object_file(source).map_err(Cow::from)?;
+        fs::write(destination, object.to_string())?;
+        return Ok(());
+    }
+
+    let assembly = assembler::assemble_file(source).map_err(Cow::from)?;
+    if let Some(source_map) = source_map {
+        fs::write(source_map, assembly.source_map.to_string())?;
+    }
+    write_program(&assembly.memory, destination.map(String::as_str))
+}
+
+fn link(args: &[String]) -> Result<(), Failure> {
+    let (mut inputs, mut destination, mut map, mut source_map) = (Vec::new(), None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => destination = Some(args.next().ok_or_else(Failure::usage)?),
+            "--map" => map = Some(args.next().ok_or_else(Failure::usage)?),
+            "--source-map" => source_map = Some(args.next().ok_or_else(Failure::usage)?),
+            _ if arg.starts_with("--") => return Err(Failure::usage()),
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(Failure::usage());
+    }
+
+    let objects = inputs
+        .into_iter()
+        .map(|input| {
+            if input.ends_with(".asm") {
+                assembler::assemble_object_file(input).map_err(Cow::from)
+            } else {
+                Object::load(input)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let linked = linker::link(objects)?;
+
+    if let Some(map) = map {
+        fs::write(map, linked.map.to_string())?;
+    }
+    if let Some(source_map) = source_map {
+        fs::write(source_map, linked.source_map.to_string())?;
+    }
+    write_program(&linked.memory, destination.map(String::as_str))
+}
+
+fn disassemble(args: &[String]) -> Result<(), Failure> {
+    let (path, source_map) = match args {
+        [path] => (path, None),
+        [path, flag, source_map] if flag == "--source-map" => (path, Some(source_map.clone())),
+        _ => return Err(Failure::usage()),
+    };
+    let (memory, source_map) = load_program_with_source_map(path, &source_map)?;
+
+    print!(
+        "{}",
+        disassembler::disassemble(&memory, source_map.as_ref())
+    );
+
+    Ok(())
+}
+
+/// Compiles a program like `assemble`, or into assembly when the destination ends in `.asm`.
+fn compile(args: &[String]) -> Result<(), Failure> {
+    let (args, source_map) = match args {
+        [args @ .., flag, path] if flag == "--source-map" => (args, Some(path)),
+        _ => (args, None),
+    };
+    let (source, destination) = match args {
+        [source] => (source, None),
+        [source, destination] => (source, Some(destination)),
+        _ => return Err(Failure::usage()),
+    };
+
+    if let Some(destination) = destination.filter(|destination| destination.ends_with(".asm")) {
+        // The assembly is compiled again when it is assembled, with a source map of its own.
+        if source_map.is_some() {
+            return Err(Failure::usage());
+        }
+        let text = fs::read_to_string(source)
+            .map_err(|error| format!("Failed to read {}: {}", source, error))?;
+        fs::write(
+            destination,
+            compiler::compile_to_assembly(&text, source).map_err(Cow::from)?,
+        )?;
+        return Ok(());
+    }
+
+    let assembly = compiler::compile_file(source).map_err(Cow::from)?;
+    if let Some(source_map) = source_map {
+        fs::write(source_map, assembly.source_map.to_string())?;
+    }
+    write_program(&assembly.memory, destination.map(String::as_str))
+}
+
+fn is_source(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension == compiler::EXTENSION)
+}
+
+fn is_object(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension == object::EXTENSION)
+}
+
+/// Writes a program to stdout or a file as text, or as an image when the file ends in `.img`.
+fn write_program(memory: &[i32], destination: Option<&str>) -> Result<(), Failure> {
+    match destination {
+        Some(destination) if destination.ends_with(".img") => {
+            let mut file = fs::File::create(destination)?;
+            loader::write_image(memory, &mut file)?;
+        }
+        Some(destination) => fs::write(destination, format_program(memory))?,
+        None => print!("{}", format_program(memory)),
+    }
+
+    Ok(())
+}
+
+fn format_program(memory: &[i32]) -> String {
+    let values: Vec<_> = memory.iter().map(i32::to_string).collect();
+
+    format!("{}\n", values.join(","))
+}
+
+/// Converts a text program to the binary image format.
+fn image(args: &[String]) -> Result<(), Failure> {
+    let (path, destination) = match args {
+        [path, destination] => (path, destination),
+        _ => return Err(Failure::usage()),
+    };
+    let memory = load_program(path)?;
+
+    let mut file = fs::File::create(destination)?;
+    loader::write_image(&memory, &mut file)?;
+
+    Ok(())
+}
+
+/// Validates every program and fails if any of them has an issue.
+fn check(paths: &[String]) -> Result<(), Failure> {
+    if paths.is_empty() {
+        return Err(Failure::usage());
+    }
+
+    let mut invalid = 0;
+    for path in paths {
+        let report = validator::validate(&load_program(path)?);
+
+        for issue in &report.issues {
+            println!("{}: {}", path, issue);
+        }
+        for address in &report.rewritten {
+            eprintln!(
+                "{}: Address {}: not checked, the program writes to it",
+                path, address
+            );
+        }
+        if !report.is_valid() {
+            invalid += 1;
+        }
+    }
+
+    if invalid > 0 {
+        return Err(format!("{} of {} programs are invalid", invalid, paths.len()).into());
+    }
+
+    Ok(())
+}
+
+fn coverage(args: &[String]) -> Result<(), Failure> {
+    let (path, source_map, runs) = match args {
+        [path, flag, source_map, runs @ ..] if flag == "--source-map" => {
+            (path, Some(source_map.clone()), runs)
+        }
+        [path, runs @ ..] => (path, None, runs),
+        [] => return Err(Failure::usage()),
+    };
+    if runs.is_empty() {
+        return Err(Failure::usage());
+    }
+    let (memory, source_map) = load_program_with_source_map(path, &source_map)?;
+
+    let mut coverage = Coverage::new();
+    for run in runs {
+        let mut input = IteratorInput::new(parse_values(run)?);
+        let mut output = ProgrammableOutput::new();
+        let result = Program::new(memory.clone(), &mut input, &mut output)
+            .with_coverage(&mut coverage)
+            .run();
+
+        if let Err(error) = result {
+            eprintln!("Run with inputs {} failed: {}", run, error);
+        }
+    }
+
+    match &source_map {
+        Some(source_map) => print!("{}", coverage.source_listing(&memory, source_map)),
+        None => print!("{}", coverage.listing(&memory)),
+    }
+
+    Ok(())
+}
+
+fn minimise(args: &[String]) -> Result<(), Failure> {
+    let (path, mut options) = match args.split_first() {
+        Some((path, options)) => (path, options.iter()),
+        None => return Err(Failure::usage()),
+    };
+    let (mut inputs, mut error, mut max_steps) = (Vec::new(), String::new(), 100_000);
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or_else(Failure::usage)?;
+        match option.as_str() {
+            "--input" => inputs = parse_values(value)?,
+            "--error" => error = value.clone(),
+            "--max-steps" => max_steps = value.parse().map_err(|_| Failure::usage())?,
+            _ => return Err(Failure::usage()),
+        }
+    }
+
+    let reproduces = |case: &Case| {
+        let mut input = IteratorInput::new(case.inputs.clone());
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(case.memory.clone(), &mut input, &mut output);
+
+        for _ in 0..max_steps {
+            match program.step() {
+                Ok(State::Running) => {}
+                Ok(State::Halted) => return false,
+                Err(message) => return message.contains(error.as_str()),
+            }
+        }
+
+        false
+    };
+
+    let case = Case::new(load_program(path)?, inputs);
+    if !reproduces(&case) {
+        return Err(Cow::from("The program does not fail as described").into());
+    }
+
+    println!("{}", minimiser::minimise(case, reproduces));
+
+    Ok(())
+}
+
+fn fuzz(args: &[String]) -> Result<(), Failure> {
+    let (mut seed, mut iterations) = (1, 1000);
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or_else(Failure::usage)?;
+        match option.as_str() {
+            "--seed" => seed = value.parse().map_err(|_| Failure::usage())?,
+            "--iterations" => iterations = value.parse().map_err(|_| Failure::usage())?,
+            _ => return Err(Failure::usage()),
+        }
+    }
+
+    fuzz::fuzz(&Generator::new(), seed, iterations)
+        .map_err(|disagreement| disagreement.to_string())?;
+    println!("{} programs agreed", iterations);
+
+    Ok(())
+}
+
+fn test(paths: &[String]) -> Result<(), Failure> {
+    if paths.is_empty() {
+        return Err(Failure::usage());
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    for path in paths {
+        for file in ictest::discover(path)? {
+            let results = match ictest::run_file(&file) {
+                Ok(results) => results,
+                Err(error) => {
+                    println!("FAILED {}: {}", file.display(), error);
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            for (case, result) in results {
+                match result {
+                    Ok(()) => {
+                        println!("ok     {}:{}: {}", file.display(), case.line, case.name);
+                        passed += 1;
+                    }
+                    Err(error) => {
+                        println!(
+                            "FAILED {}:{}: {}: {}",
+                            file.display(),
+                            case.line,
+                            case.name,
+                            error
+                        );
+                        failed += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        return Err(format!("{} test cases failed", failed).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Runs a program that reads nothing and writes nothing, so that stdio stays untouched.
+    fn run_program(name: &str, program: &str, options: &[&str]) -> Result<(), i32> {
+        let path = std::env::temp_dir().join(format!("intcode-{}-{}", name, process::id()));
+        fs::write(&path, program).unwrap();
+
+        let mut arguments = args(options);
+        arguments.insert(0, path.display().to_string());
+        let result = run(&arguments).map_err(|failure| failure.code);
+        fs::remove_file(&path).unwrap();
+
+        result
+    }
+
+    #[test]
+    fn set_patches_cells() {
+        let arguments = args(&["a.txt", "--set", "1=12", "--set", " 2 = -3"]);
+        let (path, options) = run_arguments(&arguments).ok().unwrap();
+
+        assert_eq!(path, "a.txt");
+        assert_eq!(options.patches, [(1, 12), (2, -3)]);
+        for invalid in &[
+            &["--set"][..],
+            &["--set", "1"],
+            &["--set", "x=1"],
+            &["--set", "1=y"],
+        ] {
+            assert!(RunOptions::parse(&args(invalid)).is_none());
+        }
+    }
+
+    #[test]
+    fn input_is_comma_separated() {
+        let options = RunOptions::parse(&args(&["--input", " 1, -5,"])).unwrap();
+        let mut reader = options.reader().ok().unwrap();
+
+        assert_eq!(reader.read_line(), Ok(1));
+        assert_eq!(reader.read_line(), Ok(-5));
+        assert!(reader.read_line().is_err());
+
+        let options = RunOptions::parse(&args(&["--input", "1,x"])).unwrap();
+        assert_eq!(
+            options.reader().err().map(|failure| failure.code),
+            Some(EXIT_USAGE)
+        );
+        assert!(RunOptions::parse(&args(&["--input"])).is_none());
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(run_program("halt", "1101,1,1,5,99,0", &[]), Ok(()));
+        assert_eq!(run_program("fail", "1,100,0,0,99", &[]), Err(EXIT_FAILURE));
+        assert_eq!(
+            run_program("set", "99", &["--set", "1=0"]),
+            Err(EXIT_FAILURE)
+        );
+        assert_eq!(run_program("usage", "99", &["--bogus"]), Err(EXIT_USAGE));
+        assert_eq!(run(&[]).err().map(|failure| failure.code), Some(EXIT_USAGE));
+        assert_eq!(
+            run_program("loop", "1105,1,0", &["--max-steps", "10"]),
+            Err(EXIT_STEP_LIMIT)
+        );
+    }
+
+    #[test]
+    fn max_steps_allows_exactly_that_many_instructions() {
+        // Adds, then halts, so it runs 2 instructions.
+        let program = "1101,1,1,5,99,0";
+
+        assert_eq!(run_program("exact", program, &["--max-steps", "2"]), Ok(()));
+        assert_eq!(
+            run_program("short", program, &["--max-steps", "1"]),
+            Err(EXIT_STEP_LIMIT)
+        );
+        assert_eq!(
+            run_program("none", program, &["--max-steps", "0"]),
+            Err(EXIT_STEP_LIMIT)
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Shrinks a program and its inputs while they still reproduce a problem.
+//!
+//! The minimiser is a simple delta debugger: it shortens the inputs, deletes whole
+//! instructions, deletes runs of words, drops everything in front of the interesting part and
+//! replaces single words with 99, keeping every change
+//! after which the predicate still holds, until none of them make progress. Deleting words
+//! shifts everything after them, so a smaller case often fails in a different place than the
+//! original; the predicate should be as specific as the problem allows.
+
+use super::{validator, Operation};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub memory: Vec<i32>,
+    pub inputs: Vec<i32>,
+}
+
+impl Case {
+    pub fn new(memory: Vec<i32>, inputs: Vec<i32>) -> Self {
+        Self { memory, inputs }
+    }
+}
+
+/// Formats the case as Rust code for a unit test.
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "let memory = vec!{:?};", self.memory)?;
+        write!(f, "let inputs = vec!{:?};", self.inputs)
+    }
+}
+
+/// Returns the smallest case found for which `predicate` still holds, or `case` itself when
+/// the predicate does not hold for it in the first place.
+pub fn minimise<P>(mut case: Case, mut predicate: P) -> Case
+where
+    P: FnMut(&Case) -> bool,
+{
+    if !predicate(&case) {
+        return case;
+    }
+
+    loop {
+        let before = case.clone();
+
+        remove_chunks(&mut case, |case| &mut case.inputs, &mut predicate);
+        delete_instructions(&mut case, &mut predicate);
+        remove_chunks(&mut case, |case| &mut case.memory, &mut predicate);
+        remove_prefix(&mut case, &mut predicate);
+        replace_with_halt(&mut case, &mut predicate);
+
+        if case == before {
+            return case;
+        }
+    }
+}
+
+/// Removes runs of values, starting with halves and ending with single values.
+fn remove_chunks<F, P>(case: &mut Case, values: F, predicate: &mut P)
+where
+    F: Fn(&mut Case) -> &mut Vec<i32>,
+    P: FnMut(&Case) -> bool,
+{
+    let mut chunk = values(case).len() / 2;
+
+    while chunk > 0 {
+        let mut start = 0;
+
+        while start < values(case).len() {
+            let mut candidate = case.clone();
+            let candidate_values = values(&mut candidate);
+            let end = (start + chunk).min(candidate_values.len());
+            candidate_values.drain(start..end);
+
+            if predicate(&candidate) {
+                *case = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+
+        chunk /= 2;
+    }
+}
+
+/// Removes the longest prefix of memory that can go, which deleting chunks rarely finds
+/// because every shorter prefix shifts the code after it out of place.
+fn remove_prefix<P>(case: &mut Case, predicate: &mut P)
+where
+    P: FnMut(&Case) -> bool,
+{
+    for len in (1..case.memory.len()).rev() {
+        let mut candidate = case.clone();
+        candidate.memory.drain(..len);
+
+        if predicate(&candidate) {
+            *case = candidate;
+            return;
+        }
+    }
+}
+
+/// Removes the instructions reachable from address 0, last ones first.
+fn delete_instructions<P>(case: &mut Case, predicate: &mut P)
+where
+    P: FnMut(&Case) -> bool,
+{
+    let instructions = validator::validate(&case.memory).instructions;
+
+    for &address in instructions.iter().rev() {
+        let len = match Operation::from_slice(&case.memory[address..]) {
+            Ok(operation) => operation.op_len(),
+            Err(_) => continue,
+        };
+
+        let mut candidate = case.clone();
+        candidate.memory.drain(address..address + len);
+        if predicate(&candidate) {
+            *case = candidate;
+        }
+    }
+}
+
+fn replace_with_halt<P>(case: &mut Case, predicate: &mut P)
+where
+    P: FnMut(&Case) -> bool,
+{
+    for idx in 0..case.memory.len() {
+        if case.memory[idx] == 99 {
+            continue;
+        }
+
+        let mut candidate = case.clone();
+        candidate.memory[idx] = 99;
+        if predicate(&candidate) {
+            *case = candidate;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{adapter::IteratorInput, programmable::ProgrammableOutput};
+    use crate::{Program, State};
+    use std::borrow::Cow;
+
+    /// Runs a case for at most 1000 steps, returning its outputs.
+    fn run(case: &Case) -> Result<Vec<i32>, Cow<'static, str>> {
+        let mut input = IteratorInput::new(case.inputs.clone());
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(case.memory.clone(), &mut input, &mut output);
+
+        for _ in 0..1000 {
+            if program.step()? == State::Halted {
+                return Ok(output.output());
+            }
+        }
+
+        Err("Step limit reached".into())
+    }
+
+    #[test]
+    fn inputs_are_shortened() {
+        let case = Case::new(vec![99], vec![1, 7, 3, 4, 7, 9]);
+
+        let minimal = minimise(case, |case| case.inputs.contains(&7));
+
+        assert_eq!(minimal, Case::new(vec![], vec![7]));
+    }
+
+    #[test]
+    fn unsupported_opcode_is_isolated() {
+        // Reads two values, adds them, prints the sum, then runs into opcode 42.
+        let memory = vec![
+            3, 18, 3, 19, 1, 18, 19, 20, 4, 20, 1002, 20, 2, 20, 4, 20, 42, 99, 0, 0, 0,
+        ];
+        let case = Case::new(memory, vec![3, 4, 5]);
+        let predicate = |case: &Case| match run(case) {
+            Err(error) => error.contains("'42'"),
+            Ok(_) => false,
+        };
+
+        let minimal = minimise(case, predicate);
+
+        assert_eq!(minimal, Case::new(vec![42], vec![]));
+    }
+
+    #[test]
+    fn outputs_are_kept() {
+        // Multiplies its input by 3 and prints it twice.
+        let memory = vec![3, 11, 1002, 11, 3, 11, 4, 11, 4, 11, 99, 0];
+        let case = Case::new(memory, vec![5]);
+        let predicate = |case: &Case| run(case).is_ok_and(|outputs| outputs.contains(&15));
+
+        let minimal = minimise(case.clone(), predicate);
+
+        assert!(predicate(&minimal));
+        assert_ne!(minimal, case);
+    }
+
+    #[test]
+    fn cases_that_do_not_reproduce_are_returned_unchanged() {
+        let case = Case::new(vec![1, 0, 0, 0, 99], vec![1]);
+
+        assert_eq!(minimise(case.clone(), |_| false), case);
+    }
+
+    #[test]
+    fn display_as_test_code() {
+        let case = Case::new(vec![3, 0, 99], vec![-1]);
+
+        assert_eq!(
+            case.to_string(),
+            "let memory = vec![3, 0, 99];\nlet inputs = vec![-1];"
+        );
+    }
+}
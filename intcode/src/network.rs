@@ -0,0 +1,367 @@
+use super::{
+    io::{LineReader, LineWriter},
+    Operation, Program, State,
+};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// How many instructions a node may execute per turn without reading input.
+const QUANTUM: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    pub source: Option<usize>,
+    pub destination: i32,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+/// Hooks for whoever watches the network from the outside.
+pub trait Supervisor {
+    /// Receives every packet sent to an address that has no node.
+    fn receive(&mut self, packet: Packet) -> Control;
+
+    /// Called whenever the network is idle. The returned packet is routed like any other,
+    /// and returning `None` stops the network.
+    fn idle(&mut self) -> Option<Packet>;
+}
+
+/// Hosts one Intcode program per address and routes the `(destination, x, y)` packets they
+/// write to each other.
+///
+/// Every node reads its own address first. Nodes take turns in address order and a turn ends
+/// after one read, so the same programs always exchange the same packets in the same order.
+pub struct Network {
+    memories: Vec<Vec<i32>>,
+    no_data: i32,
+    idle_rounds: usize,
+}
+
+#[derive(Default)]
+struct Mailbox {
+    inbox: VecDeque<i32>,
+    outbox: Vec<i32>,
+    empty_reads: usize,
+}
+
+struct Inbox {
+    mailbox: Rc<RefCell<Mailbox>>,
+    no_data: i32,
+}
+
+impl LineReader for Inbox {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        let mut mailbox = self.mailbox.borrow_mut();
+
+        match mailbox.inbox.pop_front() {
+            Some(value) => {
+                mailbox.empty_reads = 0;
+                Ok(value)
+            }
+            None => {
+                mailbox.empty_reads += 1;
+                Ok(self.no_data)
+            }
+        }
+    }
+}
+
+struct Outbox {
+    mailbox: Rc<RefCell<Mailbox>>,
+}
+
+impl LineWriter for Outbox {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        self.mailbox.borrow_mut().outbox.push(value);
+
+        Ok(())
+    }
+}
+
+impl Network {
+    pub fn new(memories: Vec<Vec<i32>>, no_data: i32) -> Self {
+        Self {
+            memories,
+            no_data,
+            idle_rounds: 2,
+        }
+    }
+
+    /// Sets how many rounds in a row every node has to read no data without any packet being
+    /// sent before the network counts as idle.
+    pub fn with_idle_rounds(mut self, idle_rounds: usize) -> Self {
+        self.idle_rounds = idle_rounds.max(1);
+        self
+    }
+
+    /// Runs the nodes until the supervisor stops the network or every node halted.
+    pub fn run<S>(&self, supervisor: &mut S) -> Result<(), Cow<'static, str>>
+    where
+        S: Supervisor,
+    {
+        let mailboxes: Vec<_> = (0..self.memories.len())
+            .map(|address| {
+                let mut mailbox = Mailbox::default();
+                mailbox.inbox.push_back(address as i32);
+                Rc::new(RefCell::new(mailbox))
+            })
+            .collect();
+        let mut inboxes: Vec<_> = mailboxes
+            .iter()
+            .map(|mailbox| Inbox {
+                mailbox: mailbox.clone(),
+                no_data: self.no_data,
+            })
+            .collect();
+        let mut outboxes: Vec<_> = mailboxes
+            .iter()
+            .map(|mailbox| Outbox {
+                mailbox: mailbox.clone(),
+            })
+            .collect();
+        let mut programs: Vec<_> = self
+            .memories
+            .iter()
+            .zip(inboxes.iter_mut().zip(outboxes.iter_mut()))
+            .map(|(memory, (inbox, outbox))| Program::new(memory.clone(), inbox, outbox))
+            .collect();
+
+        let mut halted = vec![false; programs.len()];
+        let mut idle_rounds = 0;
+
+        while halted.iter().any(|halted| !halted) {
+            let mut quiet = true;
+
+            for (address, program) in programs.iter_mut().enumerate() {
+                if halted[address] {
+                    continue;
+                }
+
+                let failed = |error| format!("Node {} failed: {}", address, error);
+                let mut waiting = false;
+                for _ in 0..QUANTUM {
+                    let operation = program.next_operation().map_err(failed)?;
+                    let reads = matches!(operation, Operation::Input { .. });
+                    let state = program.step().map_err(failed)?;
+
+                    let packet = {
+                        let mut mailbox = mailboxes[address].borrow_mut();
+                        if mailbox.outbox.len() == 3 {
+                            let values: Vec<_> = mailbox.outbox.drain(..).collect();
+                            Some(Packet {
+                                source: Some(address),
+                                destination: values[0],
+                                x: values[1],
+                                y: values[2],
+                            })
+                        } else {
+                            None
+                        }
+                    };
+                    if let Some(packet) = packet {
+                        quiet = false;
+                        if route(&mailboxes, packet, supervisor) == Control::Stop {
+                            return Ok(());
+                        }
+                    }
+
+                    if state == State::Halted {
+                        halted[address] = true;
+                        break;
+                    }
+                    if reads {
+                        waiting = mailboxes[address].borrow().empty_reads > 0;
+                        break;
+                    }
+                }
+
+                if !waiting {
+                    quiet = false;
+                }
+            }
+
+            idle_rounds = if quiet { idle_rounds + 1 } else { 0 };
+
+            if idle_rounds >= self.idle_rounds {
+                idle_rounds = 0;
+
+                match supervisor.idle() {
+                    Some(packet) => {
+                        if route(&mailboxes, packet, supervisor) == Control::Stop {
+                            return Ok(());
+                        }
+                    }
+                    None => return Ok(()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn route<S>(mailboxes: &[Rc<RefCell<Mailbox>>], packet: Packet, supervisor: &mut S) -> Control
+where
+    S: Supervisor,
+{
+    let mailbox = usize::try_from(packet.destination)
+        .ok()
+        .and_then(|destination| mailboxes.get(destination));
+
+    match mailbox {
+        Some(mailbox) => {
+            let mut mailbox = mailbox.borrow_mut();
+            mailbox.inbox.push_back(packet.x);
+            mailbox.inbox.push_back(packet.y);
+            Control::Continue
+        }
+        None => supervisor.receive(packet),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Node 0 sends `(1, 0, 0)`, every node forwards the packets it receives to the next
+    /// address with `y` incremented, and every node polls its input forever.
+    fn relay() -> Vec<i32> {
+        vec![
+            3, 46, 1008, 46, 0, 50, 1005, 50, 37, 3, 47, 1008, 47, -1, 50, 1005, 50, 9, 3, 48,
+            1001, 46, 1, 49, 1001, 48, 1, 48, 4, 49, 4, 47, 4, 48, 1105, 1, 9, 104, 1, 104, 0, 104,
+            0, 1105, 1, 9, 0, 0, 0, 0, 0,
+        ]
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        received: Vec<Packet>,
+        injections: Vec<Packet>,
+        idle_calls: usize,
+        stop_after: Option<usize>,
+    }
+
+    impl Supervisor for Recorder {
+        fn receive(&mut self, packet: Packet) -> Control {
+            self.received.push(packet);
+            if Some(self.received.len()) == self.stop_after {
+                Control::Stop
+            } else {
+                Control::Continue
+            }
+        }
+
+        fn idle(&mut self) -> Option<Packet> {
+            self.idle_calls += 1;
+            self.injections.pop()
+        }
+    }
+
+    #[test]
+    fn packets_are_routed_through_the_network() {
+        let network = Network::new(vec![relay(); 5], -1);
+        let mut recorder = Recorder {
+            stop_after: Some(1),
+            ..Recorder::default()
+        };
+
+        network.run(&mut recorder).unwrap();
+
+        assert_eq!(
+            recorder.received,
+            [Packet {
+                source: Some(4),
+                destination: 5,
+                x: 0,
+                y: 4,
+            }]
+        );
+        assert_eq!(recorder.idle_calls, 0);
+    }
+
+    #[test]
+    fn supervisor_is_called_when_idle() {
+        let network = Network::new(vec![relay(); 3], -1);
+        let mut recorder = Recorder {
+            injections: vec![Packet {
+                source: None,
+                destination: 0,
+                x: 7,
+                y: 100,
+            }],
+            ..Recorder::default()
+        };
+
+        network.run(&mut recorder).unwrap();
+
+        assert_eq!(recorder.idle_calls, 2);
+        assert_eq!(recorder.received.len(), 2);
+        assert_eq!(recorder.received[1].x, 7);
+        assert_eq!(recorder.received[1].y, 103);
+    }
+
+    #[test]
+    fn runs_are_reproducible() {
+        let run = || {
+            let network = Network::new(vec![relay(); 10], -1).with_idle_rounds(1);
+            let mut recorder = Recorder {
+                injections: (0..10)
+                    .map(|idx| Packet {
+                        source: None,
+                        destination: idx,
+                        x: idx,
+                        y: idx * 10,
+                    })
+                    .collect(),
+                ..Recorder::default()
+            };
+            network.run(&mut recorder).unwrap();
+
+            recorder.received
+        };
+
+        let first = run();
+
+        assert_eq!(first.len(), 11);
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn no_data_value_is_configurable() {
+        // Sends whatever it reads after its address to the supervisor.
+        let program = vec![3, 11, 3, 11, 104, 2, 104, 0, 4, 11, 99, 0];
+        let network = Network::new(vec![program], 42);
+        let mut recorder = Recorder::default();
+
+        network.run(&mut recorder).unwrap();
+
+        assert_eq!(recorder.received[0].y, 42);
+    }
+
+    #[test]
+    fn halted_network_stops() {
+        let network = Network::new(vec![vec![104, 9, 104, 1, 104, 2, 99]; 2], -1);
+        let mut recorder = Recorder::default();
+
+        network.run(&mut recorder).unwrap();
+
+        assert_eq!(recorder.received.len(), 2);
+        assert_eq!(recorder.idle_calls, 0);
+    }
+
+    #[test]
+    fn failing_node_is_reported() {
+        let network = Network::new(vec![vec![99], vec![42]], -1);
+
+        assert!(network.run(&mut Recorder::default()).is_err());
+    }
+}
@@ -18,6 +18,7 @@ pub struct OpCodeMode {
 pub enum ParameterMode {
     Position,
     Immediate,
+    Relative,
 }
 
 impl TryFrom<i32> for OpCode {
@@ -55,6 +56,7 @@ impl TryFrom<i32> for ParameterMode {
         match value {
             0 => Ok(ParameterMode::Position),
             1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
             unsupported_mode => {
                 Err(format!("Unsupported parameter mode: {}", unsupported_mode).into())
             }
@@ -159,6 +161,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extract_parameter_mode_21209() {
+        let op_with_mode = 21209;
+
+        assert_eq!(
+            extract_parameter_mode(op_with_mode, 0),
+            Ok(ParameterMode::Relative)
+        );
+        assert_eq!(
+            extract_parameter_mode(op_with_mode, 1),
+            Ok(ParameterMode::Immediate)
+        );
+        assert_eq!(
+            extract_parameter_mode(op_with_mode, 2),
+            Ok(ParameterMode::Relative)
+        );
+        assert!(extract_parameter_mode(309, 0).is_err());
+    }
+
     #[test]
     fn extract_parameter_mode_102() {
         let op_with_mode = 102;
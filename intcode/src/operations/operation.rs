@@ -0,0 +1,571 @@
+use super::{OpCode, Parameter, ParameterMode, ToParameter};
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::fmt;
+
+/// A decoded instruction. Destinations are `Parameter::Address` or `Parameter::Relative`,
+/// never a value.
+#[derive(Debug, PartialEq)]
+pub enum Operation {
+    Add {
+        addend_1: Parameter,
+        addend_2: Parameter,
+        destination: Parameter,
+    },
+    Exit,
+    Input {
+        destination: Parameter,
+    },
+    Multiply {
+        factor_1: Parameter,
+        factor_2: Parameter,
+        destination: Parameter,
+    },
+    Output {
+        source: Parameter,
+    },
+    JumpIfTrue {
+        condition: Parameter,
+        location: Parameter,
+    },
+    JumpIfFalse {
+        condition: Parameter,
+        location: Parameter,
+    },
+    LessThan {
+        value_1: Parameter,
+        value_2: Parameter,
+        destination: Parameter,
+    },
+    Equals {
+        value_1: Parameter,
+        value_2: Parameter,
+        destination: Parameter,
+    },
+    AdjustRelativeBase {
+        adjustment: Parameter,
+    },
+}
+
+impl Operation {
+    pub fn op_len(&self) -> usize {
+        match self {
+            Operation::Add { .. }
+            | Operation::Multiply { .. }
+            | Operation::LessThan { .. }
+            | Operation::Equals { .. } => 4,
+            Operation::JumpIfTrue { .. } | Operation::JumpIfFalse { .. } => 3,
+            Operation::Input { .. }
+            | Operation::Output { .. }
+            | Operation::AdjustRelativeBase { .. } => 2,
+            Operation::Exit => 1,
+        }
+    }
+
+    /// A short lowercase name for the instruction, as used in traces and listings.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Operation::Add { .. } => "add",
+            Operation::Multiply { .. } => "mul",
+            Operation::Input { .. } => "in",
+            Operation::Output { .. } => "out",
+            Operation::JumpIfTrue { .. } => "jnz",
+            Operation::JumpIfFalse { .. } => "jz",
+            Operation::LessThan { .. } => "lt",
+            Operation::Equals { .. } => "eq",
+            Operation::AdjustRelativeBase { .. } => "arb",
+            Operation::Exit => "halt",
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<&Parameter> {
+        match self {
+            Operation::Add {
+                addend_1, addend_2, ..
+            } => vec![addend_1, addend_2],
+            Operation::Multiply {
+                factor_1, factor_2, ..
+            } => vec![factor_1, factor_2],
+            Operation::LessThan {
+                value_1, value_2, ..
+            }
+            | Operation::Equals {
+                value_1, value_2, ..
+            } => vec![value_1, value_2],
+            Operation::Output { source } => vec![source],
+            Operation::JumpIfTrue {
+                condition,
+                location,
+            }
+            | Operation::JumpIfFalse {
+                condition,
+                location,
+            } => vec![condition, location],
+            Operation::AdjustRelativeBase { adjustment } => vec![adjustment],
+            Operation::Input { .. } | Operation::Exit => vec![],
+        }
+    }
+
+    pub fn destination(&self) -> Option<&Parameter> {
+        match self {
+            Operation::Add { destination, .. }
+            | Operation::Multiply { destination, .. }
+            | Operation::LessThan { destination, .. }
+            | Operation::Equals { destination, .. }
+            | Operation::Input { destination } => Some(destination),
+            Operation::Output { .. }
+            | Operation::JumpIfTrue { .. }
+            | Operation::JumpIfFalse { .. }
+            | Operation::AdjustRelativeBase { .. }
+            | Operation::Exit => None,
+        }
+    }
+
+    /// The address the instruction writes to, when it does not depend on the relative base.
+    pub fn destination_address(&self) -> Option<usize> {
+        match self.destination() {
+            Some(Parameter::Address(address)) => Some(*address),
+            _ => None,
+        }
+    }
+
+    /// Whether any parameter or the destination is relative to the relative base.
+    pub fn is_relative(&self) -> bool {
+        self.parameters()
+            .into_iter()
+            .chain(self.destination())
+            .any(|parameter| matches!(parameter, Parameter::Relative(_)))
+    }
+
+    pub fn from_slice(current: &[i32]) -> Result<Operation, Cow<'static, str>> {
+        match Self::split_opcode(current)? {
+            (
+                OpCode {
+                    operation: 1,
+                    ref mode,
+                },
+                [addend_1, addend_2, destination, ..],
+            ) => Self::has_destination(destination, &mode.parameter_3, |destination| {
+                Operation::Add {
+                    addend_1: addend_1.to_parameter(&mode.parameter_1),
+                    addend_2: addend_2.to_parameter(&mode.parameter_2),
+                    destination,
+                }
+            }),
+            (
+                OpCode {
+                    operation: 2,
+                    ref mode,
+                },
+                [factor_1, factor_2, destination, ..],
+            ) => Self::has_destination(destination, &mode.parameter_3, |destination| {
+                Operation::Multiply {
+                    factor_1: factor_1.to_parameter(&mode.parameter_1),
+                    factor_2: factor_2.to_parameter(&mode.parameter_2),
+                    destination,
+                }
+            }),
+            (
+                OpCode {
+                    operation: 3,
+                    ref mode,
+                },
+                [destination, ..],
+            ) => Self::has_destination(destination, &mode.parameter_1, |destination| {
+                Operation::Input { destination }
+            }),
+            (
+                OpCode {
+                    operation: 4,
+                    ref mode,
+                },
+                [source, ..],
+            ) => Ok(Operation::Output {
+                source: source.to_parameter(&mode.parameter_1),
+            }),
+            (
+                OpCode {
+                    operation: 5,
+                    ref mode,
+                },
+                [condition, location, ..],
+            ) => Ok(Operation::JumpIfTrue {
+                condition: condition.to_parameter(&mode.parameter_1),
+                location: location.to_parameter(&mode.parameter_2),
+            }),
+            (
+                OpCode {
+                    operation: 6,
+                    ref mode,
+                },
+                [condition, location, ..],
+            ) => Ok(Operation::JumpIfFalse {
+                condition: condition.to_parameter(&mode.parameter_1),
+                location: location.to_parameter(&mode.parameter_2),
+            }),
+            (
+                OpCode {
+                    operation: 7,
+                    ref mode,
+                },
+                [value_1, value_2, destination, ..],
+            ) => Self::has_destination(destination, &mode.parameter_3, |destination| {
+                Operation::LessThan {
+                    value_1: value_1.to_parameter(&mode.parameter_1),
+                    value_2: value_2.to_parameter(&mode.parameter_2),
+                    destination,
+                }
+            }),
+            (
+                OpCode {
+                    operation: 8,
+                    ref mode,
+                },
+                [value_1, value_2, destination, ..],
+            ) => Self::has_destination(destination, &mode.parameter_3, |destination| {
+                Operation::Equals {
+                    value_1: value_1.to_parameter(&mode.parameter_1),
+                    value_2: value_2.to_parameter(&mode.parameter_2),
+                    destination,
+                }
+            }),
+            (
+                OpCode {
+                    operation: 9,
+                    ref mode,
+                },
+                [adjustment, ..],
+            ) => Ok(Operation::AdjustRelativeBase {
+                adjustment: adjustment.to_parameter(&mode.parameter_1),
+            }),
+            (OpCode { operation: 99, .. }, [..]) => Ok(Operation::Exit),
+            (unsupported_opcode, _) => Err(format!(
+                "Operation '{}' is not yet supported!",
+                unsupported_opcode.operation
+            )
+            .into()),
+        }
+    }
+
+    fn has_destination<F>(
+        destination: &i32,
+        mode: &ParameterMode,
+        creator: F,
+    ) -> Result<Operation, Cow<'static, str>>
+    where
+        F: FnOnce(Parameter) -> Operation,
+    {
+        if *mode == ParameterMode::Immediate {
+            Err("Immediate is an invalid mode for as a destination".into())
+        } else {
+            Ok(creator(destination.to_parameter(mode)))
+        }
+    }
+
+    fn split_opcode(slice: &[i32]) -> Result<(OpCode, &[i32]), Cow<'static, str>> {
+        if !slice.is_empty() {
+            Ok((slice[0].try_into()?, &slice[1..]))
+        } else {
+            Err("Invalid instruction".into())
+        }
+    }
+
+    pub fn encode(&self) -> Vec<i32> {
+        let opcode = match self {
+            Operation::Add { .. } => 1,
+            Operation::Multiply { .. } => 2,
+            Operation::Input { .. } => 3,
+            Operation::Output { .. } => 4,
+            Operation::JumpIfTrue { .. } => 5,
+            Operation::JumpIfFalse { .. } => 6,
+            Operation::LessThan { .. } => 7,
+            Operation::Equals { .. } => 8,
+            Operation::AdjustRelativeBase { .. } => 9,
+            Operation::Exit => 99,
+        };
+        let mut words = vec![opcode];
+
+        for (idx, parameter) in self
+            .parameters()
+            .into_iter()
+            .chain(self.destination())
+            .enumerate()
+        {
+            let (mode, word) = match parameter {
+                Parameter::Address(address) => (0, *address as i32),
+                Parameter::Value(value) => (1, *value),
+                Parameter::Relative(offset) => (2, *offset),
+            };
+            words[0] += mode * 100 * i32::pow(10, idx as u32);
+            words.push(word);
+        }
+
+        words
+    }
+}
+
+/// Formats the instruction as its mnemonic followed by its operands, where `[n]` is the cell
+/// at address `n`, `[rb + n]` the cell `n` past the relative base and a bare number is an
+/// immediate value.
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+
+        let mut separator = " ";
+        for parameter in self.parameters().into_iter().chain(self.destination()) {
+            write!(f, "{}{}", separator, parameter)?;
+            separator = ", ";
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_add_exact() {
+        let opcodes = [1, 2, 3, 4];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Add {
+                addend_1: Parameter::Address(2),
+                addend_2: Parameter::Address(3),
+                destination: Parameter::Address(4),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_add_invalid_destination() {
+        let opcodes = [11101, 2, 3, 4];
+        let op = Operation::from_slice(&opcodes);
+
+        assert!(op.is_err());
+    }
+
+    #[test]
+    fn parse_add_trailing() {
+        let opcodes = [1, 2, 3, 4, 5, 6];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Add {
+                addend_1: Parameter::Address(2),
+                addend_2: Parameter::Address(3),
+                destination: Parameter::Address(4),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_add_too_short() {
+        let opcodes = [1, 2, 3];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(op.is_err(), true);
+    }
+
+    #[test]
+    fn parse_multiply_exact() {
+        let opcodes = [2, 3, 4, 5];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Multiply {
+                factor_1: Parameter::Address(3),
+                factor_2: Parameter::Address(4),
+                destination: Parameter::Address(5),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_multiply_invalid_destination() {
+        let opcodes = [10002, 3, 4, 5];
+        let op = Operation::from_slice(&opcodes);
+
+        assert!(op.is_err());
+    }
+
+    #[test]
+    fn parse_multiply_trailing() {
+        let opcodes = [2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Multiply {
+                factor_1: Parameter::Address(3),
+                factor_2: Parameter::Address(4),
+                destination: Parameter::Address(5),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_input_exact() {
+        let opcodes = [3, 10];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Input {
+                destination: Parameter::Address(10)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_input_exact_invalid_testination() {
+        let opcodes = [103, 10];
+        let op = Operation::from_slice(&opcodes);
+
+        assert!(op.is_err());
+    }
+
+    #[test]
+    fn parse_output_trailing() {
+        let opcodes = [104, 10, 2];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Output {
+                source: Parameter::Value(10)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_exit_exact() {
+        let opcodes = [99];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(op, Ok(Operation::Exit));
+    }
+
+    #[test]
+    fn parse_exit_trailing() {
+        let opcodes = [99, 100];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(op, Ok(Operation::Exit));
+    }
+
+    #[test]
+    fn parse_jump_if_true_trailing() {
+        let opcodes = [105, 1, 2, 3];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::JumpIfTrue {
+                condition: Parameter::Value(1),
+                location: Parameter::Address(2)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_jump_if_false_trailing() {
+        let opcodes = [1006, 1, 2, 3];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::JumpIfFalse {
+                condition: Parameter::Address(1),
+                location: Parameter::Value(2)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_less_than_exact() {
+        let opcodes = [107, 1, 2, 3];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::LessThan {
+                value_1: Parameter::Value(1),
+                value_2: Parameter::Address(2),
+                destination: Parameter::Address(3)
+            })
+        );
+    }
+
+    #[test]
+    fn encode_roundtrip() {
+        let programs: &[&[i32]] = &[
+            &[1, 2, 3, 4],
+            &[1002, 3, 4, 5],
+            &[3, 10],
+            &[104, 10],
+            &[105, 1, 2],
+            &[1006, 1, 2],
+            &[1107, 1, 2, 3],
+            &[8, 1, 2, 3],
+            &[109, 5],
+            &[21201, -1, 4, 2],
+            &[99],
+        ];
+
+        for program in programs {
+            let op = Operation::from_slice(program).unwrap();
+
+            assert_eq!(op.encode(), *program);
+        }
+    }
+
+    #[test]
+    fn parse_equals_trailing() {
+        let opcodes = [1008, 1, 2, 3, 4];
+        let op = Operation::from_slice(&opcodes);
+
+        assert_eq!(
+            op,
+            Ok(Operation::Equals {
+                value_1: Parameter::Address(1),
+                value_2: Parameter::Value(2),
+                destination: Parameter::Address(3)
+            })
+        );
+    }
+
+    #[test]
+    fn display() {
+        let operation = Operation::from_slice(&[1001, 9, 3, 10]).unwrap();
+
+        assert_eq!(operation.to_string(), "add [9], 3, [10]");
+        assert_eq!(Operation::Exit.to_string(), "halt");
+
+        let operation = Operation::from_slice(&[21201, -1, 4, 0]).unwrap();
+        assert_eq!(operation.to_string(), "add [rb - 1], 4, [rb]");
+    }
+
+    #[test]
+    fn parse_relative_parameters() {
+        assert_eq!(
+            Operation::from_slice(&[209, 3]),
+            Ok(Operation::AdjustRelativeBase {
+                adjustment: Parameter::Relative(3)
+            })
+        );
+        assert_eq!(
+            Operation::from_slice(&[203, -2]),
+            Ok(Operation::Input {
+                destination: Parameter::Relative(-2)
+            })
+        );
+        assert!(Operation::from_slice(&[303, 0]).is_err());
+    }
+}
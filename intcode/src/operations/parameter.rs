@@ -0,0 +1,38 @@
+use super::ParameterMode;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    Address(usize),
+    Value(i32),
+    /// The cell at the relative base plus the offset.
+    Relative(i32),
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Parameter::Address(address) => write!(f, "[{}]", address),
+            Parameter::Value(value) => write!(f, "{}", value),
+            Parameter::Relative(0) => write!(f, "[rb]"),
+            Parameter::Relative(offset) if *offset < 0 => {
+                write!(f, "[rb - {}]", -i64::from(*offset))
+            }
+            Parameter::Relative(offset) => write!(f, "[rb + {}]", offset),
+        }
+    }
+}
+
+pub trait ToParameter {
+    fn to_parameter(&self, mode: &ParameterMode) -> Parameter;
+}
+
+impl ToParameter for i32 {
+    fn to_parameter(&self, mode: &ParameterMode) -> Parameter {
+        match mode {
+            ParameterMode::Position => Parameter::Address(*self as usize),
+            ParameterMode::Immediate => Parameter::Value(*self),
+            ParameterMode::Relative => Parameter::Relative(*self),
+        }
+    }
+}
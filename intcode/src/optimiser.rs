@@ -0,0 +1,553 @@
+use super::{operations::Parameter, Operation};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const MAX_PASSES: usize = 16;
+const MAX_OP_LEN: usize = 4;
+
+/// Rewrites a program into an equivalent one that executes fewer or cheaper instructions.
+///
+/// Constant arithmetic is folded, jumps to unconditional jumps are threaded and code at the end
+/// of memory that can no longer run is dropped. Outputs, every cell the program uses as data and
+/// every cell that is not an instruction of the original program, such as initial data, are
+/// preserved. Programs that write into their own instructions, jump to computed locations,
+/// address memory relative to the relative base or contain overlapping instructions are
+/// returned unchanged, since any rewrite could be observed by them.
+pub fn optimise(program: &[i32]) -> Vec<i32> {
+    let mut memory = program.to_vec();
+    let code = match Analysis::new(&memory) {
+        Some(analysis) => analysis.words(),
+        None => return memory,
+    };
+
+    for _ in 0..MAX_PASSES {
+        let analysis = match Analysis::new(&memory) {
+            Some(analysis) => analysis,
+            None => return memory,
+        };
+
+        let rewritten = analysis.rewrite(&memory);
+        if rewritten == memory {
+            break;
+        }
+
+        memory = rewritten;
+    }
+
+    if let Some(analysis) = Analysis::new(&memory) {
+        let data_len = (0..memory.len())
+            .rev()
+            .find(|address| !code.contains(address))
+            .map_or(0, |address| address + 1);
+        memory.truncate(analysis.used_len(memory.len()).max(data_len));
+    }
+
+    memory
+}
+
+struct Analysis {
+    instructions: BTreeMap<usize, Operation>,
+    undecodable: Vec<usize>,
+    reads: HashSet<usize>,
+    writes: HashSet<usize>,
+}
+
+impl Analysis {
+    fn new(memory: &[i32]) -> Option<Self> {
+        let mut instructions = BTreeMap::new();
+        let mut undecodable = Vec::new();
+        let mut owners = HashMap::new();
+        let mut pending = vec![0];
+
+        while let Some(address) = pending.pop() {
+            if instructions.contains_key(&address) {
+                continue;
+            }
+
+            let operation = match memory.get(address..).map(Operation::from_slice) {
+                Some(Ok(operation)) => operation,
+                _ => {
+                    undecodable.push(address);
+                    continue;
+                }
+            };
+            if operation.is_relative() {
+                return None;
+            }
+
+            for word in address..address + operation.op_len() {
+                if let Some(owner) = owners.insert(word, address) {
+                    if owner != address {
+                        return None;
+                    }
+                }
+            }
+
+            let next = address + operation.op_len();
+            match &operation {
+                Operation::Exit => {}
+                Operation::JumpIfTrue {
+                    condition,
+                    location,
+                }
+                | Operation::JumpIfFalse {
+                    condition,
+                    location,
+                } => {
+                    let taken = match condition {
+                        Parameter::Value(value) => Some(Self::is_taken(&operation, *value)),
+                        _ => None,
+                    };
+
+                    if taken != Some(false) {
+                        match location {
+                            Parameter::Value(target) if *target >= 0 => {
+                                pending.push(*target as usize)
+                            }
+                            Parameter::Value(_) => {}
+                            _ => return None,
+                        }
+                    }
+                    if taken != Some(true) {
+                        pending.push(next);
+                    }
+                }
+                _ => pending.push(next),
+            }
+
+            instructions.insert(address, operation);
+        }
+
+        let mut reads = HashSet::new();
+        let mut writes = HashSet::new();
+        for operation in instructions.values() {
+            for parameter in operation.parameters() {
+                if let Parameter::Address(address) = parameter {
+                    reads.insert(*address);
+                }
+            }
+            writes.extend(operation.destination_address());
+        }
+
+        if writes.iter().any(|address| owners.contains_key(address)) {
+            return None;
+        }
+
+        Some(Self {
+            instructions,
+            undecodable,
+            reads,
+            writes,
+        })
+    }
+
+    fn is_taken(operation: &Operation, condition: i32) -> bool {
+        match operation {
+            Operation::JumpIfFalse { .. } => condition == 0,
+            _ => condition != 0,
+        }
+    }
+
+    /// Returns the addresses of every word of the reachable instructions.
+    fn words(&self) -> HashSet<usize> {
+        self.instructions
+            .iter()
+            .flat_map(|(address, operation)| *address..address + operation.op_len())
+            .collect()
+    }
+
+    fn used_len(&self, len: usize) -> usize {
+        let instructions = self
+            .instructions
+            .iter()
+            .map(|(address, operation)| address + operation.op_len());
+        let undecodable = self.undecodable.iter().map(|address| address + MAX_OP_LEN);
+        let data = self
+            .reads
+            .iter()
+            .chain(self.writes.iter())
+            .map(|address| address + 1);
+
+        instructions
+            .chain(undecodable)
+            .chain(data)
+            .max()
+            .unwrap_or(0)
+            .min(len)
+    }
+
+    fn rewrite(&self, memory: &[i32]) -> Vec<i32> {
+        let mut rewritten = memory.to_vec();
+
+        for (address, operation) in &self.instructions {
+            let words = *address..address + operation.op_len();
+            if words.clone().any(|word| self.reads.contains(&word)) {
+                continue;
+            }
+
+            let encoded = self.simplify(memory, operation).encode();
+            rewritten[*address..address + encoded.len()].copy_from_slice(&encoded);
+        }
+
+        rewritten
+    }
+
+    fn simplify(&self, memory: &[i32], operation: &Operation) -> Operation {
+        match operation {
+            Operation::Add {
+                addend_1,
+                addend_2,
+                destination,
+            } => self.binary(
+                memory,
+                (addend_1, addend_2),
+                *destination,
+                i32::checked_add,
+                |addend_1, addend_2, destination| Operation::Add {
+                    addend_1,
+                    addend_2,
+                    destination,
+                },
+            ),
+            Operation::Multiply {
+                factor_1,
+                factor_2,
+                destination,
+            } => self.binary(
+                memory,
+                (factor_1, factor_2),
+                *destination,
+                i32::checked_mul,
+                |factor_1, factor_2, destination| Operation::Multiply {
+                    factor_1,
+                    factor_2,
+                    destination,
+                },
+            ),
+            Operation::LessThan {
+                value_1,
+                value_2,
+                destination,
+            } => self.binary(
+                memory,
+                (value_1, value_2),
+                *destination,
+                |value_1, value_2| Some((value_1 < value_2) as i32),
+                |value_1, value_2, destination| Operation::LessThan {
+                    value_1,
+                    value_2,
+                    destination,
+                },
+            ),
+            Operation::Equals {
+                value_1,
+                value_2,
+                destination,
+            } => self.binary(
+                memory,
+                (value_1, value_2),
+                *destination,
+                |value_1, value_2| Some((value_1 == value_2) as i32),
+                |value_1, value_2, destination| Operation::Equals {
+                    value_1,
+                    value_2,
+                    destination,
+                },
+            ),
+            Operation::Output { source } => Operation::Output {
+                source: self.fold(memory, source),
+            },
+            Operation::JumpIfTrue {
+                condition,
+                location,
+            } => self.jump(
+                memory,
+                operation,
+                condition,
+                location,
+                |condition, location| Operation::JumpIfTrue {
+                    condition,
+                    location,
+                },
+            ),
+            Operation::JumpIfFalse {
+                condition,
+                location,
+            } => self.jump(
+                memory,
+                operation,
+                condition,
+                location,
+                |condition, location| Operation::JumpIfFalse {
+                    condition,
+                    location,
+                },
+            ),
+            Operation::Input { destination } => Operation::Input {
+                destination: *destination,
+            },
+            Operation::AdjustRelativeBase { adjustment } => Operation::AdjustRelativeBase {
+                adjustment: self.fold(memory, adjustment),
+            },
+            Operation::Exit => Operation::Exit,
+        }
+    }
+
+    fn binary<E, C>(
+        &self,
+        memory: &[i32],
+        (parameter_1, parameter_2): (&Parameter, &Parameter),
+        destination: Parameter,
+        evaluate: E,
+        create: C,
+    ) -> Operation
+    where
+        E: FnOnce(i32, i32) -> Option<i32>,
+        C: FnOnce(Parameter, Parameter, Parameter) -> Operation,
+    {
+        let parameter_1 = self.fold(memory, parameter_1);
+        let parameter_2 = self.fold(memory, parameter_2);
+
+        match (&parameter_1, &parameter_2) {
+            (Parameter::Value(value_1), Parameter::Value(value_2)) => {
+                match evaluate(*value_1, *value_2) {
+                    Some(result) => Operation::Add {
+                        addend_1: Parameter::Value(result),
+                        addend_2: Parameter::Value(0),
+                        destination,
+                    },
+                    None => create(parameter_1, parameter_2, destination),
+                }
+            }
+            _ => create(parameter_1, parameter_2, destination),
+        }
+    }
+
+    fn jump<C>(
+        &self,
+        memory: &[i32],
+        operation: &Operation,
+        condition: &Parameter,
+        location: &Parameter,
+        create: C,
+    ) -> Operation
+    where
+        C: FnOnce(Parameter, Parameter) -> Operation,
+    {
+        let condition = self.fold(memory, condition);
+        let location = match location {
+            Parameter::Value(target) => Parameter::Value(self.thread(memory, *target)),
+            location => *location,
+        };
+
+        match (&condition, &location) {
+            (Parameter::Value(value), Parameter::Value(target))
+                if Self::is_taken(operation, *value) =>
+            {
+                if *target >= 0
+                    && self.instructions.get(&(*target as usize)) == Some(&Operation::Exit)
+                {
+                    Operation::Exit
+                } else {
+                    Operation::JumpIfTrue {
+                        condition: Parameter::Value(1),
+                        location,
+                    }
+                }
+            }
+            _ => create(condition, location),
+        }
+    }
+
+    fn thread(&self, memory: &[i32], mut target: i32) -> i32 {
+        let mut visited = HashSet::new();
+
+        while target >= 0 && visited.insert(target) {
+            match self.instructions.get(&(target as usize)) {
+                Some(
+                    operation @ Operation::JumpIfTrue {
+                        condition,
+                        location: Parameter::Value(next),
+                    },
+                )
+                | Some(
+                    operation @ Operation::JumpIfFalse {
+                        condition,
+                        location: Parameter::Value(next),
+                    },
+                ) => match self.fold(memory, condition) {
+                    Parameter::Value(value) if Self::is_taken(operation, value) => target = *next,
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+
+        target
+    }
+
+    fn fold(&self, memory: &[i32], parameter: &Parameter) -> Parameter {
+        match parameter {
+            Parameter::Address(address)
+                if *address < memory.len() && !self.writes.contains(address) =>
+            {
+                Parameter::Value(memory[*address])
+            }
+            parameter => *parameter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::programmable::ProgrammableOutput;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::Program;
+
+    fn run_fixed_io(program: Vec<i32>, input: Vec<i32>, output: Vec<i32>) {
+        let mut input = UnitTestInput::new(input);
+        let mut output = UnitTestOutput::new(output);
+
+        let mut program = Program::new(program, &mut input, &mut output);
+        let result = program.run();
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn constant_arithmetic_is_folded() {
+        let program = vec![1, 7, 8, 9, 4, 9, 99, 2, 3, 0];
+
+        let optimised = optimise(&program);
+
+        assert_eq!(optimised[..4], [1101, 5, 0, 9]);
+        run_fixed_io(optimised, vec![], vec![5]);
+    }
+
+    #[test]
+    fn jump_chains_are_threaded() {
+        let program = vec![
+            1101, 2, 3, 17, 1105, 1, 9, 104, 0, 1105, 1, 12, 4, 17, 99, 0, 0, 0,
+        ];
+
+        let optimised = optimise(&program);
+
+        assert_eq!(optimised[4..7], [1105, 1, 12]);
+        run_fixed_io(optimised, vec![], vec![5]);
+    }
+
+    #[test]
+    fn jump_to_exit_becomes_exit() {
+        let optimised = optimise(&[1106, 0, 5, 104, 1, 99]);
+
+        assert_eq!(optimised, [99, 0, 5, 104, 1]);
+    }
+
+    #[test]
+    fn unreachable_tail_is_dropped() {
+        let program = vec![1105, 1, 6, 104, 2, 99, 1105, 1, 3];
+
+        let optimised = optimise(&program);
+
+        assert_eq!(optimised, [1105, 1, 3, 104, 2, 99]);
+        run_fixed_io(optimised, vec![], vec![2]);
+    }
+
+    #[test]
+    fn initial_data_is_kept() {
+        let program = vec![1101, 1, 1, 7, 4, 7, 99, 0, 1, 2, 3, 4];
+
+        let optimised = optimise(&program);
+
+        assert_eq!(optimised, [1101, 2, 0, 7, 4, 7, 99, 0, 1, 2, 3, 4]);
+        run_fixed_io(optimised, vec![], vec![2]);
+    }
+
+    #[test]
+    fn conditional_jump_on_input_is_kept() {
+        let program = vec![3, 12, 1005, 12, 9, 104, 0, 99, 0, 104, 1, 99, 0];
+
+        let optimised = optimise(&program);
+
+        assert_eq!(optimised, program);
+        run_fixed_io(optimised.clone(), vec![0], vec![0]);
+        run_fixed_io(optimised, vec![3], vec![1]);
+    }
+
+    #[test]
+    fn self_modifying_program_is_unchanged() {
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+
+        assert_eq!(optimise(&program), program);
+    }
+
+    #[test]
+    fn relative_program_is_unchanged() {
+        let program = [109, 8, 1101, 2, 3, 9, 204, 1, 99, 0];
+
+        assert_eq!(optimise(&program), program);
+    }
+
+    #[test]
+    fn computed_jump_is_unchanged() {
+        let program = [1105, 1, 5, 99, 6, 1005, 4, 4, 99];
+
+        assert_eq!(optimise(&program), program);
+    }
+
+    #[test]
+    fn data_cells_are_preserved() {
+        let examples: &[(&[i32], usize, i32)] = &[
+            (&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], 0, 3500),
+            (&[1, 0, 0, 0, 99], 0, 2),
+            (&[2, 3, 0, 3, 99], 3, 6),
+            (&[2, 4, 4, 5, 99, 0], 5, 9801),
+            (&[1, 1, 1, 4, 99, 5, 6, 0, 99], 0, 30),
+        ];
+
+        for (program, address, expected_value) in examples {
+            let mut input = UnitTestInput::new(vec![]);
+            let mut output = UnitTestOutput::new(vec![]);
+            let mut program = Program::new(optimise(program), &mut input, &mut output);
+
+            assert!(program.run().is_ok());
+            assert_eq!(program.memory()[*address], *expected_value);
+        }
+    }
+
+    #[test]
+    fn final_memory_matches_outside_the_code() {
+        let examples: &[&[i32]] = &[
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &[1101, 1, 1, 7, 4, 7, 99, 0, 1, 2, 3, 4],
+            &[1, 7, 8, 9, 99, 0, 0, 2, 3, 0],
+            &[1101, 2, 3, 9, 1106, 0, 8, 0, 99, 0, 7, 7],
+        ];
+
+        for program in examples {
+            let code =
+                Analysis::new(program).map_or_else(HashSet::new, |analysis| analysis.words());
+            let mut final_memory = Vec::new();
+            let mut outputs = Vec::new();
+            for program in [program.to_vec(), optimise(program)] {
+                let mut input = UnitTestInput::new(vec![]);
+                let mut output = ProgrammableOutput::new();
+                let mut program = Program::new(program, &mut input, &mut output);
+
+                assert!(program.run().is_ok());
+                final_memory.push(program.memory().to_vec());
+                outputs.push(output.output());
+            }
+
+            assert_eq!(outputs[0], outputs[1]);
+            assert_eq!(final_memory[0].len(), final_memory[1].len());
+            for (address, (original, optimised)) in
+                final_memory[0].iter().zip(&final_memory[1]).enumerate()
+            {
+                if !code.contains(&address) {
+                    assert_eq!(original, optimised, "cell {}", address);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,629 @@
+use super::{
+    coverage::Coverage,
+    extensions::ExtensionSet,
+    io::{LineReader, LineWriter},
+    operations::Parameter,
+    protection::{Fault, Protection, Protections},
+    Operation,
+};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Running,
+    Halted,
+}
+
+pub struct Program<'a, Input, Output>
+where
+    Input: LineReader,
+    Output: LineWriter,
+{
+    memory: Vec<i32>,
+    instruction_pointer: usize,
+    relative_base: i32,
+    input: &'a mut Input,
+    output: &'a mut Output,
+    extensions: Option<&'a mut dyn ExtensionSet>,
+    protections: Protections,
+    fault: Option<Fault>,
+    coverage: Option<&'a mut Coverage>,
+}
+
+impl<'a, Input, Output> Program<'a, Input, Output>
+where
+    Input: LineReader,
+    Output: LineWriter,
+{
+    pub fn new(memory: Vec<i32>, input: &'a mut Input, output: &'a mut Output) -> Self {
+        Self::with_entry_point(memory, 0, input, output)
+    }
+
+    pub fn with_entry_point(
+        memory: Vec<i32>,
+        entry_point: usize,
+        input: &'a mut Input,
+        output: &'a mut Output,
+    ) -> Self {
+        Self {
+            input,
+            memory,
+            instruction_pointer: entry_point,
+            relative_base: 0,
+            output,
+            extensions: None,
+            protections: Protections::new(),
+            fault: None,
+            coverage: None,
+        }
+    }
+
+    /// Adds custom instructions on top of the built-in ones.
+    pub fn with_extensions(mut self, extensions: &'a mut dyn ExtensionSet) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    pub fn run(&mut self) -> Result<(), Cow<'static, str>> {
+        while self.step()? == State::Running {}
+
+        Ok(())
+    }
+
+    /// Decodes the instruction at the instruction pointer without executing it.
+    pub fn next_operation(&self) -> Result<Operation, Cow<'static, str>> {
+        let memory = self
+            .memory
+            .get(self.instruction_pointer..)
+            .ok_or("Instruction pointer is out of bounds")?;
+
+        Operation::from_slice(memory)
+    }
+
+    /// Executes a single instruction.
+    ///
+    /// Stepping a halted program leaves it halted. Additions, multiplications and relative base
+    /// adjustments whose result does not fit an `i32` fail, see `overflow`.
+    pub fn step(&mut self) -> Result<State, Cow<'static, str>> {
+        if self
+            .protections
+            .is_protected(self.instruction_pointer, Protection::NoExecute)
+        {
+            return Err(self.raise(Fault::Execute {
+                address: self.instruction_pointer,
+            }));
+        }
+
+        if let Some(result) = self.step_extension() {
+            return result;
+        }
+
+        let op_code = self.next_operation()?;
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.hit(
+                self.instruction_pointer,
+                self.memory[self.instruction_pointer],
+                &op_code,
+            );
+        }
+
+        match &op_code {
+            Operation::Add {
+                addend_1,
+                addend_2,
+                destination,
+            } => {
+                let result = overflow(self.load(addend_1)?.checked_add(self.load(addend_2)?))?;
+                self.store(destination, result)?;
+            }
+            Operation::Multiply {
+                factor_1,
+                factor_2,
+                destination,
+            } => {
+                let result = overflow(self.load(factor_1)?.checked_mul(self.load(factor_2)?))?;
+                self.store(destination, result)?;
+            }
+            Operation::Exit => return Ok(State::Halted),
+            Operation::Input { destination } => {
+                let value = self.input.read_line()?;
+                self.store(destination, value)?
+            }
+            Operation::Output { source } => {
+                let value = self.load(source)?;
+                self.output.write_line(value)?;
+            }
+            Operation::JumpIfTrue {
+                condition,
+                location,
+            } => {
+                let value = self.load(condition)?;
+                self.record_branch(value != 0);
+                if value != 0 {
+                    let location = self.load(location)?;
+                    self.instruction_pointer = location as usize;
+                    return Ok(State::Running);
+                }
+            }
+            Operation::JumpIfFalse {
+                condition,
+                location,
+            } => {
+                let value = self.load(condition)?;
+                self.record_branch(value == 0);
+                if value == 0 {
+                    let location = self.load(location)?;
+                    self.instruction_pointer = location as usize;
+                    return Ok(State::Running);
+                }
+            }
+            Operation::LessThan {
+                value_1,
+                value_2,
+                destination,
+            } => {
+                let value_1 = self.load(value_1)?;
+                let value_2 = self.load(value_2)?;
+
+                if value_1 < value_2 {
+                    self.store(destination, 1)?
+                } else {
+                    self.store(destination, 0)?
+                }
+            }
+            Operation::Equals {
+                value_1,
+                value_2,
+                destination,
+            } => {
+                let value_1 = self.load(value_1)?;
+                let value_2 = self.load(value_2)?;
+
+                if value_1 == value_2 {
+                    self.store(destination, 1)?
+                } else {
+                    self.store(destination, 0)?
+                }
+            }
+            Operation::AdjustRelativeBase { adjustment } => {
+                self.relative_base =
+                    overflow(self.relative_base.checked_add(self.load(adjustment)?))?;
+            }
+        }
+
+        self.instruction_pointer += op_code.op_len();
+
+        Ok(State::Running)
+    }
+
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    pub fn set_instruction_pointer(&mut self, instruction_pointer: usize) {
+        self.instruction_pointer = instruction_pointer;
+    }
+
+    /// The base that relative parameters, `[rb + n]` in assembly, are offsets from. It starts
+    /// at 0 and `arb` adjusts it.
+    pub fn relative_base(&self) -> i32 {
+        self.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i32) {
+        self.relative_base = relative_base;
+    }
+
+    /// Records every executed instruction and conditional jump into `coverage`.
+    pub fn with_coverage(mut self, coverage: &'a mut Coverage) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    /// Protects `range` from now on. Writes through `memory_mut` are not checked.
+    pub fn protect(&mut self, range: Range<usize>, protection: Protection) {
+        self.protections.protect(range, protection);
+    }
+
+    /// The protection fault that stopped the program, if any.
+    pub fn fault(&self) -> Option<&Fault> {
+        self.fault.as_ref()
+    }
+
+    pub fn memory(&self) -> &[i32] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [i32] {
+        &mut self.memory
+    }
+
+    /// Executes the instruction at the instruction pointer if it is an extension.
+    fn step_extension(&mut self) -> Option<Result<State, Cow<'static, str>>> {
+        let words = self.memory.get(self.instruction_pointer..)?;
+        let decoded = self.extensions.as_ref()?.decode(words)?;
+
+        Some(decoded.and_then(|decoded| {
+            let word = self.memory[self.instruction_pointer];
+            if let Some(coverage) = self.coverage.as_mut() {
+                coverage.hit(self.instruction_pointer, word, &decoded);
+            }
+            let values = decoded
+                .parameters
+                .iter()
+                .map(|parameter| self.load(parameter))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let extensions = self.extensions.as_mut().expect("decoded above");
+            if let (Some(value), Some(destination)) =
+                (extensions.execute(word, &values)?, &decoded.destination)
+            {
+                self.store(destination, value)?;
+            }
+
+            self.instruction_pointer += decoded.op_len();
+            Ok(State::Running)
+        }))
+    }
+
+    fn record_branch(&mut self, taken: bool) {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.branch(self.instruction_pointer, taken);
+        }
+    }
+
+    fn raise(&mut self, fault: Fault) -> Cow<'static, str> {
+        self.fault = Some(fault);
+        fault.into()
+    }
+
+    /// The address a position or relative parameter refers to.
+    fn address(&self, parameter: &Parameter) -> Result<usize, Cow<'static, str>> {
+        match parameter {
+            Parameter::Address(address) => Ok(*address),
+            Parameter::Relative(offset) => {
+                let address = i64::from(self.relative_base) + i64::from(*offset);
+                usize::try_from(address)
+                    .map_err(|_| format!("Address {} is out of bounds", address).into())
+            }
+            Parameter::Value(_) => Err("Immediate is an invalid mode for as a destination".into()),
+        }
+    }
+
+    fn load(&self, parameter: &Parameter) -> Result<i32, Cow<'static, str>> {
+        if let Parameter::Value(value) = parameter {
+            return Ok(*value);
+        }
+
+        let address = self.address(parameter)?;
+        self.memory
+            .get(address)
+            .copied()
+            .ok_or_else(|| format!("Address {} is out of bounds", address).into())
+    }
+
+    fn store(&mut self, destination: &Parameter, value: i32) -> Result<(), Cow<'static, str>> {
+        let address = self.address(destination)?;
+        if self.protections.is_protected(address, Protection::ReadOnly) {
+            return Err(self.raise(Fault::Write {
+                address,
+                instruction_pointer: self.instruction_pointer,
+            }));
+        }
+
+        match self.memory.get_mut(address) {
+            Some(cell) => {
+                *cell = value;
+                Ok(())
+            }
+            None => Err(format!("Address {} is out of bounds", address).into()),
+        }
+    }
+}
+
+/// Turns the result of a checked operation into an error when it overflowed.
+///
+/// Intcode leaves overflow undefined. Failing the program makes it behave the same in debug and
+/// release builds, rather than panicking in one and silently wrapping in the other, and lets
+/// tools that compare runs, like the fuzzer, reject such programs.
+fn overflow(result: Option<i32>) -> Result<i32, Cow<'static, str>> {
+    result.ok_or_else(|| "Arithmetic overflow".into())
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::optimiser::optimise;
+
+    fn null_input_and_output() -> (UnitTestInput, UnitTestOutput) {
+        (
+            UnitTestInput::new(Vec::new()),
+            UnitTestOutput::new(Vec::new()),
+        )
+    }
+
+    fn run_fixed_io(program: Vec<i32>, input: Vec<i32>, output: Vec<i32>) {
+        let optimised = optimise(&program);
+
+        for program in [program, optimised] {
+            let mut input = UnitTestInput::new(input.clone());
+            let mut output = UnitTestOutput::new(output.clone());
+
+            let mut program = Program::new(program, &mut input, &mut output);
+            let result = program.run();
+
+            assert_eq!(result.is_ok(), true);
+        }
+    }
+
+    #[test]
+    fn day_02_run_example_explained_in_text() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(
+            vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &mut input,
+            &mut output,
+        );
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(
+            program.memory,
+            [3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]
+        );
+    }
+
+    #[test]
+    fn step_one_instruction_at_a_time() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1, 0, 0, 0, 1105, 1, 7, 99], &mut input, &mut output);
+
+        assert_eq!(program.step(), Ok(State::Running));
+        assert_eq!(program.instruction_pointer(), 4);
+        assert_eq!(program.memory[0], 2);
+
+        assert_eq!(program.step(), Ok(State::Running));
+        assert_eq!(program.instruction_pointer(), 7);
+        assert_eq!(program.next_operation(), Ok(Operation::Exit));
+
+        assert_eq!(program.step(), Ok(State::Halted));
+        assert_eq!(program.step(), Ok(State::Halted));
+        assert_eq!(program.instruction_pointer(), 7);
+    }
+
+    #[test]
+    fn out_of_bounds_access_fails() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1, 12, 0, 0, 99], &mut input, &mut output);
+
+        assert!(program.run().is_err());
+
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1101, 1, 1, 12, 99], &mut input, &mut output);
+
+        assert!(program.run().is_err());
+    }
+
+    #[test]
+    fn arithmetic_overflow_fails() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1102, 65536, 65536, 0, 99], &mut input, &mut output);
+
+        assert_eq!(program.run(), Err("Arithmetic overflow".into()));
+
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1101, i32::MAX, 1, 0, 99], &mut input, &mut output);
+
+        assert_eq!(program.run(), Err("Arithmetic overflow".into()));
+
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![109, i32::MIN, 109, -1, 99], &mut input, &mut output);
+
+        assert_eq!(program.run(), Err("Arithmetic overflow".into()));
+        assert_eq!(program.relative_base(), i32::MIN);
+    }
+
+    #[test]
+    fn relative_parameters_follow_the_relative_base() {
+        let mut input = UnitTestInput::new(vec![]);
+        let mut output = UnitTestOutput::new(vec![42, 5]);
+        let mut program = Program::new(
+            vec![109, 9, 204, 2, 21101, 2, 3, 3, 4, 12, 99, 42, 0],
+            &mut input,
+            &mut output,
+        );
+
+        assert!(program.run().is_ok());
+        assert_eq!(program.relative_base(), 9);
+
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![109, -1, 204, 0, 99], &mut input, &mut output);
+
+        assert_eq!(program.run(), Err("Address -1 is out of bounds".into()));
+    }
+
+    #[test]
+    fn day_02_run_example_short_1() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1, 0, 0, 0, 99], &mut input, &mut output);
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(program.memory, [2, 0, 0, 0, 99]);
+    }
+
+    #[test]
+    fn day_02_run_example_short_2() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![2, 3, 0, 3, 99], &mut input, &mut output);
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(program.memory, [2, 3, 0, 6, 99]);
+    }
+
+    #[test]
+    fn day_02_run_example_short_3() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![2, 4, 4, 5, 99, 0], &mut input, &mut output);
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(program.memory, [2, 4, 4, 5, 99, 9801]);
+    }
+
+    #[test]
+    fn day_02_run_example_short_4() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99], &mut input, &mut output);
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(program.memory, [30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    }
+
+    #[test]
+    fn day_05_run_negative() {
+        let (mut input, mut output) = null_input_and_output();
+        let mut program = Program::new(vec![1101, 100, -1, 4, 0], &mut input, &mut output);
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(program.memory, [1101, 100, -1, 4, 99]);
+    }
+
+    #[test]
+    fn day_05_test_io() {
+        let mut input = UnitTestInput::new(vec![1, 2]);
+        let mut output = UnitTestOutput::new(vec![3]);
+
+        let mut program = Program::new(
+            vec![3, 0, 3, 1, 1, 0, 1, 2, 4, 2, 99],
+            &mut input,
+            &mut output,
+        );
+        let result = program.run();
+
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(program.memory, [1, 2, 3, 1, 1, 0, 1, 2, 4, 2, 99]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_1_input_8() {
+        run_fixed_io(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], vec![8], vec![1]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_1_input_9() {
+        run_fixed_io(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], vec![9], vec![0]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_2_input_7() {
+        run_fixed_io(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8], vec![7], vec![1]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_2_input_9() {
+        run_fixed_io(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8], vec![9], vec![0]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_3_input_8() {
+        run_fixed_io(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99], vec![8], vec![1]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_3_input_9() {
+        run_fixed_io(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99], vec![9], vec![0]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_4_input_7() {
+        run_fixed_io(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99], vec![7], vec![1]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_4_input_9() {
+        run_fixed_io(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99], vec![9], vec![0]);
+    }
+
+    #[test]
+    fn day_05_part_2_example_5_input_0() {
+        run_fixed_io(
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            vec![0],
+            vec![0],
+        );
+    }
+
+    #[test]
+    fn day_05_part_2_example_5_input_5() {
+        run_fixed_io(
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            vec![5],
+            vec![1],
+        );
+    }
+
+    #[test]
+    fn day_05_part_2_example_6_input_0() {
+        run_fixed_io(
+            vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+            vec![0],
+            vec![0],
+        );
+    }
+
+    #[test]
+    fn day_05_part_2_example_6_input_5() {
+        run_fixed_io(
+            vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+            vec![5],
+            vec![1],
+        );
+    }
+
+    #[test]
+    fn day_05_part_2_example_7_input_7() {
+        run_fixed_io(
+            vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+            vec![7],
+            vec![999],
+        );
+    }
+
+    #[test]
+    fn day_05_part_2_example_7_input_8() {
+        run_fixed_io(
+            vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+            vec![8],
+            vec![1000],
+        );
+    }
+
+    #[test]
+    fn day_05_part_2_example_7_input_9() {
+        run_fixed_io(
+            vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+            vec![9],
+            vec![1001],
+        );
+    }
+}
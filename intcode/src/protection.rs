@@ -0,0 +1,162 @@
+//! Memory protection for `Program`.
+//!
+//! Ranges of memory can be made read-only, so that stores into them fault, or no-execute, so
+//! that running an instruction that starts inside them faults. Faults stop the program like any
+//! other error, and `Program::fault` tells them apart from the rest.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protection {
+    ReadOnly,
+    NoExecute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// The instruction at `instruction_pointer` stored into a read-only `address`.
+    Write {
+        address: usize,
+        instruction_pointer: usize,
+    },
+    /// Execution reached `address`, which is no-execute.
+    Execute { address: usize },
+}
+
+impl Fault {
+    pub fn address(&self) -> usize {
+        match self {
+            Fault::Write { address, .. } | Fault::Execute { address } => *address,
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Write {
+                address,
+                instruction_pointer,
+            } => write!(
+                f,
+                "Protection fault: instruction at {} wrote to read-only address {}",
+                instruction_pointer, address
+            ),
+            Fault::Execute { address } => write!(
+                f,
+                "Protection fault: executed no-execute address {}",
+                address
+            ),
+        }
+    }
+}
+
+impl From<Fault> for Cow<'static, str> {
+    fn from(fault: Fault) -> Self {
+        fault.to_string().into()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Protections {
+    read_only: Vec<Range<usize>>,
+    no_execute: Vec<Range<usize>>,
+}
+
+impl Protections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn protect(&mut self, range: Range<usize>, protection: Protection) {
+        match protection {
+            Protection::ReadOnly => self.read_only.push(range),
+            Protection::NoExecute => self.no_execute.push(range),
+        }
+    }
+
+    pub fn is_protected(&self, address: usize, protection: Protection) -> bool {
+        let ranges = match protection {
+            Protection::ReadOnly => &self.read_only,
+            Protection::NoExecute => &self.no_execute,
+        };
+
+        ranges.iter().any(|range| range.contains(&address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::Program;
+
+    fn run(memory: Vec<i32>, protections: &[(Range<usize>, Protection)]) -> Option<Fault> {
+        let mut input = UnitTestInput::new(Vec::new());
+        let mut output = UnitTestOutput::new(Vec::new());
+        let mut program = Program::new(memory, &mut input, &mut output);
+        for (range, protection) in protections {
+            program.protect(range.clone(), *protection);
+        }
+
+        let _ = program.run();
+        program.fault().copied()
+    }
+
+    #[test]
+    fn unprotected_programs_run_normally() {
+        assert_eq!(run(vec![1, 0, 0, 0, 99], &[]), None);
+    }
+
+    #[test]
+    fn store_into_read_only_code() {
+        // Overwrites its own halt instruction.
+        let memory = vec![1101, 1, 1, 4, 99];
+
+        assert_eq!(
+            run(memory.clone(), &[(0..5, Protection::ReadOnly)]),
+            Some(Fault::Write {
+                address: 4,
+                instruction_pointer: 0
+            })
+        );
+        assert_eq!(run(memory, &[(0..4, Protection::ReadOnly)]), None);
+    }
+
+    #[test]
+    fn jump_into_data() {
+        let memory = vec![1105, 1, 4, 99, 7, 8];
+
+        assert_eq!(
+            run(memory, &[(4..6, Protection::NoExecute)]),
+            Some(Fault::Execute { address: 4 })
+        );
+    }
+
+    #[test]
+    fn fault_message_names_the_address() {
+        let fault = Fault::Write {
+            address: 12,
+            instruction_pointer: 3,
+        };
+
+        assert_eq!(
+            Cow::from(fault),
+            "Protection fault: instruction at 3 wrote to read-only address 12"
+        );
+        assert_eq!(fault.address(), 12);
+    }
+
+    #[test]
+    fn other_errors_are_not_faults() {
+        let mut input = UnitTestInput::new(Vec::new());
+        let mut output = UnitTestOutput::new(Vec::new());
+        let mut program = Program::new(vec![42], &mut input, &mut output);
+        program.protect(0..1, Protection::ReadOnly);
+
+        assert!(program.run().is_err());
+        assert_eq!(program.fault(), None);
+    }
+}
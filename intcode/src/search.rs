@@ -0,0 +1,327 @@
+use super::{
+    io::{adapter::IteratorInput, programmable::ProgrammableOutput},
+    Program, State,
+};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const DEFAULT_MAX_STEPS: usize = 100_000;
+
+/// What a run is judged on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Observation {
+    /// The value of a memory cell after the program halted.
+    Cell(usize),
+    /// Everything the program wrote.
+    Outputs,
+}
+
+/// The patched addresses and their values, in the order the patches were declared.
+pub type Assignment = Vec<(usize, i32)>;
+
+/// Runs a program for every combination of values of a few patched memory cells, like the
+/// noun and verb of day 2, and collects the combinations whose observation passes a predicate.
+///
+/// Runs that fail or exceed the step limit never match.
+pub struct Search {
+    memory: Vec<i32>,
+    patches: Vec<(usize, Range<i32>)>,
+    inputs: Vec<i32>,
+    observation: Observation,
+    max_steps: usize,
+    threads: usize,
+}
+
+impl Search {
+    pub fn new(memory: Vec<i32>) -> Self {
+        Self {
+            memory,
+            patches: Vec::new(),
+            inputs: Vec::new(),
+            observation: Observation::Cell(0),
+            max_steps: DEFAULT_MAX_STEPS,
+            threads: 1,
+        }
+    }
+
+    pub fn patch(mut self, address: usize, domain: Range<i32>) -> Self {
+        self.patches.push((address, domain));
+        self
+    }
+
+    /// The inputs every run reads.
+    pub fn inputs(mut self, inputs: Vec<i32>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn observe(mut self, observation: Observation) -> Self {
+        self.observation = observation;
+        self
+    }
+
+    /// Limits every run to `max_steps` steps, 100 000 by default.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Runs the program once with `values` patched in, one value per declared patch.
+    ///
+    /// Returns `None` when the run failed or exceeded the step limit.
+    pub fn evaluate(&self, values: &[i32]) -> Option<Vec<i32>> {
+        let mut memory = self.memory.clone();
+        for ((address, _), value) in self.patches.iter().zip(values) {
+            *memory.get_mut(*address)? = *value;
+        }
+
+        let mut input = IteratorInput::new(self.inputs.iter().copied());
+        let mut output = ProgrammableOutput::new();
+        let mut program = Program::new(memory, &mut input, &mut output);
+        let mut steps = 0;
+        while program.step().ok()? == State::Running {
+            steps += 1;
+            if steps >= self.max_steps {
+                return None;
+            }
+        }
+
+        match self.observation {
+            Observation::Cell(address) => program.memory().get(address).map(|value| vec![*value]),
+            Observation::Outputs => Some(output.output()),
+        }
+    }
+
+    /// Finds the first matching assignment, in the order in which the last declared patch
+    /// varies fastest, and stops searching as soon as it is known.
+    pub fn find_first<P>(&self, predicate: P) -> Result<Option<Assignment>, Cow<'static, str>>
+    where
+        P: Fn(&[i32]) -> bool + Sync,
+    {
+        Ok(self.search(predicate, true)?.into_iter().next())
+    }
+
+    /// Finds every matching assignment, in the same order as `find_first`.
+    pub fn find_all<P>(&self, predicate: P) -> Result<Vec<Assignment>, Cow<'static, str>>
+    where
+        P: Fn(&[i32]) -> bool + Sync,
+    {
+        self.search(predicate, false)
+    }
+
+    fn search<P>(
+        &self,
+        predicate: P,
+        first_only: bool,
+    ) -> Result<Vec<Assignment>, Cow<'static, str>>
+    where
+        P: Fn(&[i32]) -> bool + Sync,
+    {
+        if let Some((address, _)) = self
+            .patches
+            .iter()
+            .find(|(address, _)| *address >= self.memory.len())
+        {
+            return Err(format!(
+                "Cannot patch address {}, the program is only {} long",
+                address,
+                self.memory.len()
+            )
+            .into());
+        }
+
+        let total = self
+            .patches
+            .iter()
+            .try_fold(1usize, |total, (_, domain)| total.checked_mul(domain.len()))
+            .ok_or("Too many assignments to search")?;
+
+        // Indices beyond the first match are skipped once it is found.
+        let first_match = AtomicUsize::new(usize::MAX);
+        let matches = Mutex::new(Vec::new());
+        let worker = |offset: usize| {
+            let mut found = Vec::new();
+
+            for idx in (offset..total).step_by(self.threads) {
+                if first_only && idx > first_match.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let values = self.values(idx);
+                if self
+                    .evaluate(&values)
+                    .is_some_and(|observed| predicate(&observed))
+                {
+                    found.push((idx, values));
+                    if first_only {
+                        first_match.fetch_min(idx, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+
+            matches.lock().unwrap().extend(found);
+        };
+
+        if self.threads == 1 {
+            worker(0);
+        } else {
+            thread::scope(|scope| {
+                for offset in 0..self.threads {
+                    scope.spawn(move || worker(offset));
+                }
+            });
+        }
+
+        let mut matches = matches.into_inner().unwrap();
+        matches.sort_by_key(|(idx, _)| *idx);
+        if first_only {
+            matches.truncate(1);
+        }
+
+        Ok(matches
+            .into_iter()
+            .map(|(_, values)| {
+                self.patches
+                    .iter()
+                    .map(|(address, _)| *address)
+                    .zip(values)
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// The values of the assignment with index `idx`, counting like an odometer.
+    fn values(&self, mut idx: usize) -> Vec<i32> {
+        let mut values = vec![0; self.patches.len()];
+
+        for (value, (_, domain)) in values.iter_mut().zip(&self.patches).rev() {
+            *value = domain.start + (idx % domain.len()) as i32;
+            idx /= domain.len();
+        }
+
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Computes `100 * noun + verb + 7` into cell 0.
+    fn noun_and_verb() -> Vec<i32> {
+        vec![1002, 13, 100, 0, 1, 0, 14, 0, 1001, 0, 7, 0, 99, 0, 0]
+    }
+
+    #[test]
+    fn evaluate_one_assignment() {
+        let search = Search::new(noun_and_verb())
+            .patch(13, 0..100)
+            .patch(14, 0..100);
+
+        assert_eq!(search.evaluate(&[12, 34]), Some(vec![1241]));
+    }
+
+    #[test]
+    fn find_first_noun_and_verb() {
+        let search = Search::new(noun_and_verb())
+            .patch(13, 0..100)
+            .patch(14, 0..100);
+
+        let found = search.find_first(|observed| observed == [1234]).unwrap();
+
+        assert_eq!(found, Some(vec![(13, 12), (14, 27)]));
+    }
+
+    #[test]
+    fn find_all_is_ordered() {
+        let search = Search::new(noun_and_verb())
+            .patch(13, 0..10)
+            .patch(14, -200..200);
+
+        let found = search.find_all(|observed| observed == [507]).unwrap();
+
+        assert_eq!(
+            found,
+            [
+                vec![(13, 4), (14, 100)],
+                vec![(13, 5), (14, 0)],
+                vec![(13, 6), (14, -100)],
+                vec![(13, 7), (14, -200)],
+            ]
+        );
+    }
+
+    #[test]
+    fn threads_find_the_same_matches() {
+        let search = Search::new(noun_and_verb())
+            .patch(13, 0..50)
+            .patch(14, 0..500);
+        let predicate = |observed: &[i32]| observed[0] % 97 == 0;
+
+        let single = search.find_all(predicate).unwrap();
+        let search = search.threads(4);
+
+        assert_eq!(search.find_all(predicate).unwrap(), single);
+        assert_eq!(
+            search.find_first(predicate).unwrap().as_ref(),
+            single.first()
+        );
+    }
+
+    #[test]
+    fn observe_outputs_with_inputs() {
+        // Outputs its input plus the patched cell 10.
+        let program = vec![3, 9, 1, 9, 10, 9, 4, 9, 99, 0, 0];
+        let search = Search::new(program)
+            .patch(10, 0..10)
+            .inputs(vec![5])
+            .observe(Observation::Outputs);
+
+        let found = search.find_all(|outputs| outputs == [12]).unwrap();
+
+        assert_eq!(found, [vec![(10, 7)]]);
+    }
+
+    #[test]
+    fn failing_runs_never_match() {
+        // Jumps to the patched cell, where nothing but 99 is a valid instruction.
+        let program = vec![1105, 1, 3, 0];
+        let search = Search::new(program).patch(3, 0..100);
+
+        let found = search.find_all(|_| true).unwrap();
+
+        assert_eq!(found, [vec![(3, 99)]]);
+    }
+
+    #[test]
+    fn endless_runs_never_match() {
+        let search = Search::new(vec![1105, 1, 0, 0])
+            .patch(3, 0..10)
+            .max_steps(100);
+
+        assert_eq!(search.find_all(|_| true).unwrap(), Vec::<Assignment>::new());
+    }
+
+    #[test]
+    fn endless_runs_stop_by_default() {
+        let search = Search::new(vec![1105, 1, 0, 0]).patch(3, 0..2);
+
+        assert_eq!(search.find_first(|_| true).unwrap(), None);
+    }
+
+    #[test]
+    fn patch_outside_the_program() {
+        let search = Search::new(vec![99]).patch(1, 0..10);
+
+        assert!(search.find_all(|_| true).is_err());
+    }
+}
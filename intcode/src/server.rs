@@ -0,0 +1,76 @@
+use super::{
+    io::socket::{self, Stream},
+    Program,
+};
+use std::borrow::Cow;
+
+/// Runs a fresh copy of `memory` that reads from and writes to `stream`.
+///
+/// The connection is closed once the program halts, which tells the peer that no more
+/// outputs will follow.
+pub fn serve_connection<S>(stream: S, memory: Vec<i32>) -> Result<(), Cow<'static, str>>
+where
+    S: Stream,
+{
+    let (mut input, mut output) =
+        socket::split(stream).map_err(|error| format!("Failed to set up connection: {}", error))?;
+
+    Program::new(memory, &mut input, &mut output).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    #[test]
+    fn every_connection_gets_a_fresh_program() {
+        // Adds its input to a total kept in memory, so a shared memory would show up as a
+        // running sum.
+        let memory = vec![3, 9, 1, 9, 10, 10, 4, 10, 99, 0, 0];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                serve_connection(stream.unwrap(), memory.clone()).unwrap();
+            }
+        });
+
+        for value in &[5, 6] {
+            let mut client = TcpStream::connect(address).unwrap();
+            writeln!(client, "{}", value).unwrap();
+
+            let mut response = String::new();
+            client.read_to_string(&mut response).unwrap();
+            assert_eq!(response, format!("{}\n", value));
+        }
+
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_input_fails() {
+        use std::os::unix::net::UnixStream;
+
+        let (mut client, server) = UnixStream::pair().unwrap();
+        writeln!(client, "five").unwrap();
+
+        assert!(serve_connection(server, vec![3, 0, 99]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn closed_connection_ends_the_run() {
+        use std::os::unix::net::UnixStream;
+
+        let (client, server) = UnixStream::pair().unwrap();
+        drop(client);
+
+        // Outputs forever, so only the failed write can end it.
+        assert!(serve_connection(server, vec![104, 1, 1105, 1, 0]).is_err());
+    }
+}
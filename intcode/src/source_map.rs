@@ -0,0 +1,291 @@
+//! Maps the addresses of an assembled program back to its source and labels.
+//!
+//! The assembler and the linker both produce source maps, which are stored as text with one
+//! entry per line:
+//!
+//! ```text
+//! length 40
+//! label 0 start
+//! code 0 3 main.asm
+//! data 36 12 main.asm
+//! ```
+//!
+//! `code` and `data` give the address where the words of a source line start, followed by the
+//! line and the file. The line's words run up to the next entry.
+//!
+//! Addresses are shown relative to the closest label before them, like `loop+3`. Labels
+//! starting with `__` are left out, since the library's macros use them for their own jumps.
+
+use super::{assembler::Location, operations::Parameter, Operation};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const EXTENSION: &str = "icmap";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub location: Location,
+    /// Whether the words of the line are instructions rather than data.
+    pub code: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    len: usize,
+    lines: BTreeMap<usize, SourceLine>,
+    labels: BTreeMap<usize, Vec<String>>,
+}
+
+impl SourceMap {
+    /// Creates an empty source map for a program of `len` words.
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn add_line(&mut self, address: usize, location: Location, code: bool) {
+        self.lines.insert(address, SourceLine { location, code });
+    }
+
+    pub fn add_label(&mut self, address: usize, name: &str) {
+        self.labels
+            .entry(address)
+            .or_default()
+            .push(name.to_string());
+    }
+
+    /// Replaces the location of every source line with what `f` returns for it.
+    pub fn map_locations<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Location) -> Location,
+    {
+        for line in self.lines.values_mut() {
+            line.location = f(&line.location);
+        }
+    }
+
+    /// Returns the source line that produced the word at `address`.
+    pub fn line(&self, address: usize) -> Option<&SourceLine> {
+        if address >= self.len {
+            return None;
+        }
+
+        self.lines
+            .range(..=address)
+            .next_back()
+            .map(|(_, line)| line)
+    }
+
+    /// Returns where the line that produced the word at `address` is.
+    pub fn location(&self, address: usize) -> Option<&Location> {
+        self.line(address).map(|line| &line.location)
+    }
+
+    /// Returns whether a source line starts at `address`.
+    pub fn starts_line(&self, address: usize) -> bool {
+        self.lines.contains_key(&address)
+    }
+
+    /// Returns the labels of `address` that are not internal to a macro, in the order they
+    /// were added.
+    pub fn labels(&self, address: usize) -> impl Iterator<Item = &str> {
+        self.labels
+            .get(&address)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|name| !name.starts_with("__"))
+    }
+
+    /// Returns the address of a label.
+    pub fn address(&self, label: &str) -> Option<usize> {
+        self.labels
+            .iter()
+            .find(|(_, names)| names.iter().any(|name| name == label))
+            .map(|(address, _)| *address)
+    }
+
+    /// Formats `address` relative to the closest label at or before it, or as a number when
+    /// there is none or the address is outside the program.
+    pub fn symbolise(&self, address: usize) -> String {
+        if address >= self.len {
+            return address.to_string();
+        }
+
+        let label = self
+            .labels
+            .range(..=address)
+            .rev()
+            .find_map(|(&start, _)| Some((start, self.labels(start).next()?)));
+        match label {
+            Some((start, name)) if start == address => name.to_string(),
+            Some((start, name)) => format!("{}+{}", name, address - start),
+            None => address.to_string(),
+        }
+    }
+
+    /// Formats `operation` like its `Display` implementation, with every address symbolised.
+    /// Offsets from the relative base stay numbers.
+    pub fn operation(&self, operation: &Operation) -> String {
+        let jump = matches!(
+            operation,
+            Operation::JumpIfTrue { .. } | Operation::JumpIfFalse { .. }
+        );
+        let operands: Vec<_> = operation
+            .parameters()
+            .into_iter()
+            .chain(operation.destination())
+            .enumerate()
+            .map(|(idx, parameter)| match parameter {
+                Parameter::Address(address) => format!("[{}]", self.symbolise(*address)),
+                Parameter::Value(value) if jump && idx == 1 && *value >= 0 => {
+                    self.symbolise(*value as usize)
+                }
+                parameter => parameter.to_string(),
+            })
+            .collect();
+
+        if operands.is_empty() {
+            operation.mnemonic().to_string()
+        } else {
+            format!("{} {}", operation.mnemonic(), operands.join(", "))
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Cow<'static, str>> {
+        let mut map = Self::default();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = || format!("Line {}: invalid entry `{}`", idx + 1, line);
+            let fields: Vec<_> = line.splitn(4, ' ').collect();
+            match fields.as_slice() {
+                ["length", len] => map.len = len.parse().map_err(|_| error())?,
+                ["label", address, name] => {
+                    map.add_label(address.parse().map_err(|_| error())?, name)
+                }
+                [kind @ ("code" | "data"), address, line, file] => map.add_line(
+                    address.parse().map_err(|_| error())?,
+                    Location::new(file, line.parse().map_err(|_| error())?),
+                    *kind == "code",
+                ),
+                _ => return Err(error().into()),
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, Cow<'static, str>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+
+        Self::parse(&text).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+}
+
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "length {}", self.len)?;
+        for (address, names) in &self.labels {
+            for name in names {
+                writeln!(f, "label {} {}", address, name)?;
+            }
+        }
+        for (address, line) in &self.lines {
+            let kind = if line.code { "code" } else { "data" };
+            writeln!(
+                f,
+                "{} {} {} {}",
+                kind, address, line.location.line, line.location.file
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_map() -> SourceMap {
+        let mut map = SourceMap::new(12);
+        map.add_label(0, "start");
+        map.add_label(4, "__push1");
+        map.add_label(8, "value");
+        map.add_label(8, "result");
+        map.add_line(0, Location::new("main.asm", 2), true);
+        map.add_line(4, Location::new("main.asm", 3), true);
+        map.add_line(8, Location::new("my data.asm", 7), false);
+
+        map
+    }
+
+    #[test]
+    fn addresses_relative_to_labels() {
+        let map = source_map();
+
+        assert_eq!(map.symbolise(0), "start");
+        assert_eq!(map.symbolise(6), "start+6");
+        assert_eq!(map.symbolise(9), "value+1");
+        assert_eq!(map.symbolise(12), "12");
+        assert_eq!(map.address("result"), Some(8));
+        assert_eq!(map.address("missing"), None);
+        assert_eq!(map.labels(4).count(), 0);
+        assert_eq!(map.labels(8).collect::<Vec<_>>(), ["value", "result"]);
+    }
+
+    #[test]
+    fn lines_cover_their_words() {
+        let map = source_map();
+
+        assert_eq!(map.location(5), Some(&Location::new("main.asm", 3)));
+        assert!(!map.line(11).unwrap().code);
+        assert_eq!(map.line(12), None);
+        assert!(map.starts_line(4));
+        assert!(!map.starts_line(5));
+    }
+
+    #[test]
+    fn operations_are_symbolised() {
+        let map = source_map();
+        let add = Operation::from_slice(&[1001, 8, 3, 9]).unwrap();
+        let jump = Operation::from_slice(&[1106, 0, 4]).unwrap();
+
+        assert_eq!(map.operation(&add), "add [value], 3, [value+1]");
+        assert_eq!(map.operation(&jump), "jz 0, start+4");
+        assert_eq!(map.operation(&Operation::Exit), "halt");
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let map = source_map();
+        let text = map.to_string();
+
+        assert!(text.contains("data 8 7 my data.asm\n"));
+        assert_eq!(SourceMap::parse(&text), Ok(map));
+        assert!(SourceMap::parse("code 0 main.asm").is_err());
+    }
+}
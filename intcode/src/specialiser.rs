@@ -0,0 +1,580 @@
+use super::{operations::Parameter, validator, Operation};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+const DEFAULT_MAX_STEPS: usize = 100_000;
+
+/// The residual of a partially evaluated program.
+///
+/// Running `memory` from `entry_point` with `inputs` followed by the inputs that were unknown
+/// at analysis time produces the same outputs as running the original program with all inputs.
+#[derive(Debug, PartialEq)]
+pub struct Specialisation {
+    pub memory: Vec<i32>,
+    pub entry_point: usize,
+    /// The known inputs that analysis stopped before reading.
+    pub inputs: Vec<i32>,
+}
+
+/// Executes everything that only depends on known inputs and memory patches ahead of time.
+///
+/// Instructions that depend on unknown values are emitted into a residual block that is
+/// appended to memory. Analysis stops at the first conditional jump, jump target or
+/// instruction that depends on an unknown value, and the residual block hands control back
+/// to the original code from there. It also stops at the first `arb`, so relative parameters
+/// are analysed with the relative base at 0 and the original code resumes with the relative
+/// base it expects.
+///
+/// Memory cannot grow, so an access past its end makes the original program fail. The residual
+/// block is only appended when every instruction the original code may run after it accesses
+/// memory at fixed addresses below the end, and so cannot reach the residual block. Otherwise
+/// the program is returned with only the patches applied, to run from 0 with every input.
+pub struct Specialiser {
+    max_steps: usize,
+}
+
+impl Default for Specialiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Specialiser {
+    pub fn new() -> Self {
+        Self::with_max_steps(DEFAULT_MAX_STEPS)
+    }
+
+    pub fn with_max_steps(max_steps: usize) -> Self {
+        Self { max_steps }
+    }
+
+    pub fn specialise(
+        &self,
+        program: &[i32],
+        patches: &[(usize, i32)],
+        inputs: &[i32],
+    ) -> Result<Specialisation, Cow<'static, str>> {
+        let mut analysis = Analysis::new(program, inputs);
+        let mut patched = program.to_vec();
+
+        for (address, value) in patches {
+            if !analysis.store_known(*address, *value) {
+                return Err(format!("Patched address {} is out of bounds", address).into());
+            }
+            patched[*address] = *value;
+        }
+
+        let mut idx = 0;
+        let mut halted = false;
+
+        for _ in 0..self.max_steps {
+            let op_code = match analysis.decode(idx) {
+                Some(op_code) => op_code,
+                None => break,
+            };
+
+            let completed = match &op_code {
+                Operation::Add {
+                    addend_1,
+                    addend_2,
+                    destination,
+                } => analysis.binary(1, addend_1, addend_2, destination, |a, b| a.checked_add(b)),
+                Operation::Multiply {
+                    factor_1,
+                    factor_2,
+                    destination,
+                } => analysis.binary(2, factor_1, factor_2, destination, |a, b| a.checked_mul(b)),
+                Operation::LessThan {
+                    value_1,
+                    value_2,
+                    destination,
+                } => analysis.binary(7, value_1, value_2, destination, |a, b| {
+                    Some((a < b) as i32)
+                }),
+                Operation::Equals {
+                    value_1,
+                    value_2,
+                    destination,
+                } => analysis.binary(8, value_1, value_2, destination, |a, b| {
+                    Some((a == b) as i32)
+                }),
+                Operation::Input { destination } => analysis.input(destination),
+                Operation::Output { source } => analysis.output(source),
+                Operation::JumpIfTrue {
+                    condition,
+                    location,
+                } => match analysis.jump(condition, location, |value| value != 0) {
+                    Jump::Taken(location) => {
+                        idx = location;
+                        continue;
+                    }
+                    Jump::NotTaken => true,
+                    Jump::Unknown => false,
+                },
+                Operation::JumpIfFalse {
+                    condition,
+                    location,
+                } => match analysis.jump(condition, location, |value| value == 0) {
+                    Jump::Taken(location) => {
+                        idx = location;
+                        continue;
+                    }
+                    Jump::NotTaken => true,
+                    Jump::Unknown => false,
+                },
+                Operation::AdjustRelativeBase { .. } => false,
+                Operation::Exit => {
+                    halted = true;
+                    false
+                }
+            };
+
+            if !completed {
+                break;
+            }
+
+            idx += op_code.op_len();
+        }
+
+        Ok(analysis
+            .finish(idx, halted)
+            .unwrap_or_else(|| Specialisation {
+                memory: patched,
+                entry_point: 0,
+                inputs: inputs.to_vec(),
+            }))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Known(i32),
+    Dynamic(usize),
+}
+
+enum Jump {
+    Taken(usize),
+    NotTaken,
+    Unknown,
+}
+
+struct Analysis<'a> {
+    known: Vec<Option<i32>>,
+    snapshot: Vec<i32>,
+    written_at_runtime: Vec<bool>,
+    residual: Vec<i32>,
+    inputs: std::slice::Iter<'a, i32>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(program: &[i32], inputs: &'a [i32]) -> Self {
+        Self {
+            known: program.iter().copied().map(Some).collect(),
+            snapshot: program.to_vec(),
+            written_at_runtime: vec![false; program.len()],
+            residual: Vec::new(),
+            inputs: inputs.iter(),
+        }
+    }
+
+    fn decode(&self, idx: usize) -> Option<Operation> {
+        let words: Vec<i32> = self
+            .known
+            .get(idx..)?
+            .iter()
+            .take(4)
+            .map_while(|word| *word)
+            .collect();
+
+        Operation::from_slice(&words).ok()
+    }
+
+    /// The address a position or relative parameter refers to, with the relative base at 0.
+    fn address(parameter: &Parameter) -> Option<usize> {
+        match parameter {
+            Parameter::Address(address) => Some(*address),
+            Parameter::Relative(offset) => usize::try_from(*offset).ok(),
+            Parameter::Value(_) => None,
+        }
+    }
+
+    fn operand(&self, parameter: &Parameter) -> Option<Operand> {
+        if let Parameter::Value(value) = parameter {
+            return Some(Operand::Known(*value));
+        }
+
+        let address = Self::address(parameter)?;
+        match self.known.get(address)? {
+            Some(value) => Some(Operand::Known(*value)),
+            None => Some(Operand::Dynamic(address)),
+        }
+    }
+
+    fn binary<F>(
+        &mut self,
+        operation: i32,
+        parameter_1: &Parameter,
+        parameter_2: &Parameter,
+        destination: &Parameter,
+        evaluate: F,
+    ) -> bool
+    where
+        F: FnOnce(i32, i32) -> Option<i32>,
+    {
+        let destination_address = match Self::address(destination) {
+            Some(address) if address < self.known.len() => address,
+            _ => return false,
+        };
+
+        match (self.operand(parameter_1), self.operand(parameter_2)) {
+            (Some(Operand::Known(value_1)), Some(Operand::Known(value_2))) => {
+                match evaluate(value_1, value_2) {
+                    Some(result) => self.store_known(destination_address, result),
+                    None => false,
+                }
+            }
+            (Some(operand_1), Some(operand_2)) => {
+                self.emit(
+                    operation,
+                    &[operand_1, operand_2],
+                    Some(destination_address),
+                );
+                self.store_dynamic(destination_address);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn input(&mut self, destination: &Parameter) -> bool {
+        let destination_address = match Self::address(destination) {
+            Some(address) if address < self.known.len() => address,
+            _ => return false,
+        };
+
+        match self.inputs.next() {
+            Some(value) => self.store_known(destination_address, *value),
+            None => {
+                self.emit(3, &[], Some(destination_address));
+                self.store_dynamic(destination_address);
+                true
+            }
+        }
+    }
+
+    fn output(&mut self, source: &Parameter) -> bool {
+        match self.operand(source) {
+            Some(operand) => {
+                self.emit(4, &[operand], None);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn jump<F>(&self, condition: &Parameter, location: &Parameter, is_taken: F) -> Jump
+    where
+        F: FnOnce(i32) -> bool,
+    {
+        match self.operand(condition) {
+            Some(Operand::Known(value)) if is_taken(value) => match self.operand(location) {
+                Some(Operand::Known(location)) if location >= 0 => Jump::Taken(location as usize),
+                _ => Jump::Unknown,
+            },
+            Some(Operand::Known(_)) => Jump::NotTaken,
+            _ => Jump::Unknown,
+        }
+    }
+
+    fn store_known(&mut self, address: usize, value: i32) -> bool {
+        if address >= self.known.len() {
+            return false;
+        }
+
+        // Once residual code has written a cell, later writes have to happen at runtime
+        // as well, otherwise the residual write would clobber them.
+        if self.written_at_runtime[address] {
+            self.emit(
+                1,
+                &[Operand::Known(value), Operand::Known(0)],
+                Some(address),
+            );
+        } else {
+            self.snapshot[address] = value;
+        }
+
+        self.known[address] = Some(value);
+        true
+    }
+
+    fn store_dynamic(&mut self, address: usize) {
+        self.known[address] = None;
+        self.written_at_runtime[address] = true;
+    }
+
+    fn emit(&mut self, operation: i32, operands: &[Operand], destination_address: Option<usize>) {
+        let op_code = operands
+            .iter()
+            .enumerate()
+            .fold(operation, |op_code, (idx, operand)| match operand {
+                Operand::Known(_) => op_code + 100 * i32::pow(10, idx as u32),
+                Operand::Dynamic(_) => op_code,
+            });
+
+        self.residual.push(op_code);
+        for operand in operands {
+            self.residual.push(match operand {
+                Operand::Known(value) => *value,
+                Operand::Dynamic(address) => *address as i32,
+            });
+        }
+        if let Some(destination_address) = destination_address {
+            self.residual.push(destination_address as i32);
+        }
+    }
+
+    /// Whether the original code that may run from `idx` on only accesses memory at fixed
+    /// addresses below its end, so that it cannot reach a residual block appended to it.
+    fn stays_in_bounds(&self, idx: usize) -> bool {
+        let memory = &self.snapshot;
+        let written_at_runtime = (0..memory.len())
+            .filter(|&address| self.written_at_runtime[address])
+            .collect();
+        let report = validator::validate_with_writes(memory, &[idx], written_at_runtime);
+        if !report.is_valid() || !report.rewritten.is_empty() {
+            return false;
+        }
+
+        report.instructions.iter().all(|&address| {
+            let operation = match Operation::from_slice(&memory[address..]) {
+                Ok(operation) => operation,
+                Err(_) => return false,
+            };
+            let is_jump = matches!(
+                operation,
+                Operation::JumpIfTrue { .. } | Operation::JumpIfFalse { .. }
+            );
+
+            // A written immediate operand only changes a value, but a written opcode, address
+            // or jump target could send the access anywhere.
+            !report.writes.contains(&address)
+                && operation
+                    .parameters()
+                    .into_iter()
+                    .chain(operation.destination())
+                    .enumerate()
+                    .all(|(idx, parameter)| {
+                        let written = report.writes.contains(&(address + idx + 1));
+                        match parameter {
+                            Parameter::Address(target) => !written && *target < memory.len(),
+                            Parameter::Value(_) => !(written && is_jump && idx == 1),
+                            Parameter::Relative(_) => false,
+                        }
+                    })
+        })
+    }
+
+    fn finish(mut self, idx: usize, halted: bool) -> Option<Specialisation> {
+        let inputs = self.inputs.as_slice().to_vec();
+
+        if self.residual.is_empty() {
+            return Some(Specialisation {
+                memory: self.snapshot,
+                entry_point: idx,
+                inputs,
+            });
+        }
+
+        if halted {
+            self.residual.push(99);
+        } else if self.stays_in_bounds(idx) {
+            self.residual.extend_from_slice(&[1105, 1, idx as i32]);
+        } else {
+            return None;
+        }
+
+        let entry_point = self.snapshot.len();
+        self.snapshot.extend(self.residual);
+
+        Some(Specialisation {
+            memory: self.snapshot,
+            entry_point,
+            inputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::Program;
+
+    fn run_specialisation(specialisation: &Specialisation, input: Vec<i32>, output: Vec<i32>) {
+        let mut input = UnitTestInput::new(input);
+        let mut output = UnitTestOutput::new(output);
+
+        let mut program = Program::with_entry_point(
+            specialisation.memory.clone(),
+            specialisation.entry_point,
+            &mut input,
+            &mut output,
+        );
+        let result = program.run();
+
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn fully_known_program_is_evaluated() {
+        let specialisation = Specialiser::new()
+            .specialise(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[], &[])
+            .unwrap();
+
+        assert_eq!(
+            specialisation,
+            Specialisation {
+                memory: vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
+                entry_point: 8,
+                inputs: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn patches_are_applied_before_evaluation() {
+        let specialisation = Specialiser::new()
+            .specialise(&[1, 0, 0, 0, 99], &[(1, 4), (2, 4)], &[])
+            .unwrap();
+
+        assert_eq!(specialisation.memory, [198, 4, 4, 0, 99]);
+        assert_eq!(specialisation.entry_point, 4);
+    }
+
+    #[test]
+    fn patch_out_of_bounds() {
+        let specialisation = Specialiser::new().specialise(&[99], &[(1, 4)], &[]);
+
+        assert!(specialisation.is_err());
+    }
+
+    #[test]
+    fn known_outputs_are_replayed() {
+        let specialisation = Specialiser::new()
+            .specialise(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], &[], &[8])
+            .unwrap();
+
+        assert_eq!(specialisation.memory[11..], [104, 1, 99]);
+        run_specialisation(&specialisation, vec![], vec![1]);
+    }
+
+    #[test]
+    fn amplifier_with_known_phase_setting() {
+        let program = [
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        let specialisation = Specialiser::new().specialise(&program, &[], &[4]).unwrap();
+
+        assert_eq!(
+            specialisation.memory[program.len()..],
+            [3, 16, 1002, 16, 10, 16, 1001, 16, 4, 15, 4, 15, 99]
+        );
+        run_specialisation(&specialisation, vec![3], vec![34]);
+    }
+
+    #[test]
+    fn unknown_condition_hands_over_to_original_code() {
+        let program = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+
+        let specialisation = Specialiser::new().specialise(&program, &[], &[]).unwrap();
+        assert_eq!(specialisation.entry_point, program.len());
+        assert_eq!(specialisation.memory[program.len()..], [3, 3, 1105, 1, 2]);
+
+        run_specialisation(&specialisation, vec![0], vec![0]);
+        run_specialisation(&specialisation, vec![5], vec![1]);
+    }
+
+    #[test]
+    fn access_past_the_end_keeps_failing() {
+        let program = [3, 11, 1005, 11, 7, 4, 12, 99, 0, 0, 0, 0];
+        let specialisation = Specialiser::new().specialise(&program, &[], &[]).unwrap();
+
+        assert_eq!(
+            specialisation,
+            Specialisation {
+                memory: program.to_vec(),
+                entry_point: 0,
+                inputs: vec![],
+            }
+        );
+
+        let mut input = UnitTestInput::new(vec![0]);
+        let mut output = UnitTestOutput::new(vec![]);
+        let mut program = Program::new(specialisation.memory, &mut input, &mut output);
+        assert_eq!(program.run(), Err("Address 12 is out of bounds".into()));
+    }
+
+    #[test]
+    fn relative_access_after_the_residual_is_not_specialised() {
+        let program = [1101, 1, 2, 13, 3, 12, 1005, 12, 11, 204, 13, 99, 0, 0];
+        let specialisation = Specialiser::new().specialise(&program, &[], &[]).unwrap();
+
+        assert_eq!(specialisation.memory, program);
+        assert_eq!(specialisation.entry_point, 0);
+        run_specialisation(&specialisation, vec![0], vec![3]);
+    }
+
+    #[test]
+    fn known_store_after_runtime_store_is_emitted() {
+        let program = [3, 9, 1101, 1, 2, 9, 4, 9, 99, 0];
+        let specialisation = Specialiser::new().specialise(&program, &[], &[]).unwrap();
+
+        assert_eq!(
+            specialisation.memory[program.len()..],
+            [3, 9, 1101, 3, 0, 9, 104, 3, 99]
+        );
+        run_specialisation(&specialisation, vec![7], vec![3]);
+    }
+
+    #[test]
+    fn analysis_stops_at_the_first_relative_base_adjustment() {
+        let program = [21101, 2, 3, 9, 109, 1, 204, 8, 99, 0];
+        let specialisation = Specialiser::new().specialise(&program, &[], &[]).unwrap();
+
+        assert_eq!(
+            specialisation,
+            Specialisation {
+                memory: vec![21101, 2, 3, 9, 109, 1, 204, 8, 99, 5],
+                entry_point: 4,
+                inputs: vec![],
+            }
+        );
+        run_specialisation(&specialisation, vec![], vec![5]);
+    }
+
+    #[test]
+    fn inputs_after_the_analysis_stopped_are_handed_over() {
+        let program = [1101, 2, 3, 11, 109, 1, 3, 11, 4, 11, 99, 0];
+        let specialisation = Specialiser::new().specialise(&program, &[], &[7]).unwrap();
+
+        assert_eq!(specialisation.entry_point, 4);
+        assert_eq!(specialisation.inputs, [7]);
+        run_specialisation(&specialisation, specialisation.inputs.clone(), vec![7]);
+    }
+
+    #[test]
+    fn step_budget_stops_analysis() {
+        let specialisation = Specialiser::with_max_steps(10)
+            .specialise(&[1105, 1, 0], &[], &[])
+            .unwrap();
+
+        assert_eq!(
+            specialisation,
+            Specialisation {
+                memory: vec![1105, 1, 0],
+                entry_point: 0,
+                inputs: vec![],
+            }
+        );
+    }
+}
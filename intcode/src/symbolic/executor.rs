@@ -0,0 +1,531 @@
+use super::{linear, Assignment, Expression, Symbol};
+use crate::{operations::Parameter, Operation};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::rc::Rc;
+
+const DEFAULT_MAX_STEPS: usize = 100_000;
+const DEFAULT_MAX_PATHS: usize = 1_000;
+
+/// A branch decision on a path: `condition != 0` equals `holds`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub condition: Rc<Expression>,
+    pub holds: bool,
+}
+
+impl Constraint {
+    pub fn is_satisfied(&self, assignment: &Assignment) -> bool {
+        self.condition
+            .evaluate(assignment)
+            .is_some_and(|value| (value != 0) == self.holds)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Termination {
+    Halted,
+    StepLimit,
+    PathLimit,
+    Unsupported(Cow<'static, str>),
+}
+
+#[derive(Debug)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Rc<Expression>>,
+    pub memory: Vec<Rc<Expression>>,
+    pub termination: Termination,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Observation {
+    Cell(usize),
+    Output(usize),
+}
+
+/// Runs a program with symbolic inputs and memory cells, forking at every conditional jump
+/// whose condition depends on them.
+pub struct SymbolicExecutor {
+    memory: Vec<Rc<Expression>>,
+    max_steps: usize,
+    max_paths: usize,
+}
+
+struct State {
+    idx: usize,
+    relative_base: i32,
+    steps: usize,
+    inputs: usize,
+    memory: Vec<Rc<Expression>>,
+    constraints: Vec<Constraint>,
+    outputs: Vec<Rc<Expression>>,
+}
+
+enum Step {
+    Continue,
+    Fork(State),
+    Done(Termination),
+}
+
+impl SymbolicExecutor {
+    pub fn new(program: &[i32], symbolic_cells: &[usize]) -> Self {
+        Self::with_limits(
+            program,
+            symbolic_cells,
+            DEFAULT_MAX_STEPS,
+            DEFAULT_MAX_PATHS,
+        )
+    }
+
+    pub fn with_limits(
+        program: &[i32],
+        symbolic_cells: &[usize],
+        max_steps: usize,
+        max_paths: usize,
+    ) -> Self {
+        let memory = program
+            .iter()
+            .enumerate()
+            .map(|(address, value)| {
+                if symbolic_cells.contains(&address) {
+                    Rc::new(Expression::Symbol(Symbol::Cell(address)))
+                } else {
+                    Rc::new(Expression::Constant(*value))
+                }
+            })
+            .collect();
+
+        Self {
+            memory,
+            max_steps,
+            max_paths,
+        }
+    }
+
+    pub fn explore(&self) -> Vec<Path> {
+        let mut paths = Vec::new();
+        let mut pending = vec![State {
+            idx: 0,
+            relative_base: 0,
+            steps: 0,
+            inputs: 0,
+            memory: self.memory.clone(),
+            constraints: Vec::new(),
+            outputs: Vec::new(),
+        }];
+
+        while let Some(mut state) = pending.pop() {
+            let termination = loop {
+                if state.steps >= self.max_steps {
+                    break Termination::StepLimit;
+                }
+                state.steps += 1;
+
+                match state.step() {
+                    Step::Continue => {}
+                    Step::Fork(forked) => {
+                        if paths.len() + pending.len() + 2 > self.max_paths {
+                            break Termination::PathLimit;
+                        }
+                        pending.push(forked);
+                    }
+                    Step::Done(termination) => break termination,
+                }
+            };
+
+            paths.push(Path {
+                constraints: state.constraints,
+                outputs: state.outputs,
+                memory: state.memory,
+                termination,
+            });
+        }
+
+        paths
+    }
+
+    /// Finds every assignment of the domain symbols for which the observed value equals
+    /// `target` on a path that halts.
+    pub fn solve(
+        &self,
+        observation: Observation,
+        target: i32,
+        domains: &[(Symbol, Range<i32>)],
+    ) -> Vec<Assignment> {
+        let mut solutions = Vec::new();
+
+        for path in self.explore() {
+            if path.termination != Termination::Halted {
+                continue;
+            }
+
+            let expression = match observation {
+                Observation::Cell(address) => path.memory.get(address),
+                Observation::Output(idx) => path.outputs.get(idx),
+            };
+
+            if let Some(expression) = expression {
+                solutions.extend(linear::solve(expression, target, domains, |assignment| {
+                    path.constraints
+                        .iter()
+                        .all(|constraint| constraint.is_satisfied(assignment))
+                }));
+            }
+        }
+
+        solutions.sort();
+        solutions.dedup();
+        solutions
+    }
+}
+
+impl State {
+    fn step(&mut self) -> Step {
+        let words: Vec<i32> = match self.memory.get(self.idx..) {
+            Some(words) => words
+                .iter()
+                .take(4)
+                .map(|word| word.constant().unwrap_or(0))
+                .collect(),
+            None => return Step::Done(Termination::Unsupported("Jumped out of memory".into())),
+        };
+
+        if self.memory[self.idx].constant().is_none() {
+            return Step::Done(Termination::Unsupported(
+                format!("Symbolic instruction at {}", self.idx).into(),
+            ));
+        }
+
+        let op_code = match Operation::from_slice(&words) {
+            Ok(op_code) => op_code,
+            Err(error) => return Step::Done(Termination::Unsupported(error)),
+        };
+
+        let parameters = op_code.parameters();
+        let mut operands = Vec::with_capacity(parameters.len());
+        for (idx, parameter) in parameters.into_iter().enumerate() {
+            match self.operand(idx, parameter) {
+                Some(operand) => operands.push(operand),
+                None => {
+                    return Step::Done(Termination::Unsupported(
+                        format!("Out of bounds read at {}", self.idx).into(),
+                    ))
+                }
+            }
+        }
+
+        let destination_address = match op_code.destination() {
+            Some(destination) => {
+                let destination_word = &self.memory[self.idx + operands.len() + 1];
+                if destination_word.constant().is_none() {
+                    return Step::Done(Termination::Unsupported(
+                        format!("Symbolic destination at {}", self.idx).into(),
+                    ));
+                }
+                match self.address(destination) {
+                    Some(address) => Some(address),
+                    None => {
+                        return Step::Done(Termination::Unsupported(
+                            format!("Out of bounds write at {}", self.idx).into(),
+                        ))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let result = match &op_code {
+            Operation::Add { .. } => {
+                Some(Expression::sum(operands[0].clone(), operands[1].clone()))
+            }
+            Operation::Multiply { .. } => Some(Expression::product(
+                operands[0].clone(),
+                operands[1].clone(),
+            )),
+            Operation::LessThan { .. } => Some(Expression::less_than(
+                operands[0].clone(),
+                operands[1].clone(),
+            )),
+            Operation::Equals { .. } => {
+                Some(Expression::equals(operands[0].clone(), operands[1].clone()))
+            }
+            Operation::Input { .. } => {
+                let input = Symbol::Input(self.inputs);
+                self.inputs += 1;
+                Some(Rc::new(Expression::Symbol(input)))
+            }
+            Operation::Output { .. } => {
+                self.outputs.push(operands[0].clone());
+                None
+            }
+            Operation::JumpIfTrue { .. } | Operation::JumpIfFalse { .. } => {
+                return self.jump(&op_code, &operands[0], &operands[1]);
+            }
+            Operation::AdjustRelativeBase { .. } => {
+                match operands[0]
+                    .constant()
+                    .and_then(|adjustment| self.relative_base.checked_add(adjustment))
+                {
+                    Some(relative_base) => self.relative_base = relative_base,
+                    None => {
+                        return Step::Done(Termination::Unsupported(
+                            format!("Symbolic relative base at {}", self.idx).into(),
+                        ))
+                    }
+                }
+                None
+            }
+            Operation::Exit => return Step::Done(Termination::Halted),
+        };
+
+        if let (Some(result), Some(destination_address)) = (result, destination_address) {
+            match self.memory.get_mut(destination_address) {
+                Some(cell) => *cell = result,
+                None => {
+                    return Step::Done(Termination::Unsupported(
+                        format!("Out of bounds write at {}", self.idx).into(),
+                    ))
+                }
+            }
+        }
+
+        self.idx += op_code.op_len();
+        Step::Continue
+    }
+
+    fn operand(&self, idx: usize, parameter: &Parameter) -> Option<Rc<Expression>> {
+        let word = self.memory.get(self.idx + idx + 1)?;
+
+        match (parameter, word.constant()) {
+            (Parameter::Value(value), Some(_)) => Some(Rc::new(Expression::Constant(*value))),
+            (Parameter::Value(_), None) => Some(word.clone()),
+            (Parameter::Address(address), Some(_)) => self.memory.get(*address).cloned(),
+            (Parameter::Address(_), None) => Some(Rc::new(Expression::Load {
+                address: word.clone(),
+                memory: Rc::new(self.memory.clone()),
+            })),
+            (Parameter::Relative(_), Some(_)) => self.memory.get(self.address(parameter)?).cloned(),
+            (Parameter::Relative(_), None) => Some(Rc::new(Expression::Load {
+                address: Expression::sum(
+                    word.clone(),
+                    Rc::new(Expression::Constant(self.relative_base)),
+                ),
+                memory: Rc::new(self.memory.clone()),
+            })),
+        }
+    }
+
+    /// The address a position or relative parameter refers to, if it is not negative.
+    fn address(&self, parameter: &Parameter) -> Option<usize> {
+        match parameter {
+            Parameter::Address(address) => Some(*address),
+            Parameter::Relative(offset) => {
+                usize::try_from(i64::from(self.relative_base) + i64::from(*offset)).ok()
+            }
+            Parameter::Value(_) => None,
+        }
+    }
+
+    fn jump(
+        &mut self,
+        op_code: &Operation,
+        condition: &Rc<Expression>,
+        location: &Rc<Expression>,
+    ) -> Step {
+        let jump_if_true = matches!(op_code, Operation::JumpIfTrue { .. });
+        let location = match location.constant() {
+            Some(location) if location >= 0 => location as usize,
+            _ => {
+                return Step::Done(Termination::Unsupported(
+                    format!("Symbolic jump target at {}", self.idx).into(),
+                ))
+            }
+        };
+        let next = self.idx + op_code.op_len();
+
+        match condition.constant() {
+            Some(value) => {
+                self.idx = if (value != 0) == jump_if_true {
+                    location
+                } else {
+                    next
+                };
+                Step::Continue
+            }
+            None => {
+                let taken = Constraint {
+                    condition: condition.clone(),
+                    holds: jump_if_true,
+                };
+                let not_taken = Constraint {
+                    condition: condition.clone(),
+                    holds: !jump_if_true,
+                };
+
+                if self.constraints.contains(&not_taken) {
+                    self.idx = next;
+                    return Step::Continue;
+                }
+                if self.constraints.contains(&taken) {
+                    self.idx = location;
+                    return Step::Continue;
+                }
+
+                let mut forked = State {
+                    idx: next,
+                    relative_base: self.relative_base,
+                    steps: self.steps,
+                    inputs: self.inputs,
+                    memory: self.memory.clone(),
+                    constraints: self.constraints.clone(),
+                    outputs: self.outputs.clone(),
+                };
+                forked.constraints.push(not_taken);
+
+                self.idx = location;
+                self.constraints.push(taken);
+
+                Step::Fork(forked)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day_02_like_program() -> Vec<i32> {
+        vec![
+            1, 0, 0, 3, 2, 1, 20, 0, 1, 0, 2, 0, 1, 0, 21, 0, 99, 0, 0, 0, 100, 7,
+        ]
+    }
+
+    #[test]
+    fn output_cell_is_linear_in_noun_and_verb() {
+        let executor = SymbolicExecutor::new(&day_02_like_program(), &[1, 2]);
+        let paths = executor.explore();
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].termination, Termination::Halted);
+
+        let mut assignment = Assignment::new();
+        assignment.insert(Symbol::Cell(1), 12);
+        assignment.insert(Symbol::Cell(2), 27);
+        assert_eq!(paths[0].memory[0].evaluate(&assignment), Some(1234));
+    }
+
+    #[test]
+    fn solve_noun_and_verb() {
+        let executor = SymbolicExecutor::new(&day_02_like_program(), &[1, 2]);
+        let domains = [(Symbol::Cell(1), 0..100), (Symbol::Cell(2), 0..100)];
+
+        let solutions = executor.solve(Observation::Cell(0), 1234, &domains);
+
+        let mut expected = Assignment::new();
+        expected.insert(Symbol::Cell(1), 12);
+        expected.insert(Symbol::Cell(2), 27);
+        assert_eq!(solutions, vec![expected]);
+    }
+
+    #[test]
+    fn conditional_jump_forks() {
+        let executor =
+            SymbolicExecutor::new(&[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1], &[]);
+        let paths = executor.explore();
+
+        assert_eq!(paths.len(), 2);
+        assert!(paths
+            .iter()
+            .all(|path| path.termination == Termination::Halted));
+
+        let mut assignment = Assignment::new();
+        for (input, expected_output) in &[(0, 0), (5, 1)] {
+            assignment.insert(Symbol::Input(0), *input);
+
+            let path = paths
+                .iter()
+                .find(|path| {
+                    path.constraints
+                        .iter()
+                        .all(|constraint| constraint.is_satisfied(&assignment))
+                })
+                .unwrap();
+            assert_eq!(
+                path.outputs[0].evaluate(&assignment),
+                Some(*expected_output)
+            );
+        }
+    }
+
+    #[test]
+    fn solve_for_output() {
+        let program = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        let executor = SymbolicExecutor::new(&program, &[]);
+        let domains = [(Symbol::Input(0), -100..100)];
+
+        let solutions = executor.solve(Observation::Output(0), 999, &domains);
+
+        assert_eq!(solutions.len(), 108);
+        assert!(solutions
+            .iter()
+            .all(|assignment| assignment[&Symbol::Input(0)] < 8));
+    }
+
+    #[test]
+    fn relative_parameters_follow_the_relative_base() {
+        let program = [109, 11, 203, 0, 22201, 0, 0, 1, 204, 1, 99, 0, 0];
+        let executor = SymbolicExecutor::new(&program, &[]);
+        let domains = [(Symbol::Input(0), -100..100)];
+
+        let solutions = executor.solve(Observation::Output(0), 14, &domains);
+
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0][&Symbol::Input(0)], 7);
+
+        let executor = SymbolicExecutor::new(&[109, 5, 99, 0, 0, 0], &[1]);
+        let paths = executor.explore();
+
+        assert!(matches!(paths[0].termination, Termination::Unsupported(_)));
+    }
+
+    #[test]
+    fn step_limit() {
+        let executor = SymbolicExecutor::with_limits(&[1105, 1, 0], &[], 10, 10);
+        let paths = executor.explore();
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].termination, Termination::StepLimit);
+    }
+
+    #[test]
+    fn path_limit() {
+        let executor =
+            SymbolicExecutor::with_limits(&[3, 10, 1005, 10, 0, 1105, 1, 0, 0, 0, 0], &[], 100, 4);
+        let paths = executor.explore();
+
+        assert!(paths.len() <= 4);
+        assert!(paths
+            .iter()
+            .any(|path| path.termination == Termination::PathLimit));
+    }
+
+    #[test]
+    fn symbolic_destination_is_unsupported() {
+        let executor = SymbolicExecutor::new(&[3, 3, 99, 0], &[]);
+        let paths = executor.explore();
+
+        assert_eq!(paths[0].termination, Termination::Halted);
+
+        let executor = SymbolicExecutor::new(&[1101, 1, 1, 0, 99], &[3]);
+        let paths = executor.explore();
+
+        assert!(matches!(paths[0].termination, Termination::Unsupported(_)));
+    }
+}
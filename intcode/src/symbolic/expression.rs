@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symbol {
+    Input(usize),
+    Cell(usize),
+}
+
+pub type Assignment = BTreeMap<Symbol, i32>;
+
+#[derive(Debug, PartialEq)]
+pub enum Expression {
+    Constant(i32),
+    Symbol(Symbol),
+    Add(Rc<Expression>, Rc<Expression>),
+    Multiply(Rc<Expression>, Rc<Expression>),
+    LessThan(Rc<Expression>, Rc<Expression>),
+    Equals(Rc<Expression>, Rc<Expression>),
+    Load {
+        address: Rc<Expression>,
+        memory: Rc<Vec<Rc<Expression>>>,
+    },
+}
+
+impl Expression {
+    pub fn constant(&self) -> Option<i32> {
+        match self {
+            Expression::Constant(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn sum(lhs: Rc<Expression>, rhs: Rc<Expression>) -> Rc<Expression> {
+        match (lhs.constant(), rhs.constant()) {
+            (Some(lhs), Some(rhs)) if lhs.checked_add(rhs).is_some() => {
+                Rc::new(Expression::Constant(lhs + rhs))
+            }
+            (Some(0), _) => rhs,
+            (_, Some(0)) => lhs,
+            _ => Rc::new(Expression::Add(lhs, rhs)),
+        }
+    }
+
+    pub fn product(lhs: Rc<Expression>, rhs: Rc<Expression>) -> Rc<Expression> {
+        match (lhs.constant(), rhs.constant()) {
+            (Some(lhs), Some(rhs)) if lhs.checked_mul(rhs).is_some() => {
+                Rc::new(Expression::Constant(lhs * rhs))
+            }
+            (Some(0), _) if !rhs.can_fail() => lhs,
+            (_, Some(0)) if !lhs.can_fail() => rhs,
+            (Some(1), _) => rhs,
+            (_, Some(1)) => lhs,
+            _ => Rc::new(Expression::Multiply(lhs, rhs)),
+        }
+    }
+
+    pub fn less_than(lhs: Rc<Expression>, rhs: Rc<Expression>) -> Rc<Expression> {
+        match (lhs.constant(), rhs.constant()) {
+            (Some(lhs), Some(rhs)) => Rc::new(Expression::Constant((lhs < rhs) as i32)),
+            _ => Rc::new(Expression::LessThan(lhs, rhs)),
+        }
+    }
+
+    pub fn equals(lhs: Rc<Expression>, rhs: Rc<Expression>) -> Rc<Expression> {
+        match (lhs.constant(), rhs.constant()) {
+            (Some(lhs), Some(rhs)) => Rc::new(Expression::Constant((lhs == rhs) as i32)),
+            _ if lhs == rhs && !lhs.can_fail() => Rc::new(Expression::Constant(1)),
+            _ => Rc::new(Expression::Equals(lhs, rhs)),
+        }
+    }
+
+    /// Whether evaluating the expression can fail for a full assignment, which a product with 0
+    /// or a comparison of an expression with itself must not hide.
+    fn can_fail(&self) -> bool {
+        match self {
+            Expression::Constant(_) | Expression::Symbol(_) => false,
+            Expression::LessThan(lhs, rhs) | Expression::Equals(lhs, rhs) => {
+                lhs.can_fail() || rhs.can_fail()
+            }
+            Expression::Add(..) | Expression::Multiply(..) | Expression::Load { .. } => true,
+        }
+    }
+
+    /// Evaluates the expression with the same semantics as `Program`.
+    ///
+    /// Returns `None` when a symbol is unassigned, a load is out of bounds or the arithmetic
+    /// overflows, since the concrete run would fail in those cases as well.
+    pub fn evaluate(&self, assignment: &Assignment) -> Option<i32> {
+        match self {
+            Expression::Constant(value) => Some(*value),
+            Expression::Symbol(symbol) => assignment.get(symbol).copied(),
+            Expression::Add(lhs, rhs) => lhs
+                .evaluate(assignment)?
+                .checked_add(rhs.evaluate(assignment)?),
+            Expression::Multiply(lhs, rhs) => lhs
+                .evaluate(assignment)?
+                .checked_mul(rhs.evaluate(assignment)?),
+            Expression::LessThan(lhs, rhs) => {
+                Some((lhs.evaluate(assignment)? < rhs.evaluate(assignment)?) as i32)
+            }
+            Expression::Equals(lhs, rhs) => {
+                Some((lhs.evaluate(assignment)? == rhs.evaluate(assignment)?) as i32)
+            }
+            Expression::Load { address, memory } => {
+                let address = address.evaluate(assignment)?;
+                if address < 0 {
+                    return None;
+                }
+
+                memory.get(address as usize)?.evaluate(assignment)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(symbol: Symbol) -> Rc<Expression> {
+        Rc::new(Expression::Symbol(symbol))
+    }
+
+    fn constant(value: i32) -> Rc<Expression> {
+        Rc::new(Expression::Constant(value))
+    }
+
+    #[test]
+    fn constants_are_folded() {
+        let expression =
+            Expression::sum(constant(2), Expression::product(constant(3), constant(4)));
+
+        assert_eq!(*expression, Expression::Constant(14));
+    }
+
+    #[test]
+    fn identities_are_simplified() {
+        let noun = symbol(Symbol::Cell(1));
+
+        assert_eq!(Expression::sum(noun.clone(), constant(0)), noun);
+        assert_eq!(Expression::product(constant(1), noun.clone()), noun);
+        assert_eq!(
+            *Expression::product(noun.clone(), constant(0)),
+            Expression::Constant(0)
+        );
+        assert_eq!(
+            *Expression::equals(noun.clone(), noun),
+            Expression::Constant(1)
+        );
+    }
+
+    #[test]
+    fn products_with_zero_keep_failures() {
+        let noun = symbol(Symbol::Cell(1));
+        let overflowing = Expression::sum(noun.clone(), constant(i32::MAX));
+        let product = Expression::product(constant(0), overflowing);
+        let mut assignment = Assignment::new();
+        assignment.insert(Symbol::Cell(1), 1);
+
+        assert_eq!(product.evaluate(&assignment), None);
+        assert_eq!(
+            *Expression::product(constant(0), Expression::less_than(noun, constant(3))),
+            Expression::Constant(0)
+        );
+    }
+
+    #[test]
+    fn equal_sides_keep_failures() {
+        let noun = symbol(Symbol::Cell(1));
+        let overflowing = Expression::sum(noun.clone(), constant(i32::MAX));
+        let comparison = Expression::equals(overflowing.clone(), overflowing);
+        let mut assignment = Assignment::new();
+        assignment.insert(Symbol::Cell(1), 1);
+
+        assert_eq!(comparison.evaluate(&assignment), None);
+        assert_eq!(
+            *Expression::equals(noun.clone(), noun),
+            Expression::Constant(1)
+        );
+    }
+
+    #[test]
+    fn evaluate_with_assignment() {
+        let expression = Expression::less_than(
+            Expression::product(symbol(Symbol::Input(0)), constant(3)),
+            constant(10),
+        );
+        let mut assignment = Assignment::new();
+
+        assignment.insert(Symbol::Input(0), 3);
+        assert_eq!(expression.evaluate(&assignment), Some(1));
+
+        assignment.insert(Symbol::Input(0), 4);
+        assert_eq!(expression.evaluate(&assignment), Some(0));
+    }
+
+    #[test]
+    fn evaluate_load_uses_snapshot() {
+        let memory = Rc::new(vec![constant(7), symbol(Symbol::Input(0))]);
+        let expression = Expression::Load {
+            address: symbol(Symbol::Cell(1)),
+            memory,
+        };
+        let mut assignment = Assignment::new();
+        assignment.insert(Symbol::Input(0), 42);
+
+        assignment.insert(Symbol::Cell(1), 0);
+        assert_eq!(expression.evaluate(&assignment), Some(7));
+
+        assignment.insert(Symbol::Cell(1), 1);
+        assert_eq!(expression.evaluate(&assignment), Some(42));
+
+        assignment.insert(Symbol::Cell(1), 2);
+        assert_eq!(expression.evaluate(&assignment), None);
+    }
+
+    #[test]
+    fn evaluate_overflow() {
+        let expression = Expression::Add(symbol(Symbol::Input(0)), constant(1));
+        let mut assignment = Assignment::new();
+        assignment.insert(Symbol::Input(0), i32::MAX);
+
+        assert_eq!(expression.evaluate(&assignment), None);
+    }
+}
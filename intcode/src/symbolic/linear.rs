@@ -0,0 +1,278 @@
+use super::{Assignment, Expression, Symbol};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// An expression of the form `constant + sum(coefficient * symbol)`.
+#[derive(Debug, PartialEq)]
+pub struct Linear {
+    pub constant: i64,
+    pub coefficients: BTreeMap<Symbol, i64>,
+}
+
+impl Linear {
+    pub fn from_expression(expression: &Expression) -> Option<Self> {
+        match expression {
+            Expression::Constant(value) => Some(Self::constant(i64::from(*value))),
+            Expression::Symbol(symbol) => {
+                let mut coefficients = BTreeMap::new();
+                coefficients.insert(*symbol, 1);
+                Some(Self {
+                    constant: 0,
+                    coefficients,
+                })
+            }
+            Expression::Add(lhs, rhs) => {
+                Self::from_expression(lhs)?.plus(Self::from_expression(rhs)?)
+            }
+            Expression::Multiply(lhs, rhs) => {
+                let lhs = Self::from_expression(lhs)?;
+                let rhs = Self::from_expression(rhs)?;
+
+                if lhs.coefficients.is_empty() {
+                    rhs.scale(lhs.constant)
+                } else if rhs.coefficients.is_empty() {
+                    lhs.scale(rhs.constant)
+                } else {
+                    None
+                }
+            }
+            Expression::LessThan(..) | Expression::Equals(..) | Expression::Load { .. } => None,
+        }
+    }
+
+    /// Returns what the symbols missing from `assignment` have to add up to for the expression
+    /// to equal `target`, unless it overflows.
+    fn remaining(&self, target: i32, assignment: &Assignment) -> Option<i64> {
+        assignment.iter().try_fold(
+            i64::from(target).checked_sub(self.constant)?,
+            |remaining, (symbol, value)| {
+                let coefficient = self.coefficients.get(symbol).unwrap_or(&0);
+                remaining.checked_sub(coefficient.checked_mul(i64::from(*value))?)
+            },
+        )
+    }
+
+    fn constant(constant: i64) -> Self {
+        Self {
+            constant,
+            coefficients: BTreeMap::new(),
+        }
+    }
+
+    fn plus(mut self, other: Self) -> Option<Self> {
+        self.constant = self.constant.checked_add(other.constant)?;
+
+        for (symbol, coefficient) in other.coefficients {
+            let entry = self.coefficients.entry(symbol).or_insert(0);
+            *entry = entry.checked_add(coefficient)?;
+        }
+        self.coefficients.retain(|_, coefficient| *coefficient != 0);
+
+        Some(self)
+    }
+
+    fn scale(mut self, factor: i64) -> Option<Self> {
+        self.constant = self.constant.checked_mul(factor)?;
+
+        for coefficient in self.coefficients.values_mut() {
+            *coefficient = coefficient.checked_mul(factor)?;
+        }
+        self.coefficients.retain(|_, coefficient| *coefficient != 0);
+
+        Some(self)
+    }
+}
+
+/// Finds every assignment of the domain symbols for which `expression` evaluates to `target`
+/// and which `accept` agrees with.
+///
+/// Linear expressions are solved for one symbol, so only the remaining domains are
+/// enumerated. Other expressions fall back to evaluating every assignment.
+pub fn solve<F>(
+    expression: &Expression,
+    target: i32,
+    domains: &[(Symbol, Range<i32>)],
+    mut accept: F,
+) -> Vec<Assignment>
+where
+    F: FnMut(&Assignment) -> bool,
+{
+    let mut solutions = Vec::new();
+    let mut check = |assignment: &Assignment| {
+        if expression.evaluate(assignment) == Some(target) && accept(assignment) {
+            solutions.push(assignment.clone());
+        }
+    };
+
+    let linear = Linear::from_expression(expression).filter(|linear| {
+        linear
+            .coefficients
+            .keys()
+            .all(|symbol| domains.iter().any(|(domain, _)| domain == symbol))
+    });
+
+    let pivot = linear.as_ref().and_then(|linear| {
+        domains
+            .iter()
+            .enumerate()
+            .filter(|(_, (symbol, _))| linear.coefficients.contains_key(symbol))
+            .max_by_key(|(_, (_, range))| range.len())
+            .map(|(idx, _)| idx)
+    });
+
+    match (linear, pivot) {
+        (Some(linear), Some(pivot)) => {
+            let (pivot_symbol, pivot_range) = &domains[pivot];
+            let pivot_coefficient = linear.coefficients[pivot_symbol];
+            let others: Vec<_> = domains
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| *idx != pivot)
+                .map(|(_, domain)| domain.clone())
+                .collect();
+
+            for_each_assignment(&others, &mut Assignment::new(), &mut |assignment| {
+                // Sums too large for an i64 cannot add up to a cell, so they have no solution.
+                let remaining = match linear.remaining(target, assignment) {
+                    Some(remaining) => remaining,
+                    None => return,
+                };
+
+                if remaining % pivot_coefficient == 0 {
+                    let value = remaining / pivot_coefficient;
+                    if value >= i64::from(pivot_range.start) && value < i64::from(pivot_range.end) {
+                        assignment.insert(*pivot_symbol, value as i32);
+                        check(assignment);
+                        assignment.remove(pivot_symbol);
+                    }
+                }
+            });
+        }
+        _ => for_each_assignment(domains, &mut Assignment::new(), &mut |assignment| {
+            check(assignment)
+        }),
+    }
+
+    solutions.sort();
+    solutions
+}
+
+fn for_each_assignment<F>(
+    domains: &[(Symbol, Range<i32>)],
+    assignment: &mut Assignment,
+    callback: &mut F,
+) where
+    F: FnMut(&mut Assignment),
+{
+    match domains.split_first() {
+        Some(((symbol, range), rest)) => {
+            for value in range.clone() {
+                assignment.insert(*symbol, value);
+                for_each_assignment(rest, assignment, callback);
+            }
+            assignment.remove(symbol);
+        }
+        None => callback(assignment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    fn symbol(symbol: Symbol) -> Rc<Expression> {
+        Rc::new(Expression::Symbol(symbol))
+    }
+
+    fn constant(value: i32) -> Rc<Expression> {
+        Rc::new(Expression::Constant(value))
+    }
+
+    fn noun_and_verb() -> Rc<Expression> {
+        Expression::sum(
+            Expression::sum(
+                Expression::product(symbol(Symbol::Cell(1)), constant(100)),
+                symbol(Symbol::Cell(2)),
+            ),
+            constant(7),
+        )
+    }
+
+    #[test]
+    fn linear_form() {
+        let linear = Linear::from_expression(&noun_and_verb()).unwrap();
+
+        assert_eq!(linear.constant, 7);
+        assert_eq!(linear.coefficients[&Symbol::Cell(1)], 100);
+        assert_eq!(linear.coefficients[&Symbol::Cell(2)], 1);
+    }
+
+    #[test]
+    fn non_linear_form() {
+        let expression = Expression::product(symbol(Symbol::Cell(1)), symbol(Symbol::Cell(2)));
+
+        assert_eq!(Linear::from_expression(&expression), None);
+    }
+
+    #[test]
+    fn solve_linear() {
+        let domains = [(Symbol::Cell(1), 0..100), (Symbol::Cell(2), 0..100)];
+
+        let solutions = solve(&noun_and_verb(), 1234, &domains, |_| true);
+
+        let mut expected = Assignment::new();
+        expected.insert(Symbol::Cell(1), 12);
+        expected.insert(Symbol::Cell(2), 27);
+        assert_eq!(solutions, vec![expected]);
+    }
+
+    #[test]
+    fn solve_linear_with_many_solutions() {
+        let expression = Expression::sum(symbol(Symbol::Cell(1)), symbol(Symbol::Cell(2)));
+        let domains = [(Symbol::Cell(1), 0..10), (Symbol::Cell(2), 0..10)];
+
+        let solutions = solve(&expression, 5, &domains, |_| true);
+
+        assert_eq!(solutions.len(), 6);
+    }
+
+    #[test]
+    fn solve_respects_accept() {
+        let expression = Expression::sum(symbol(Symbol::Cell(1)), symbol(Symbol::Cell(2)));
+        let domains = [(Symbol::Cell(1), 0..10), (Symbol::Cell(2), 0..10)];
+
+        let solutions = solve(&expression, 5, &domains, |assignment| {
+            assignment[&Symbol::Cell(1)] > 3
+        });
+
+        assert_eq!(solutions.len(), 2);
+    }
+
+    #[test]
+    fn solve_linear_with_overflowing_terms() {
+        let huge = Expression::product(
+            constant(i32::MAX),
+            Expression::product(constant(i32::MAX), symbol(Symbol::Cell(2))),
+        );
+        let expression = Expression::sum(symbol(Symbol::Cell(1)), huge);
+        let domains = [(Symbol::Cell(1), 0..100), (Symbol::Cell(2), -4..4)];
+
+        let solutions = solve(&expression, 5, &domains, |_| true);
+
+        let mut expected = Assignment::new();
+        expected.insert(Symbol::Cell(1), 5);
+        expected.insert(Symbol::Cell(2), 0);
+        assert_eq!(solutions, vec![expected]);
+    }
+
+    #[test]
+    fn solve_non_linear() {
+        let expression = Expression::product(symbol(Symbol::Cell(1)), symbol(Symbol::Cell(2)));
+        let domains = [(Symbol::Cell(1), 0..10), (Symbol::Cell(2), 0..10)];
+
+        let solutions = solve(&expression, 12, &domains, |_| true);
+
+        assert_eq!(solutions.len(), 4);
+    }
+}
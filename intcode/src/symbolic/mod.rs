@@ -0,0 +1,7 @@
+mod executor;
+mod expression;
+mod linear;
+
+pub use executor::{Constraint, Observation, Path, SymbolicExecutor, Termination};
+pub use expression::{Assignment, Expression, Symbol};
+pub use linear::{solve, Linear};
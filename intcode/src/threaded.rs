@@ -0,0 +1,413 @@
+use super::{
+    io::{
+        channel::{self, ChannelReader, ChannelWriter},
+        LineReader, LineWriter,
+    },
+    Program,
+};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+#[derive(Debug, PartialEq)]
+pub enum Shutdown {
+    Halted,
+    /// The program was waiting for input after the program feeding it stopped.
+    InputClosed,
+    /// Every program that was still running was waiting for input, so none could continue.
+    Deadlocked,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub outputs: Vec<i32>,
+    pub shutdowns: Vec<Shutdown>,
+}
+
+type Worker = JoinHandle<(Result<Shutdown, Cow<'static, str>>, ChannelReader)>;
+
+/// What the programs know about each other, which is only changed while locked, so that a
+/// reader always sees every value that was sent before it checks for a deadlock.
+struct State {
+    running: Vec<bool>,
+    waiting: Vec<bool>,
+    /// How many values were sent to each program, and to the output, but not read yet.
+    pending: Vec<usize>,
+    /// The program feeding each program, if any.
+    upstreams: Vec<Option<usize>>,
+    /// A writer to the input of every program that is fed by another one, which wakes it up
+    /// after a deadlock. It is dropped once the upstream stopped, so that the channel closes.
+    wakers: Vec<Option<ChannelWriter>>,
+    deadlocked: bool,
+}
+
+impl State {
+    /// Whether every program that still runs waits for a value that no other program can send.
+    fn is_deadlocked(&self) -> bool {
+        (0..self.running.len()).all(|idx| {
+            !self.running[idx]
+                || self.waiting[idx]
+                    && self.pending[idx] == 0
+                    && self.upstreams[idx].is_some_and(|upstream| self.running[upstream])
+        })
+    }
+
+    /// Wakes up every waiting program after a deadlock, which then finds nothing to read.
+    fn wake(&mut self) {
+        for (waiting, waker) in self.waiting.iter().zip(&mut self.wakers) {
+            if let (true, Some(waker)) = (waiting, waker) {
+                // The reader is still alive, as the threads return it when they stop.
+                let _ = waker.write_line(0);
+            }
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A worker that panicked while holding the lock left the state consistent.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn stop(&self, idx: usize, target: usize) {
+        let mut state = self.lock();
+        state.running[idx] = false;
+        if let Some(waker) = state.wakers.get_mut(target) {
+            *waker = None;
+        }
+    }
+}
+
+struct Reader {
+    input: ChannelReader,
+    shared: Arc<Shared>,
+    idx: usize,
+    shutdown: Option<Shutdown>,
+}
+
+impl LineReader for Reader {
+    fn read_line(&mut self) -> Result<i32, Cow<'static, str>> {
+        {
+            let mut state = self.shared.lock();
+            state.waiting[self.idx] = true;
+            if state.deadlocked || state.is_deadlocked() {
+                state.waiting[self.idx] = false;
+                if !state.deadlocked {
+                    state.deadlocked = true;
+                    state.wake();
+                }
+                self.shutdown = Some(Shutdown::Deadlocked);
+                return Err("Every program is waiting for input".into());
+            }
+        }
+
+        let result = self.input.read_line();
+
+        let mut state = self.shared.lock();
+        state.waiting[self.idx] = false;
+        if state.deadlocked {
+            // Nothing is sent once every program waits, so this read was woken up.
+            self.shutdown = Some(Shutdown::Deadlocked);
+            return Err("Every program is waiting for input".into());
+        }
+        match result {
+            Ok(value) => {
+                state.pending[self.idx] -= 1;
+                Ok(value)
+            }
+            Err(error) => {
+                self.shutdown = Some(Shutdown::InputClosed);
+                Err(error)
+            }
+        }
+    }
+}
+
+struct Writer {
+    output: ChannelWriter,
+    shared: Arc<Shared>,
+    target: usize,
+}
+
+impl LineWriter for Writer {
+    fn write_line(&mut self, value: i32) -> Result<(), Cow<'static, str>> {
+        let mut state = self.shared.lock();
+        self.output.write_line(value)?;
+        state.pending[self.target] += 1;
+
+        Ok(())
+    }
+}
+
+/// Runs every program on its own thread and feeds its outputs to the next program.
+///
+/// Each program is paired with the inputs that are queued for it before anything starts.
+/// The outputs of the last program are returned. A program that is waiting for input after
+/// the program before it halted is shut down instead of blocking forever.
+pub fn pipeline(programs: Vec<(Vec<i32>, Vec<i32>)>) -> Result<Completion, Cow<'static, str>> {
+    connect(programs, false)
+}
+
+/// Like `pipeline`, but the outputs of the last program are fed back into the first one.
+///
+/// The values the last program sent after the first one stopped reading are returned, which
+/// is where a feedback loop leaves its final signal. When every program that is still running
+/// waits for input, they are all shut down as deadlocked.
+pub fn ring(programs: Vec<(Vec<i32>, Vec<i32>)>) -> Result<Completion, Cow<'static, str>> {
+    connect(programs, true)
+}
+
+/// Connects the programs with the channels of `io::channel`. A program sees its input close
+/// once the program feeding it stopped, or, for the first program of a pipeline, once its
+/// inputs ran out. As a blocked read cannot tell a deadlock from a slow upstream, the programs
+/// also count what they send and wait for in a shared `State`.
+fn connect(
+    programs: Vec<(Vec<i32>, Vec<i32>)>,
+    ring: bool,
+) -> Result<Completion, Cow<'static, str>> {
+    if programs.is_empty() {
+        return Err("There should be at least 1 program".into());
+    }
+
+    let len = programs.len();
+    // Channel `idx` feeds program `idx`, and the last one holds the outputs of a pipeline.
+    let (mut writers, mut readers): (Vec<_>, Vec<_>) = (0..=len)
+        .map(|_| {
+            let (writer, reader) = channel::channel();
+            (Some(writer), Some(reader))
+        })
+        .unzip();
+    let mut pending = vec![0; len + 1];
+    for (idx, (_, inputs)) in programs.iter().enumerate() {
+        if let Some(writer) = &mut writers[idx] {
+            for input in inputs {
+                writer.write_line(*input)?;
+            }
+        }
+        pending[idx] = inputs.len();
+    }
+
+    let upstreams: Vec<_> = (0..len)
+        .map(|idx| match idx {
+            0 if ring => Some(len - 1),
+            0 => None,
+            _ => Some(idx - 1),
+        })
+        .collect();
+    let wakers = upstreams
+        .iter()
+        .zip(&writers)
+        .map(|(upstream, writer)| upstream.and(writer.clone()))
+        .collect();
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            running: vec![true; len],
+            waiting: vec![false; len],
+            pending,
+            upstreams,
+            wakers,
+            deadlocked: false,
+        }),
+    });
+
+    let workers: Vec<Worker> = programs
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (memory, _))| {
+            let target = if ring { (idx + 1) % len } else { idx + 1 };
+            let mut input = Reader {
+                input: readers[idx].take().unwrap(),
+                shared: shared.clone(),
+                idx,
+                shutdown: None,
+            };
+            let mut output = Writer {
+                output: writers[target].take().unwrap(),
+                shared: shared.clone(),
+                target,
+            };
+
+            thread::spawn(move || {
+                let result = Program::new(memory, &mut input, &mut output).run();
+                input.shared.stop(idx, target);
+                drop(output);
+
+                let shutdown = match (result, input.shutdown) {
+                    (Ok(()), _) => Ok(Shutdown::Halted),
+                    (Err(_), Some(shutdown)) => Ok(shutdown),
+                    (Err(error), None) => Err(error),
+                };
+                (shutdown, input.input)
+            })
+        })
+        .collect();
+    // Nothing feeds the first program of a pipeline once its inputs are queued.
+    drop(writers);
+
+    let mut results = Vec::with_capacity(len);
+    let mut inputs = Vec::with_capacity(len);
+    for (idx, worker) in workers.into_iter().enumerate() {
+        match worker.join() {
+            Ok((result, input)) => {
+                results.push(result);
+                inputs.push(input);
+            }
+            Err(_) => return Err(format!("Program {} panicked", idx).into()),
+        }
+    }
+
+    let mut shutdowns = Vec::with_capacity(len);
+    for (idx, result) in results.into_iter().enumerate() {
+        shutdowns.push(result.map_err(|error| format!("Program {} failed: {}", idx, error))?);
+    }
+
+    let outputs = match &mut readers[len] {
+        Some(output) if !ring => output.drain(),
+        _ => inputs[0].drain(),
+    };
+
+    Ok(Completion { outputs, shutdowns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback_loop(
+        program: Vec<i32>,
+        phase_settings: &[i32],
+    ) -> Result<Completion, Cow<'static, str>> {
+        let programs = phase_settings
+            .iter()
+            .enumerate()
+            .map(|(idx, phase_setting)| {
+                let inputs = if idx == 0 {
+                    vec![*phase_setting, 0]
+                } else {
+                    vec![*phase_setting]
+                };
+                (program.clone(), inputs)
+            })
+            .collect();
+
+        ring(programs)
+    }
+
+    #[test]
+    fn pipeline_of_amplifiers() {
+        let program = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        let programs = vec![
+            (program.clone(), vec![4, 0]),
+            (program.clone(), vec![3]),
+            (program.clone(), vec![2]),
+            (program.clone(), vec![1]),
+            (program, vec![0]),
+        ];
+
+        let completion = pipeline(programs).unwrap();
+
+        assert_eq!(completion.outputs, [43210]);
+        assert!(completion
+            .shutdowns
+            .iter()
+            .all(|shutdown| *shutdown == Shutdown::Halted));
+    }
+
+    #[test]
+    fn ring_example_1() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+
+        let completion = feedback_loop(program, &[9, 8, 7, 6, 5]).unwrap();
+
+        assert_eq!(completion.outputs, [139629729]);
+    }
+
+    #[test]
+    fn ring_example_2() {
+        let program = vec![
+            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
+            -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
+            53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+        ];
+
+        let completion = feedback_loop(program, &[9, 7, 8, 5, 6]).unwrap();
+
+        assert_eq!(completion.outputs, [18216]);
+    }
+
+    #[test]
+    fn blocked_program_shuts_down_when_upstream_halts() {
+        let programs = vec![(vec![104, 1, 99], vec![]), (vec![3, 0, 3, 0, 99], vec![])];
+
+        let completion = pipeline(programs).unwrap();
+
+        assert_eq!(completion.outputs, []);
+        assert_eq!(
+            completion.shutdowns,
+            [Shutdown::Halted, Shutdown::InputClosed]
+        );
+    }
+
+    #[test]
+    fn first_program_shuts_down_when_inputs_run_out() {
+        let programs = vec![(vec![3, 7, 4, 7, 1105, 1, 0, 0], vec![1, 2])];
+
+        let completion = pipeline(programs).unwrap();
+
+        assert_eq!(completion.outputs, [1, 2]);
+        assert_eq!(completion.shutdowns, [Shutdown::InputClosed]);
+    }
+
+    #[test]
+    fn ring_waiting_for_input_is_deadlocked() {
+        // The first program passes its input on and waits for a reply, while the second one
+        // waits for a second value.
+        let programs = vec![
+            (vec![3, 7, 4, 7, 3, 7, 99, 0], vec![1]),
+            (vec![3, 7, 3, 7, 4, 7, 99, 0], vec![]),
+        ];
+
+        let completion = ring(programs).unwrap();
+
+        assert_eq!(completion.outputs, []);
+        assert_eq!(
+            completion.shutdowns,
+            [Shutdown::Deadlocked, Shutdown::Deadlocked]
+        );
+    }
+
+    #[test]
+    fn ring_without_inputs_is_deadlocked() {
+        let programs = vec![(vec![3, 0, 99], vec![]), (vec![3, 0, 99], vec![])];
+
+        let completion = ring(programs).unwrap();
+
+        assert_eq!(
+            completion.shutdowns,
+            [Shutdown::Deadlocked, Shutdown::Deadlocked]
+        );
+    }
+
+    #[test]
+    fn failing_program_is_reported() {
+        let programs = vec![(vec![42], vec![]), (vec![99], vec![])];
+
+        assert!(pipeline(programs).is_err());
+    }
+
+    #[test]
+    fn empty_pipeline() {
+        assert!(pipeline(vec![]).is_err());
+    }
+}
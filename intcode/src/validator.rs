@@ -0,0 +1,545 @@
+//! Checks a program before running it.
+//!
+//! Every instruction reachable from address 0 is decoded, following jumps whose targets are
+//! immediate values and skipping the branch a constant condition never takes. Jumps to computed
+//! locations cannot be followed, so the code they reach is only checked when something else
+//! reaches it too, and neither is code the program writes at run time. A jump whose condition
+//! or target a reachable instruction writes to counts as computed, and both of its branches
+//! are followed as far as they are known. Instructions at an address that a reachable
+//! instruction writes to, or at any address once one writes relative to the relative base,
+//! are reported as rewritten instead of invalid, since they may well be valid by the time they
+//! run.
+
+use super::protection::Protection;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    InvalidOpcode(i32),
+    /// A mode digit that is not position, immediate or relative, or that is set for a parameter
+    /// the instruction does not have. Parameters are counted from 1.
+    IllegalMode {
+        parameter: usize,
+        mode: i32,
+    },
+    ImmediateDestination {
+        parameter: usize,
+    },
+    /// The instruction needs more words than are left in memory.
+    Truncated {
+        len: usize,
+    },
+    /// Execution continues past the end of memory, or jumps outside of it.
+    OutOfBounds {
+        target: i64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub address: usize,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address {}: ", self.address)?;
+
+        match &self.kind {
+            IssueKind::InvalidOpcode(opcode) => write!(f, "invalid opcode {}", opcode),
+            IssueKind::IllegalMode { parameter, mode } => {
+                write!(f, "illegal mode {} for parameter {}", mode, parameter)
+            }
+            IssueKind::ImmediateDestination { parameter } => {
+                write!(
+                    f,
+                    "parameter {} is a destination in immediate mode",
+                    parameter
+                )
+            }
+            IssueKind::Truncated { len } => {
+                write!(
+                    f,
+                    "instruction of length {} runs past the end of memory",
+                    len
+                )
+            }
+            IssueKind::OutOfBounds { target } => {
+                write!(f, "execution continues at {}, outside of memory", target)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    /// Every problem found, ordered by address.
+    pub issues: Vec<Issue>,
+    /// The addresses of the reachable instructions that decoded cleanly or with mode issues.
+    pub instructions: BTreeSet<usize>,
+    /// Jumps whose target is read from memory or whose operands the program writes, and that
+    /// could not be followed.
+    pub computed_jumps: Vec<usize>,
+    /// Instructions that did not decode, but are written by the program before they may run.
+    pub rewritten: Vec<usize>,
+    /// The addresses that reachable instructions write to in position mode.
+    pub writes: BTreeSet<usize>,
+    /// Instructions that write relative to the relative base, and so may write to any address.
+    pub relative_writes: Vec<usize>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// The memory that holds the reachable instructions, as sorted ranges that do not touch.
+pub fn code_regions(memory: &[i32]) -> Vec<Range<usize>> {
+    regions(memory, &validate(memory).instructions)
+}
+
+/// The protections that a program can run under unless it is broken: the reachable code is
+/// read-only, apart from the cells the program writes, and everything else is no-execute.
+///
+/// Code reached through a computed jump or written at run time cannot be told apart from data,
+/// so a program with either gets no no-execute memory at all. A program that writes relative
+/// to the relative base may write anywhere, so it gets no protection at all.
+pub fn protections(memory: &[i32]) -> Vec<(Range<usize>, Protection)> {
+    let report = validate(memory);
+    let code = regions(memory, &report.instructions);
+    let mut protections = Vec::new();
+
+    if !report.relative_writes.is_empty() {
+        return protections;
+    }
+
+    for region in &code {
+        let mut start = region.start;
+        for &address in report.writes.range(region.clone()) {
+            if start < address {
+                protections.push((start..address, Protection::ReadOnly));
+            }
+            start = address + 1;
+        }
+        if start < region.end {
+            protections.push((start..region.end, Protection::ReadOnly));
+        }
+    }
+
+    if report.is_valid() && report.computed_jumps.is_empty() && report.rewritten.is_empty() {
+        let mut start = 0;
+        for region in code.iter().chain(Some(&(memory.len()..memory.len()))) {
+            if start < region.start {
+                protections.push((start..region.start, Protection::NoExecute));
+            }
+            start = region.end;
+        }
+    }
+
+    protections
+}
+
+fn regions(memory: &[i32], instructions: &BTreeSet<usize>) -> Vec<Range<usize>> {
+    let mut regions: Vec<Range<usize>> = Vec::new();
+
+    for &address in instructions {
+        let (arity, _) = shape(memory[address] % 100).expect("only valid opcodes are reachable");
+        let end = address + arity + 1;
+
+        match regions.last_mut() {
+            Some(last) if last.end >= address => last.end = last.end.max(end),
+            _ => regions.push(address..end),
+        }
+    }
+
+    regions
+}
+
+/// The number of parameters of each built-in opcode and the position of its destination.
+fn shape(opcode: i32) -> Option<(usize, Option<usize>)> {
+    match opcode {
+        1 | 2 | 7 | 8 => Some((3, Some(3))),
+        3 => Some((1, Some(1))),
+        4 => Some((1, None)),
+        5 | 6 => Some((2, None)),
+        9 => Some((1, None)),
+        99 => Some((0, None)),
+        _ => None,
+    }
+}
+
+pub fn validate(memory: &[i32]) -> Report {
+    validate_from(memory, &[0])
+}
+
+/// Like `validate`, but follows the code reachable from any of `entry_points`.
+pub fn validate_from(memory: &[i32], entry_points: &[usize]) -> Report {
+    validate_with_writes(memory, entry_points, BTreeSet::new())
+}
+
+/// Like `validate_from`, but treats `writes` as written by code that is not part of `memory`,
+/// such as the residual block of a `Specialiser`.
+pub fn validate_with_writes(
+    memory: &[i32],
+    entry_points: &[usize],
+    mut writes: BTreeSet<usize>,
+) -> Report {
+    // Which jumps are computed depends on the writes, which are only known once the code is
+    // walked, so walk it again until no new writes turn up.
+    loop {
+        let report = walk(memory, entry_points, &writes);
+        if report.writes == writes {
+            return report;
+        }
+        writes = report.writes;
+    }
+}
+
+/// Walks the code reachable from `entry_points`, treating jumps with an operand in `writes`
+/// as computed. The writes of the report include `writes`.
+fn walk(memory: &[i32], entry_points: &[usize], writes: &BTreeSet<usize>) -> Report {
+    let mut report = Report {
+        writes: writes.clone(),
+        ..Report::default()
+    };
+    let mut issues = BTreeMap::new();
+    let mut pending: Vec<_> = entry_points
+        .iter()
+        .rev()
+        .map(|&address| (address as i64, address))
+        .collect();
+
+    while let Some((address, from)) = pending.pop() {
+        let address = match usize::try_from(address) {
+            Ok(address) if address < memory.len() => address,
+            _ => {
+                issues.insert(
+                    (from, 0),
+                    Issue {
+                        address: from,
+                        kind: IssueKind::OutOfBounds { target: address },
+                    },
+                );
+                continue;
+            }
+        };
+        if report.instructions.contains(&address) {
+            continue;
+        }
+
+        let word = memory[address];
+        let (arity, destination) = match shape(word % 100) {
+            Some(shape) if word >= 0 => shape,
+            _ => {
+                issues.insert(
+                    (address, 0),
+                    Issue {
+                        address,
+                        kind: IssueKind::InvalidOpcode(word),
+                    },
+                );
+                continue;
+            }
+        };
+
+        let mut issue = |kind| {
+            let order = issues.len() + 1;
+            issues.insert((address, order), Issue { address, kind });
+        };
+
+        let mut modes = word / 100;
+        for parameter in 1..=10 {
+            let mode = modes % 10;
+            modes /= 10;
+
+            if mode > 2 || (parameter > arity && mode != 0) {
+                issue(IssueKind::IllegalMode { parameter, mode });
+            } else if destination == Some(parameter) && mode == 1 {
+                issue(IssueKind::ImmediateDestination { parameter });
+            }
+
+            if modes == 0 {
+                break;
+            }
+        }
+
+        let len = arity + 1;
+        let operands = match memory.get(address + 1..address + len) {
+            Some(operands) => operands,
+            None => {
+                issue(IssueKind::Truncated { len });
+                continue;
+            }
+        };
+        report.instructions.insert(address);
+        // Writes relative to the relative base go to addresses only known at run time.
+        if let Some(destination) = destination {
+            match (word / 10i32.pow(destination as u32 + 1)) % 10 {
+                0 => {
+                    if let Ok(address) = usize::try_from(operands[destination - 1]) {
+                        report.writes.insert(address);
+                    }
+                }
+                2 => report.relative_writes.push(address),
+                _ => {}
+            }
+        }
+
+        let next = (address + len) as i64;
+        match word % 100 {
+            99 => {}
+            opcode @ (5 | 6) => {
+                let condition_written = writes.contains(&(address + 1));
+                let target_written = writes.contains(&(address + 2));
+                if condition_written || target_written {
+                    report.computed_jumps.push(address);
+                }
+
+                let condition = match (word / 100) % 10 {
+                    1 if !condition_written => Some(operands[0] != 0),
+                    _ => None,
+                };
+                let taken = condition.map(|condition| condition == (opcode == 5));
+
+                if taken != Some(false) {
+                    match (word / 1000) % 10 {
+                        1 if !target_written => pending.push((i64::from(operands[1]), address)),
+                        _ => report.computed_jumps.push(address),
+                    }
+                }
+                if taken != Some(true) {
+                    pending.push((next, address));
+                }
+            }
+            _ => pending.push((next, address)),
+        }
+    }
+
+    for issue in issues.into_values() {
+        match issue.kind {
+            IssueKind::OutOfBounds { .. } => report.issues.push(issue),
+            _ if report.writes.contains(&issue.address) || !report.relative_writes.is_empty() => {
+                report.rewritten.push(issue.address)
+            }
+            _ => report.issues.push(issue),
+        }
+    }
+    report.rewritten.dedup();
+    report.relative_writes.sort_unstable();
+    report.computed_jumps.sort_unstable();
+    report.computed_jumps.dedup();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::testing::{UnitTestInput, UnitTestOutput};
+    use crate::Program;
+
+    #[test]
+    fn valid_program() {
+        let report = validate(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+
+        assert!(report.is_valid());
+        assert_eq!(report.instructions, [0, 2, 6, 8].iter().copied().collect());
+    }
+
+    #[test]
+    fn data_after_halt_is_not_checked() {
+        assert!(validate(&[1101, 1, 2, 5, 99, 12345, -7]).is_valid());
+    }
+
+    #[test]
+    fn reports_every_issue_in_one_pass() {
+        // An immediate destination, an unsupported mode and, through a jump, an invalid opcode.
+        let memory = [11101, 1, 2, 3, 304, 0, 1105, 1, 11, 99, 99, 42];
+        let report = validate(&memory);
+
+        assert_eq!(
+            report.issues,
+            [
+                Issue {
+                    address: 0,
+                    kind: IssueKind::ImmediateDestination { parameter: 3 }
+                },
+                Issue {
+                    address: 4,
+                    kind: IssueKind::IllegalMode {
+                        parameter: 1,
+                        mode: 3
+                    }
+                },
+                Issue {
+                    address: 11,
+                    kind: IssueKind::InvalidOpcode(42)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_modes_are_valid() {
+        // Adjusts the relative base, reads and writes through it, and jumps through it.
+        let report = validate(&[109, 9, 22201, 0, 1, 2, 2205, 0, 1, 99]);
+
+        assert!(report.is_valid());
+        assert_eq!(report.instructions, [0, 2, 6, 9].iter().copied().collect());
+        assert_eq!(report.computed_jumps, [6]);
+    }
+
+    #[test]
+    fn modes_for_missing_parameters_are_illegal() {
+        let report = validate(&[10099]);
+
+        assert_eq!(
+            report.issues,
+            [Issue {
+                address: 0,
+                kind: IssueKind::IllegalMode {
+                    parameter: 3,
+                    mode: 1
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn truncated_instruction_at_the_end() {
+        let report = validate(&[1101, 1, 2]);
+
+        assert_eq!(
+            report.issues,
+            [Issue {
+                address: 0,
+                kind: IssueKind::Truncated { len: 4 }
+            }]
+        );
+        assert_eq!(
+            report.issues[0].to_string(),
+            "Address 0: instruction of length 4 runs past the end of memory"
+        );
+    }
+
+    #[test]
+    fn running_off_the_end_and_jumping_outside() {
+        let report = validate(&[1105, 1, 20]);
+        assert_eq!(report.issues[0].kind, IssueKind::OutOfBounds { target: 20 });
+
+        let report = validate(&[1101, 1, 2, 0]);
+        assert_eq!(report.issues[0].kind, IssueKind::OutOfBounds { target: 4 });
+    }
+
+    #[test]
+    fn constant_conditions_skip_the_branch_not_taken() {
+        // The jump is never taken, so its target outside of memory does not matter.
+        assert!(validate(&[1106, 1, 100, 99]).is_valid());
+        // The jump is always taken, so the invalid opcode after it is never reached.
+        assert!(validate(&[1105, 1, 4, 0, 99]).is_valid());
+    }
+
+    #[test]
+    fn rewritten_instructions_are_not_issues() {
+        // Turns the invalid opcode 1100 at address 4 into 1101 before running it.
+        let report = validate(&[1101, 1, 1100, 4, 1100, 2, 3, 0, 99]);
+
+        assert!(report.is_valid());
+        assert_eq!(report.rewritten, [4]);
+    }
+
+    #[test]
+    fn code_regions_cover_reachable_instructions() {
+        let memory = [1105, 1, 7, 12, 13, 14, 15, 1101, 1, 2, 3, 99, 0];
+
+        assert_eq!(code_regions(&memory), [0..3, 7..12]);
+    }
+
+    #[test]
+    fn protections_split_code_and_data() {
+        // Stores into its own `add` operand at 9, and halts before the data at 13.
+        let memory = [1101, 1, 2, 13, 1101, 9, 0, 9, 1, 13, 13, 13, 99, 0];
+
+        assert_eq!(
+            protections(&memory),
+            [
+                (0..9, Protection::ReadOnly),
+                (10..13, Protection::ReadOnly),
+                (13..14, Protection::NoExecute),
+            ]
+        );
+    }
+
+    #[test]
+    fn computed_jumps_leave_data_executable() {
+        // Jumps over the data at 3, then jumps to the address stored there if it is 0.
+        let memory = [1105, 1, 4, 0, 6, 3, 0, 99];
+
+        assert_eq!(
+            protections(&memory),
+            [(0..3, Protection::ReadOnly), (4..8, Protection::ReadOnly)]
+        );
+    }
+
+    #[test]
+    fn written_jump_operands_make_the_jump_computed() {
+        // Reads the condition of the jump at 2 from the input, so both branches may run.
+        let memory = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        let report = validate(&memory);
+
+        assert!(report.is_valid());
+        assert_eq!(report.computed_jumps, [2]);
+        assert_eq!(
+            report.instructions,
+            [0, 2, 5, 9, 11].iter().copied().collect()
+        );
+        assert_eq!(
+            protections(&memory),
+            [(0..3, Protection::ReadOnly), (4..12, Protection::ReadOnly)]
+        );
+
+        let mut input = UnitTestInput::new(vec![0]);
+        let mut output = UnitTestOutput::new(vec![0]);
+        let mut program = Program::new(memory.to_vec(), &mut input, &mut output);
+        for (range, protection) in protections(&memory) {
+            program.protect(range, protection);
+        }
+
+        assert_eq!(program.run(), Ok(()));
+    }
+
+    #[test]
+    fn writes_from_elsewhere_make_the_jump_computed() {
+        // The jump at 2 of the program above, with its condition written before it starts.
+        let memory = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        let report = validate_with_writes(&memory, &[2], [3].iter().copied().collect());
+
+        assert!(report.is_valid());
+        assert_eq!(report.computed_jumps, [2]);
+        assert_eq!(report.instructions, [2, 5, 9, 11].iter().copied().collect());
+        assert_eq!(report.writes, [3, 12].iter().copied().collect());
+    }
+
+    #[test]
+    fn relative_writes_may_write_anywhere() {
+        // Writes 1101 through the relative base over the invalid opcode at 6 before running it.
+        let memory = [109, 6, 21101, 1100, 1, 0, 1100, 1, 2, 0, 99];
+        let report = validate(&memory);
+
+        assert!(report.is_valid());
+        assert_eq!(report.relative_writes, [2]);
+        assert_eq!(report.rewritten, [6]);
+        assert_eq!(protections(&memory), []);
+    }
+
+    #[test]
+    fn computed_jumps_are_reported_but_not_followed() {
+        let report = validate(&[5, 4, 5, 99, 6, 0]);
+
+        assert!(report.is_valid());
+        assert_eq!(report.computed_jumps, [0]);
+    }
+}